@@ -0,0 +1,455 @@
+use crate::{register_command_with_eol, CallbackResult, Command, EatMode, Priority};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+#[macro_export(local_inner_macros)]
+#[doc(hidden)]
+macro_rules! __command_args_internal {
+    (@TY flag) => { bool };
+    (@TY arg $T:ty) => { $T };
+    (@TY opt $T:ty) => { ::std::option::Option<$T> };
+    (@TY rest) => { ::std::option::Option<::std::string::String> };
+    (@KIND flag) => { $crate::ArgKind::Flag };
+    (@KIND arg) => { $crate::ArgKind::Arg };
+    (@KIND opt) => { $crate::ArgKind::Opt };
+    (@KIND rest) => { $crate::ArgKind::Rest };
+    (@CONVERT flag $v:expr, $name:expr) => { $v.is_some() };
+    (@CONVERT arg $v:expr, $name:expr, $T:ty) => {
+        match $v {
+            Some(value) => value
+                .parse::<$T>()
+                .map_err(|_| $crate::ArgsError::Invalid { name: $name, value })?,
+            None => return Err($crate::ArgsError::Missing($name)),
+        }
+    };
+    (@CONVERT opt $v:expr, $name:expr, $T:ty) => {
+        match $v {
+            Some(value) => Some(
+                value
+                    .parse::<$T>()
+                    .map_err(|_| $crate::ArgsError::Invalid { name: $name, value })?,
+            ),
+            None => None,
+        }
+    };
+    (@CONVERT rest $v:expr, $name:expr) => { $v };
+    (@CHECK $state:ident) => {};
+    (@CHECK $state:ident flag $field:ident $($tail:tt)*) => {
+        __command_args_internal!(@CHECK $state $($tail)*);
+    };
+    (@CHECK required arg $field:ident $($tail:tt)*) => {
+        __command_args_internal!(@CHECK required $($tail)*);
+    };
+    (@CHECK optional arg $field:ident $($tail:tt)*) => {
+        ::std::compile_error!(::std::concat!(
+            "required argument '",
+            ::std::stringify!($field),
+            "' follows an optional one"
+        ));
+    };
+    (@CHECK $state:ident opt $field:ident $($tail:tt)*) => {
+        __command_args_internal!(@CHECK optional $($tail)*);
+    };
+    (@CHECK $state:ident rest $field:ident) => {};
+    (@CHECK $state:ident rest $field:ident $($tail:tt)+) => {
+        ::std::compile_error!(::std::concat!(
+            "rest argument '",
+            ::std::stringify!($field),
+            "' is not the last field"
+        ));
+    };
+}
+
+/// A macro for declaring a struct of command arguments and implementing `CommandArgs` for it.
+///
+/// This takes the place of a `#[derive(CommandArgs)]`, which would have to live in a separate
+/// proc-macro crate for every plugin to depend on. Instead the struct is written inside the macro,
+/// with each field marked by its kind.
+///
+/// Each field is one of four kinds, and any doc comment on it is used as its help text, with its
+/// lines joined by spaces:
+///
+/// * `flag name("-f", "--flag")` - a `bool` that is `true` if any of the listed flags was passed.
+///   Flags may appear anywhere before the rest-of-line argument, and `--` ends flag parsing.
+/// * `arg name: T` - a required positional argument, parsed with `FromStr`.
+/// * `opt name: T` - an `Option<T>` positional argument, which may only be followed by other
+///   optional ones.
+/// * `rest name` - an `Option<String>` containing the remainder of the line after the positional
+///   arguments, with its original spacing intact. Must be the last field.
+///
+/// An `arg` after an `opt`, or a `rest` that is not the last field, is a compile error:
+///
+/// ```rust,compile_fail
+/// use hexchat::command_args;
+///
+/// command_args! {
+///     pub struct KickArgs {
+///         opt channel: String,
+///         arg nick: String,
+///     }
+/// }
+/// ```
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use hexchat::{command_args, register_command_with_args, EatMode, Priority};
+///
+/// command_args! {
+///     /// Arguments for `/greet`.
+///     pub struct GreetArgs {
+///         /// Greets loudly.
+///         flag loud("-l", "--loud"),
+///         /// The nick to greet.
+///         arg nick: String,
+///         /// How many times to greet them.
+///         opt times: u32,
+///         /// The greeting to use.
+///         rest greeting,
+///     }
+/// }
+///
+/// register_command_with_args("greet", "Greets someone.", Priority::NORMAL, |args: GreetArgs| {
+///     //...
+///     EatMode::All
+/// });
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! command_args {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $N:ident {
+            $(
+                $(#[doc = $help:expr])*
+                $kind:ident $field:ident $(($($flag:expr),+))? $(: $T:ty)?
+            ),* $(,)?
+        }
+    ) => {
+        __command_args_internal!(@CHECK required $($kind $field)*);
+
+        $(#[$attr])*
+        $vis struct $N {
+            $(
+                $(#[doc = $help])*
+                pub $field: __command_args_internal!(@TY $kind $($T)?),
+            )*
+        }
+
+        impl $crate::CommandArgs for $N {
+            const SPEC: &'static [$crate::ArgSpec] = &[
+                $(
+                    $crate::ArgSpec {
+                        kind: __command_args_internal!(@KIND $kind),
+                        name: ::std::stringify!($field),
+                        flags: &[$($($flag),+)?],
+                        help: &[$($help),*],
+                    },
+                )*
+            ];
+            fn from_values(
+                values: ::std::vec::Vec<::std::option::Option<::std::string::String>>,
+            ) -> ::std::result::Result<Self, $crate::ArgsError> {
+                let mut values = values.into_iter();
+                Ok(Self {
+                    $(
+                        $field: __command_args_internal!(
+                            @CONVERT $kind values.next().unwrap(),
+                            ::std::stringify!($field) $(, $T)?
+                        ),
+                    )*
+                })
+            }
+        }
+    };
+}
+
+/// A set of typed arguments to a command. Used with `register_command_with_args`. Rather than
+/// implementing this yourself, you should use the `command_args!` macro.
+pub trait CommandArgs
+where
+    Self: Sized,
+{
+    #[doc(hidden)]
+    const SPEC: &'static [ArgSpec];
+    #[doc(hidden)]
+    fn from_values(values: Vec<Option<String>>) -> Result<Self, ArgsError>;
+    /// Parses the arguments from a command's words and words-to-end-of-line. As with
+    /// `register_command`, index 0 of each should be the name of the command.
+    fn parse(word: &[String], word_eol: &[String]) -> Result<Self, ArgsError> {
+        Self::from_values(parse_values(Self::SPEC, word, word_eol)?)
+    }
+    /// Gets the usage string for these arguments, as used for the command's help text. The first
+    /// line is a synopsis, and each following line describes one argument.
+    fn usage(command: &str) -> String {
+        let mut usage = format!("Usage: /{}", command.to_uppercase());
+        let mut lines = Vec::new();
+        for spec in Self::SPEC {
+            let label = match spec.kind {
+                ArgKind::Flag => {
+                    usage.push_str(&format!(" [{}]", spec.flags.join("|")));
+                    spec.flags.join(", ")
+                }
+                ArgKind::Arg => {
+                    usage.push_str(&format!(" <{}>", spec.name));
+                    spec.name.to_string()
+                }
+                ArgKind::Opt => {
+                    usage.push_str(&format!(" [{}]", spec.name));
+                    spec.name.to_string()
+                }
+                ArgKind::Rest => {
+                    usage.push_str(&format!(" [{}...]", spec.name));
+                    spec.name.to_string()
+                }
+            };
+            let help = spec
+                .help
+                .iter()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>();
+            if !help.is_empty() {
+                lines.push((label, help.join(" ")));
+            }
+        }
+        let width = lines.iter().map(|(l, _)| l.len()).max().unwrap_or(0);
+        for (label, help) in lines {
+            usage.push_str(&format!("\n  {:width$}  {}", label, help, width = width));
+        }
+        usage
+    }
+}
+
+#[doc(hidden)]
+pub struct ArgSpec {
+    pub kind: ArgKind,
+    pub name: &'static str,
+    pub flags: &'static [&'static str],
+    pub help: &'static [&'static str],
+}
+
+#[doc(hidden)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ArgKind {
+    Flag,
+    Arg,
+    Opt,
+    Rest,
+}
+
+fn parse_values(
+    spec: &[ArgSpec],
+    word: &[String],
+    word_eol: &[String],
+) -> Result<Vec<Option<String>>, ArgsError> {
+    let mut values = vec![None; spec.len()];
+    let positional = (0..spec.len())
+        .filter(|&i| spec[i].kind == ArgKind::Arg || spec[i].kind == ArgKind::Opt)
+        .collect::<Vec<_>>();
+    let rest = spec.iter().position(|s| s.kind == ArgKind::Rest);
+    let mut next = 0;
+    let mut flags_done = false;
+    for (i, word) in word.iter().enumerate().skip(1) {
+        if word.is_empty() {
+            break;
+        }
+        if !flags_done {
+            if word == "--" {
+                flags_done = true;
+                continue;
+            }
+            let flag = spec
+                .iter()
+                .position(|s| s.kind == ArgKind::Flag && s.flags.contains(&&**word));
+            if let Some(flag) = flag {
+                values[flag] = Some(String::new());
+                continue;
+            }
+        }
+        if next < positional.len() {
+            values[positional[next]] = Some(word.clone());
+            next += 1;
+        } else if let Some(rest) = rest {
+            values[rest] = word_eol.get(i).cloned();
+            break;
+        } else {
+            return Err(ArgsError::TooMany(
+                word_eol.get(i).cloned().unwrap_or_default(),
+            ));
+        }
+    }
+    for &i in &positional[next..] {
+        if spec[i].kind == ArgKind::Arg {
+            return Err(ArgsError::Missing(spec[i].name));
+        }
+    }
+    Ok(values)
+}
+
+/// An error produced while parsing `CommandArgs`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ArgsError {
+    /// A required argument was not provided.
+    Missing(&'static str),
+    /// An argument could not be parsed.
+    Invalid {
+        /// The name of the argument.
+        name: &'static str,
+        /// The value that could not be parsed.
+        value: String,
+    },
+    /// More arguments were provided than expected. Contains the unexpected remainder of the line.
+    TooMany(String),
+}
+
+impl Display for ArgsError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            ArgsError::Missing(name) => write!(f, "Missing argument '{}'", name),
+            ArgsError::Invalid { name, value } => {
+                write!(f, "Invalid value '{}' for argument '{}'", value, name)
+            }
+            ArgsError::TooMany(rest) => write!(f, "Unexpected arguments '{}'", rest),
+        }
+    }
+}
+
+impl Error for ArgsError {}
+
+/// Registers a new command with typed arguments, as defined in `register_command`. The command's
+/// help text is generated from `T`'s usage string, preceded by `description`.
+///
+/// Returns a corresponding object that can be passed to `deregister_command`.
+///
+/// # Callback
+///
+/// The callback's signature is the parsed arguments. If the arguments fail to parse, the error and
+/// usage string are printed to the current tab instead, and the command is eaten. The callback
+/// should return who the command event should be hidden from.
+pub fn register_command_with_args<T, R>(
+    name: &str,
+    description: &str,
+    priority: Priority,
+    function: impl Fn(T) -> R + 'static,
+) -> Command
+where
+    T: CommandArgs,
+    R: CallbackResult<EatMode>,
+{
+    let usage = T::usage(name);
+    let help_text = if description.is_empty() {
+        usage.clone()
+    } else {
+        format!("{}\n{}", description, usage)
+    };
+    register_command_with_eol(name, &help_text, priority, move |args| {
+        match T::parse(args.words(), args.word_eols()) {
+            Ok(args) => function(args).into_result(),
+            Err(e) => {
+                crate::print_plain(&e.to_string());
+                crate::print_plain(&usage);
+                Ok(EatMode::All)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::command_args! {
+        struct GreetArgs {
+            /// Greets loudly.
+            flag loud("-l", "--loud"),
+            /// The nick to greet, which
+            /// may be a channel.
+            arg nick: String,
+            /// How many times to greet them.
+            opt times: u32,
+            /// The greeting to use.
+            rest greeting,
+        }
+    }
+
+    crate::command_args! {
+        struct KickArgs {
+            arg nick: String,
+        }
+    }
+
+    fn parse<T: CommandArgs>(line: &str) -> Result<T, ArgsError> {
+        let mut word = line.split(' ').map(ToString::to_string).collect::<Vec<_>>();
+        let mut word_eol = (0..word.len())
+            .map(|i| word[i..].join(" "))
+            .collect::<Vec<_>>();
+        word.resize(32, String::new());
+        word_eol.resize(32, String::new());
+        T::parse(&word, &word_eol)
+    }
+
+    #[test]
+    fn flags() {
+        let args = parse::<GreetArgs>("greet bob").unwrap();
+        assert!(!args.loud);
+        let args = parse::<GreetArgs>("greet -l bob").unwrap();
+        assert!(args.loud);
+        let args = parse::<GreetArgs>("greet bob --loud").unwrap();
+        assert!(args.loud);
+        assert_eq!(args.nick, "bob");
+    }
+
+    #[test]
+    fn double_dash() {
+        let args = parse::<GreetArgs>("greet -- -l 2 --loud").unwrap();
+        assert!(!args.loud);
+        assert_eq!(args.nick, "-l");
+        assert_eq!(args.times, Some(2));
+        assert_eq!(args.greeting.as_ref().map(String::as_str), Some("--loud"));
+    }
+
+    #[test]
+    fn optional_and_rest() {
+        let args = parse::<GreetArgs>("greet bob").unwrap();
+        assert_eq!(args.times, None);
+        assert_eq!(args.greeting, None);
+        let args = parse::<GreetArgs>("greet bob 3 good  morning to you").unwrap();
+        assert_eq!(args.times, Some(3));
+        assert_eq!(
+            args.greeting.as_ref().map(String::as_str),
+            Some("good  morning to you")
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse::<GreetArgs>("greet").err(),
+            Some(ArgsError::Missing("nick"))
+        );
+        assert_eq!(
+            parse::<GreetArgs>("greet bob lots").err(),
+            Some(ArgsError::Invalid {
+                name: "times",
+                value: "lots".to_string()
+            })
+        );
+        assert_eq!(
+            parse::<KickArgs>("kick bob now please").err(),
+            Some(ArgsError::TooMany("now please".to_string()))
+        );
+    }
+
+    #[test]
+    fn usage() {
+        assert_eq!(
+            GreetArgs::usage("greet"),
+            "Usage: /GREET [-l|--loud] <nick> [times] [greeting...]\n  \
+             -l, --loud  Greets loudly.\n  \
+             nick        The nick to greet, which may be a channel.\n  \
+             times       How many times to greet them.\n  \
+             greeting    The greeting to use."
+        );
+        assert_eq!(KickArgs::usage("kick"), "Usage: /KICK <nick>");
+    }
+}
@@ -3,7 +3,7 @@
 /// # Example
 ///
 /// ```rust
-/// use hexchat::{Plugin, plugin}
+/// use hexchat::{plugin, Plugin};
 /// struct MyPlugin;
 /// impl Plugin for MyPlugin {
 ///     //...
@@ -44,21 +44,26 @@ macro_rules! plugin {
     };
 }
 
-use parking_lot::{MappedRwLockWriteGuard, RwLock, RwLockWriteGuard};
+use parking_lot::{MappedRwLockWriteGuard, Mutex, RwLock, RwLockWriteGuard};
 use std::any::Any;
 use std::collections::HashSet;
 use std::mem;
 use std::os::raw::{c_char, c_int};
-use std::panic;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
 
+use crate::thread::ThreadEntry;
 use crate::{
-    c, to_cstring, Command, Plugin, PrintEventListener, RawServerEventListener,
-    ServerEventListener, TimerTask, WindowEventListener, ALLOCATED, EXITING,
+    c, from_cstring_opt, to_cstring, CallbackKind, Command, FdWatcher, Plugin, PluginContext,
+    PrintEventListener, RawServerEventListener, ServerEventListener, TimerTask,
+    WindowEventListener, ALLOCATED, EXITING,
 };
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 static PLUGIN: RwLock<Option<PluginDef>> = RwLock::new(None);
 static PLUGIN_INSTANCE: RwLock<Option<PluginInstance>> = RwLock::new(None);
+static LOADED_TIMER: Mutex<Option<TimerTask>> = Mutex::new(None);
+static UNLOADED_BY_ON_LOADED: AtomicBool = AtomicBool::new(false);
 unsafe impl Sync for PluginDef {}
 unsafe impl Send for PluginDef {}
 
@@ -74,11 +79,26 @@ struct PluginInstance(Box<dyn Any>);
 unsafe impl Send for PluginInstance {}
 unsafe impl Sync for PluginInstance {}
 
-#[allow(dead_code)]
 pub(crate) fn is_loaded() -> bool {
     PLUGIN.read().is_some()
 }
 
+pub(crate) fn is_registered(f: impl FnOnce(&PluginDef) -> bool) -> bool {
+    PLUGIN.read().as_ref().map_or(false, f)
+}
+
+/// Gets whether a hook was registered by the crate itself, rather than by the plugin.
+#[cfg(any(feature = "diagnostics", feature = "testing"))]
+pub(crate) fn is_internal_hook(hook: *mut c::hexchat_hook) -> bool {
+    #[cfg(feature = "profiling")]
+    {
+        if crate::profiling::is_internal(hook) {
+            return true;
+        }
+    }
+    crate::server_features::is_internal(hook) || crate::whox::is_internal(hook)
+}
+
 pub(crate) struct PluginDef {
     pub(crate) ph: *mut c::hexchat_plugin,
     pub(crate) commands: HashSet<Command>,
@@ -87,6 +107,24 @@ pub(crate) struct PluginDef {
     pub(crate) server_events: HashSet<RawServerEventListener>,
     pub(crate) timer_tasks: HashSet<TimerTask>,
     pub(crate) typed_server_events: HashSet<ServerEventListener>,
+    pub(crate) fd_watchers: HashSet<FdWatcher>,
+    pub(crate) threads: Vec<ThreadEntry>,
+}
+
+impl PluginDef {
+    fn new(ph: *mut c::hexchat_plugin) -> Self {
+        Self {
+            commands: HashSet::new(),
+            print_events: HashSet::new(),
+            window_events: HashSet::new(),
+            server_events: HashSet::new(),
+            timer_tasks: HashSet::new(),
+            typed_server_events: HashSet::new(),
+            fd_watchers: HashSet::new(),
+            threads: Vec::new(),
+            ph,
+        }
+    }
 }
 
 pub unsafe fn hexchat_plugin_init<T>(
@@ -94,23 +132,14 @@ pub unsafe fn hexchat_plugin_init<T>(
     plugin_name: *mut *const c_char,
     plugin_desc: *mut *const c_char,
     plugin_version: *mut *const c_char,
-    _arg: *mut c_char,
+    arg: *mut c_char,
 ) -> c_int
 where
     T: Plugin + 'static,
 {
     {
         *ALLOCATED.write() = Some(Vec::new());
-        let plugin_def = PluginDef {
-            commands: HashSet::new(),
-            print_events: HashSet::new(),
-            window_events: HashSet::new(),
-            server_events: HashSet::new(),
-            timer_tasks: HashSet::new(),
-            typed_server_events: HashSet::new(),
-            ph: plugin_handle,
-        };
-        *PLUGIN.write() = Some(plugin_def);
+        *PLUGIN.write() = Some(PluginDef::new(plugin_handle));
     }
     let name = to_cstring(T::NAME);
     *plugin_name = name.into_raw();
@@ -118,51 +147,170 @@ where
     *plugin_desc = desc.into_raw();
     let version = to_cstring(T::VERSION);
     *plugin_version = version.into_raw();
-    let t = panic::catch_unwind(T::new);
-    let t = match t {
-        Ok(t) => t,
+    let reload_state = crate::reload::load(T::NAME, T::STATE_VERSION);
+    let context = PluginContext::new(from_cstring_opt(arg), reload_state);
+    let t = match panic::catch_unwind(|| T::init(&context)) {
+        Ok(Ok(t)) => t,
+        Ok(Err(e)) => {
+            crate::error::report(
+                CallbackKind::PluginInit,
+                &format!("{} {}", T::NAME, T::VERSION),
+                Some(e.to_string()),
+                false,
+            );
+            return abort_init();
+        }
         Err(e) => {
-            crate::send_command(&if let Some(string) = (*e).downcast_ref::<&str>() {
-                format!(
-                    r#"GUI MSGBOX "Plugin '{} {}' failed to load. Panic message: {}""#,
-                    T::NAME,
-                    T::VERSION,
-                    string
-                )
-            } else if let Some(string) = (*e).downcast_ref::<String>() {
-                format!(
-                    r#"GUI MSGBOX "Plugin '{} {}' failed to load. Panic message: {}""#,
-                    T::NAME,
-                    T::VERSION,
-                    string
-                )
-            } else {
-                format!(
-                    r#"GUI MSGBOX "Plugin '{} {}' failed to load.""#,
-                    T::NAME,
-                    T::VERSION
-                )
-            });
-            return -4;
+            crate::error::report(
+                CallbackKind::PluginInit,
+                &format!("{} {}", T::NAME, T::VERSION),
+                crate::error::panic_message(&*e),
+                true,
+            );
+            return abort_init();
         }
     };
     *PLUGIN_INSTANCE.write() = Some(PluginInstance(Box::new(t)));
+    crate::server_features::init();
+    #[cfg(feature = "profiling")]
+    crate::profiling::init(T::NAME);
+    // HexChat only finishes registering the plugin once this function returns.
+    let timer = crate::add_timer_once(Duration::from_millis(0), || {
+        LOADED_TIMER.lock().take();
+        // Taken out of the lock, in case `on_loaded` does something that unloads the plugin.
+        let mut instance = match PLUGIN_INSTANCE.write().take() {
+            Some(instance) => instance.0.downcast::<T>().unwrap(),
+            None => return,
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| instance.on_loaded()));
+        if UNLOADED_BY_ON_LOADED.swap(false, Ordering::SeqCst) {
+            // Everything else was already torn down by `hexchat_plugin_deinit`, and with the API
+            // gone, there is nowhere left to report a panic to.
+            let _ = panic::catch_unwind(AssertUnwindSafe(move || drop(instance)));
+            return;
+        }
+        let name = format!("{} {}", T::NAME, T::VERSION);
+        crate::error::handle(CallbackKind::PluginInit, &name, result.map(Ok), ());
+        *PLUGIN_INSTANCE.write() = Some(PluginInstance(instance));
+    });
+    *LOADED_TIMER.lock() = Some(timer);
     1
 }
 
 pub unsafe fn hexchat_plugin_deinit<T>(_plugin_handle: *mut c::hexchat_plugin) -> c_int
 where
-    T: Plugin,
+    T: Plugin + 'static,
 {
-    let mut plugin = None;
-    let mut instance = None;
-    let mut write = PLUGIN.write();
-    let mut plugin_write = PLUGIN_INSTANCE.write();
-    mem::swap(&mut plugin, &mut *write);
-    mem::swap(&mut instance, &mut *plugin_write);
-    let plugin = match plugin {
-        Some(p) => p,
-        None => return 1,
+    if !is_loaded() {
+        return 1;
+    }
+    let instance = PLUGIN_INSTANCE.write().take();
+    let mut instance = instance.map(|i| i.0.downcast::<T>().unwrap());
+    let name = format!("{} {}", T::NAME, T::VERSION);
+    // The plugin stays registered throughout, so that the API can be used while unloading.
+    if let Some(instance) = &mut instance {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| instance.save_state()));
+        let result = result.map(|state| match state {
+            Some(state) => crate::reload::save(T::NAME, T::STATE_VERSION, &state)
+                .map_err(|e| format!("Could not save reload state: {}", e)),
+            None => Ok(()),
+        });
+        crate::error::handle(CallbackKind::PluginDeinit, &name, result, ());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| instance.on_unloading()));
+        crate::error::handle(CallbackKind::PluginDeinit, &name, result.map(Ok), ());
+    } else {
+        // The instance is only missing while `on_loaded` runs, so this is being called from
+        // within it. Everything else is still torn down, and the timer running `on_loaded` drops
+        // the instance once it returns.
+        UNLOADED_BY_ON_LOADED.store(true, Ordering::SeqCst);
+    }
+    let timer = LOADED_TIMER.lock().take();
+    if let Some(timer) = timer {
+        crate::remove_timer_task(timer);
+    }
+    crate::executor::shutdown();
+    crate::main_thread::shutdown();
+    crate::server_features::shutdown();
+    #[cfg(feature = "profiling")]
+    crate::profiling::shutdown();
+    #[cfg(feature = "diagnostics")]
+    let hooks = crate::diagnostics::get_hook_counts();
+    tear_down();
+    if let Some(instance) = instance {
+        let result = panic::catch_unwind(AssertUnwindSafe(move || instance.on_unloaded()));
+        crate::error::handle(CallbackKind::PluginDeinit, &name, result.map(Ok), ());
+    }
+    #[cfg(feature = "diagnostics")]
+    let statics = ALLOCATED.read().as_ref().map_or(0, Vec::len);
+    // Again, for anything registered by `on_unloaded` or `Drop`.
+    tear_down();
+    crate::whox::shutdown();
+    crate::whois::shutdown();
+    // Threads may be using safe statics, so they have to stop before those are deallocated.
+    let threads = mem::replace(&mut get_plugin().threads, Vec::new());
+    let threads_left = crate::thread::shutdown(threads);
+    #[cfg(feature = "diagnostics")]
+    crate::diagnostics::report(&crate::diagnostics::UnloadReport {
+        name: T::NAME.to_string(),
+        hooks,
+        late_statics: ALLOCATED.read().as_ref().map_or(0, Vec::len) - statics,
+        threads: threads_left,
+    });
+    EXITING.store(false, Ordering::SeqCst);
+    // Threads that are still running may be using the error sink, the plugin's state, or its safe
+    // statics, so rather than being freed out from under them, those are leaked.
+    if threads_left > 0 {
+        return 1;
+    }
+    crate::error::shutdown();
+    *PLUGIN.write() = None;
+    if free_statics() {
+        1
+    } else {
+        -5
+    }
+}
+
+// HexChat forgets a plugin that fails to load without calling `hexchat_plugin_deinit`, so anything
+// `init` registered before failing has to be removed here.
+fn abort_init() -> c_int {
+    tear_down();
+    crate::whox::shutdown();
+    crate::whois::shutdown();
+    let threads = mem::replace(&mut get_plugin().threads, Vec::new());
+    if crate::thread::shutdown(threads) > 0 {
+        // As in `hexchat_plugin_deinit`, whatever the threads may be using is leaked.
+        return 0;
+    }
+    crate::error::shutdown();
+    *PLUGIN.write() = None;
+    free_statics();
+    0
+}
+
+fn free_statics() -> bool {
+    let vec = ALLOCATED.write().take();
+    if let Some(vec) = vec {
+        for func in vec {
+            let boxed = func.0;
+            boxed();
+        }
+        true
+    } else {
+        false
+    }
+}
+
+fn tear_down() {
+    crate::executor::shutdown();
+    crate::main_thread::shutdown();
+    // Swapped out before unhooking, so that nothing is left registered for a hook's removal to
+    // find.
+    let plugin = {
+        let mut plugin = get_plugin();
+        let mut new = PluginDef::new(plugin.ph);
+        mem::swap(&mut new.threads, &mut plugin.threads);
+        mem::replace(&mut *plugin, new)
     };
     let PluginDef {
         server_events,
@@ -171,14 +319,10 @@ where
         commands,
         timer_tasks,
         typed_server_events,
-        ..
+        fd_watchers,
+        ph: _,
+        threads: _,
     } = plugin;
-    let instance = match instance {
-        Some(i) => i,
-        None => return -2,
-    };
-    mem::drop(instance);
-    EXITING.store(false, Ordering::SeqCst);
     for event in server_events {
         crate::dealloc_raw_server_event_listener(event.0);
     }
@@ -197,16 +341,7 @@ where
     for event in typed_server_events {
         crate::dealloc_server_event_listener(event.0);
     }
-    let mut vec = None;
-    let mut lock = ALLOCATED.write();
-    mem::swap(&mut vec, &mut *lock);
-    if let Some(vec) = vec {
-        for func in vec {
-            let boxed = func.0;
-            boxed();
-        }
-    } else {
-        return -5;
+    for watcher in fd_watchers {
+        crate::dealloc_fd_watcher(watcher.0);
     }
-    1
 }
@@ -0,0 +1,105 @@
+use crate::ReloadState;
+use std::path::{Path, PathBuf};
+
+/// Information about how your plugin was loaded. Passed to `Plugin::init`.
+#[derive(Clone, Debug)]
+pub struct PluginContext {
+    arg: Option<String>,
+    plugin_path: Option<PathBuf>,
+    config_dir: PathBuf,
+    reload_state: Option<ReloadState>,
+}
+
+impl PluginContext {
+    pub(crate) fn new(arg: Option<String>, reload_state: Option<ReloadState>) -> Self {
+        Self {
+            arg,
+            plugin_path: plugin_path(),
+            config_dir: crate::get_config_dir(),
+            reload_state,
+        }
+    }
+    /// Gets the argument your plugin was loaded with, i.e. anything after the file name in
+    /// `/LOAD <file> <arg>`.
+    ///
+    /// Returns `None` if there was no argument, e.g. if the plugin was loaded automatically on
+    /// startup.
+    pub fn get_arg(&self) -> Option<&str> {
+        self.arg.as_ref().map(|s| &**s)
+    }
+    /// Gets the path of your plugin's library file.
+    ///
+    /// Returns `None` if the path could not be determined.
+    pub fn get_plugin_path(&self) -> Option<&Path> {
+        self.plugin_path.as_ref().map(|p| &**p)
+    }
+    /// Gets HexChat's configuration directory. This is the same as `get_config_dir`.
+    pub fn get_config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+    /// Gets the state saved by `Plugin::save_state` when your plugin was last unloaded.
+    ///
+    /// Returns `None` if no state was saved, or if it was saved with a different
+    /// `Plugin::STATE_VERSION`.
+    pub fn get_reload_state(&self) -> Option<&ReloadState> {
+        self.reload_state.as_ref()
+    }
+}
+
+// Any address inside the plugin's library will do; this function's own is the easiest to get.
+
+#[cfg(unix)]
+fn plugin_path() -> Option<PathBuf> {
+    use std::ffi::{c_void, CStr, OsStr};
+    use std::mem;
+    use std::os::unix::ffi::OsStrExt;
+    unsafe {
+        let mut info: libc::Dl_info = mem::zeroed();
+        if libc::dladdr(plugin_path as *const c_void, &mut info) == 0 || info.dli_fname.is_null() {
+            return None;
+        }
+        let bytes = CStr::from_ptr(info.dli_fname).to_bytes();
+        Some(PathBuf::from(OsStr::from_bytes(bytes)))
+    }
+}
+
+#[cfg(windows)]
+fn plugin_path() -> Option<PathBuf> {
+    use std::ffi::{c_void, OsString};
+    use std::os::windows::ffi::OsStringExt;
+    use std::ptr;
+    const FROM_ADDRESS: u32 = 0x4;
+    const UNCHANGED_REFCOUNT: u32 = 0x2;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetModuleHandleExW(flags: u32, name: *const c_void, module: *mut *mut c_void) -> i32;
+        fn GetModuleFileNameW(module: *mut c_void, filename: *mut u16, size: u32) -> u32;
+    }
+    unsafe {
+        let mut module = ptr::null_mut();
+        if GetModuleHandleExW(
+            FROM_ADDRESS | UNCHANGED_REFCOUNT,
+            plugin_path as *const c_void,
+            &mut module,
+        ) == 0
+        {
+            return None;
+        }
+        let mut buf = vec![0u16; 1024];
+        loop {
+            let len = GetModuleFileNameW(module, buf.as_mut_ptr(), buf.len() as u32) as usize;
+            if len == 0 {
+                return None;
+            } else if len < buf.len() {
+                return Some(PathBuf::from(OsString::from_wide(&buf[..len])));
+            }
+            let new_len = buf.len() * 2;
+            buf.resize(new_len, 0);
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn plugin_path() -> Option<PathBuf> {
+    None
+}
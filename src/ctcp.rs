@@ -0,0 +1,377 @@
+use crate::irc::{InvalidMessageError, Message};
+use crate::server_event::{PrivmsgTarget, ServerEvent, NOTICE, PRIVMSG};
+use crate::{
+    add_server_event_listener, send_command, CallbackResult, EatMode, Priority,
+    ServerEventListener, UserString,
+};
+use chrono::{DateTime, Utc};
+
+const DELIM: char = '\x01';
+const M_QUOTE: char = '\x10';
+
+/// A CTCP (Client-To-Client Protocol) message, which is sent inside a `PRIVMSG` as a request or
+/// inside a `NOTICE` as a reply, delimited by `\x01` characters.
+///
+/// # Example
+///
+/// ```rust
+/// use hexchat::Ctcp;
+///
+/// let ctcp = Ctcp::parse("\x01ACTION waves\x01").unwrap();
+/// assert_eq!(ctcp, Ctcp::Action("waves".to_string()));
+/// assert_eq!(Ctcp::Version(None).encode(), "\x01VERSION\x01");
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Ctcp {
+    /// `ACTION`, as sent by `/me`, with the text of the action.
+    Action(String),
+    /// `VERSION`. Requests have no parameter, and replies contain the client's version.
+    Version(Option<String>),
+    /// `PING`, with a token that the reply echoes back.
+    Ping(Option<String>),
+    /// `TIME`. Requests have no parameter, and replies contain the client's local time.
+    Time(Option<String>),
+    /// `CLIENTINFO`. Replies contain the CTCP commands the client supports.
+    ClientInfo(Option<String>),
+    /// `DCC`, with its parameters, e.g. `SEND file.txt 3232235777 5000 1024`.
+    Dcc(String),
+    /// Any other command.
+    Other {
+        /// The command, e.g. `SOURCE`.
+        command: String,
+        /// The parameters, if there were any.
+        params: Option<String>,
+    },
+}
+
+impl Ctcp {
+    /// Creates a CTCP message from its command, which is case-insensitive, and parameters.
+    pub fn new(command: &str, params: Option<String>) -> Self {
+        match command.to_uppercase().as_str() {
+            "ACTION" => Ctcp::Action(params.unwrap_or_default()),
+            "VERSION" => Ctcp::Version(params),
+            "PING" => Ctcp::Ping(params),
+            "TIME" => Ctcp::Time(params),
+            "CLIENTINFO" => Ctcp::ClientInfo(params),
+            "DCC" => Ctcp::Dcc(params.unwrap_or_default()),
+            _ => Ctcp::Other {
+                command: command.to_string(),
+                params,
+            },
+        }
+    }
+    /// Parses the text of a `PRIVMSG` or `NOTICE` as a CTCP message, dequoting it with
+    /// `dequote_ctcp`.
+    ///
+    /// Returns `None` if the text isn't a CTCP message. The closing `\x01` is optional, and
+    /// anything after it is ignored.
+    pub fn parse(text: &str) -> Option<Self> {
+        if !text.starts_with(DELIM) {
+            return None;
+        }
+        let body = text[1..].split(DELIM).next().unwrap();
+        let body = dequote_ctcp(body);
+        let mut split = body.splitn(2, ' ');
+        let command = split.next().unwrap();
+        if command.is_empty() {
+            return None;
+        }
+        Some(Self::new(command, split.next().map(str::to_string)))
+    }
+    /// Gets the command, e.g. `VERSION`.
+    pub fn get_command(&self) -> &str {
+        match self {
+            Ctcp::Action(_) => "ACTION",
+            Ctcp::Version(_) => "VERSION",
+            Ctcp::Ping(_) => "PING",
+            Ctcp::Time(_) => "TIME",
+            Ctcp::ClientInfo(_) => "CLIENTINFO",
+            Ctcp::Dcc(_) => "DCC",
+            Ctcp::Other { command, .. } => command,
+        }
+    }
+    /// Gets the parameters, or `None` if there aren't any.
+    pub fn get_params(&self) -> Option<&str> {
+        match self {
+            Ctcp::Action(params) | Ctcp::Dcc(params) => {
+                Some(params.as_str()).filter(|p| !p.is_empty())
+            }
+            Ctcp::Version(params)
+            | Ctcp::Ping(params)
+            | Ctcp::Time(params)
+            | Ctcp::ClientInfo(params)
+            | Ctcp::Other { params, .. } => params.as_ref().map(String::as_str),
+        }
+    }
+    /// Encodes the message as the text of a `PRIVMSG` or `NOTICE`, quoting it with `quote_ctcp`
+    /// and adding the `\x01` delimiters. Any `\x01` in the parameters is removed, since CTCP has
+    /// no way to represent it.
+    pub fn encode(&self) -> String {
+        let mut body = self.get_command().to_string();
+        if let Some(params) = self.get_params() {
+            body.push(' ');
+            body.push_str(params);
+        }
+        body.retain(|c| c != DELIM);
+        format!("{}{}{}", DELIM, quote_ctcp(&body), DELIM)
+    }
+}
+
+/// Applies CTCP low-level quoting to a message, so that NUL, CR, and LF can be sent.
+pub fn quote_ctcp(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\0' => quoted.push_str("\x100"),
+            '\r' => quoted.push_str("\x10r"),
+            '\n' => quoted.push_str("\x10n"),
+            M_QUOTE => quoted.push_str("\x10\x10"),
+            _ => quoted.push(ch),
+        }
+    }
+    quoted
+}
+
+/// Reverses CTCP low-level quoting. Unknown escapes are replaced with the escaped character, and a
+/// trailing lone `\x10` is dropped.
+pub fn dequote_ctcp(text: &str) -> String {
+    let mut dequoted = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != M_QUOTE {
+            dequoted.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('0') => dequoted.push('\0'),
+            Some('r') => dequoted.push('\r'),
+            Some('n') => dequoted.push('\n'),
+            Some(other) => dequoted.push(other),
+            None => {}
+        }
+    }
+    dequoted
+}
+
+/// A type representing a CTCP message embedded in a server event. Used with `add_ctcp_listener`.
+/// It is not recommended you implement this on your own types.
+pub trait CtcpEvent
+where
+    Self: Sized,
+{
+    /// The server event the message is sent in.
+    type Event: ServerEvent;
+    /// Extracts the CTCP message from the event, or returns `None` if it doesn't contain one.
+    fn from_event(event: Self::Event) -> Option<Self>;
+}
+
+/// A CTCP request, i.e. one sent in a `PRIVMSG`. This includes `ACTION`s.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CtcpRequest {
+    user: UserString,
+    target: PrivmsgTarget,
+    ctcp: Ctcp,
+}
+
+impl CtcpRequest {
+    /// Gets the user that sent the request.
+    pub fn get_user(&self) -> &UserString {
+        &self.user
+    }
+    /// Gets the target of the request.
+    pub fn get_target(&self) -> &PrivmsgTarget {
+        &self.target
+    }
+    /// Gets the request itself.
+    pub fn get_ctcp(&self) -> &Ctcp {
+        &self.ctcp
+    }
+}
+
+impl CtcpEvent for CtcpRequest {
+    type Event = PRIVMSG;
+    fn from_event(event: PRIVMSG) -> Option<Self> {
+        let ctcp = Ctcp::parse(event.get_message())?;
+        Some(Self {
+            user: event.get_user().clone(),
+            target: event.get_target().clone(),
+            ctcp,
+        })
+    }
+}
+
+/// A CTCP reply, i.e. one sent in a `NOTICE`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CtcpReply {
+    user: UserString,
+    target: PrivmsgTarget,
+    ctcp: Ctcp,
+}
+
+impl CtcpReply {
+    /// Gets the user that sent the reply.
+    pub fn get_user(&self) -> &UserString {
+        &self.user
+    }
+    /// Gets the target of the reply.
+    pub fn get_target(&self) -> &PrivmsgTarget {
+        &self.target
+    }
+    /// Gets the reply itself.
+    pub fn get_ctcp(&self) -> &Ctcp {
+        &self.ctcp
+    }
+}
+
+impl CtcpEvent for CtcpReply {
+    type Event = NOTICE;
+    fn from_event(event: NOTICE) -> Option<Self> {
+        let ctcp = Ctcp::parse(event.get_message())?;
+        Some(Self {
+            user: event.get_user().clone(),
+            target: event.get_target().clone(),
+            ctcp,
+        })
+    }
+}
+
+/// Adds a listener for CTCP messages: either `CtcpRequest`s, or `CtcpReply`s.
+///
+/// Returns a corresponding object suitable for passing to `remove_server_event_listener`.
+///
+/// # Callback
+///
+/// The callback's signature is the CTCP message, followed by the time it was sent. The callback
+/// should return who the message should be hidden from; to stop HexChat from answering a request
+/// itself, return `EatMode::Hexchat`. `PRIVMSG`s and `NOTICE`s that aren't CTCP messages are
+/// passed over.
+pub fn add_ctcp_listener<T, R>(
+    priority: Priority,
+    function: impl Fn(T, DateTime<Utc>) -> R + 'static,
+) -> ServerEventListener
+where
+    T: CtcpEvent,
+    R: CallbackResult<EatMode>,
+{
+    add_server_event_listener(priority, move |event: T::Event, time| {
+        match T::from_event(event) {
+            Some(ctcp) => function(ctcp, time).into_result(),
+            None => Ok(EatMode::None),
+        }
+    })
+}
+
+/// Sends a CTCP request to a nick or channel on the current server.
+///
+/// The request is sent with `/QUOTE`, so HexChat doesn't show it. Returns an error if `target`
+/// isn't a valid parameter, i.e. if it is empty, contains a space, CR, LF or NUL, or starts with
+/// `:`.
+pub fn send_ctcp(target: &str, ctcp: &Ctcp) -> Result<(), InvalidMessageError> {
+    send_raw_ctcp("PRIVMSG", target, ctcp)
+}
+
+/// Sends a CTCP reply to a nick on the current server.
+///
+/// The reply is sent with `/QUOTE`, so HexChat doesn't show it. Returns an error if `target` isn't
+/// a valid parameter, as in `send_ctcp`.
+pub fn send_ctcp_reply(target: &str, ctcp: &Ctcp) -> Result<(), InvalidMessageError> {
+    send_raw_ctcp("NOTICE", target, ctcp)
+}
+
+fn send_raw_ctcp(command: &str, target: &str, ctcp: &Ctcp) -> Result<(), InvalidMessageError> {
+    let message = Message::new(command, vec![target.to_string(), ctcp.encode()])?;
+    send_command(&format!("QUOTE {}", message));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoting() {
+        assert_eq!(
+            quote_ctcp("a\0b\rc\nd\x10e"),
+            "a\x100b\x10rc\x10nd\x10\x10e"
+        );
+        assert_eq!(
+            dequote_ctcp("a\x100b\x10rc\x10nd\x10\x10e"),
+            "a\0b\rc\nd\x10e"
+        );
+        assert_eq!(dequote_ctcp("a\x10xb\x10"), "axb");
+        for text in &["", "plain", "\x10", "\x10\x10n", "line\r\nbreak\0"] {
+            assert_eq!(dequote_ctcp(&quote_ctcp(text)), *text);
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Ctcp::parse("\x01ACTION waves\x01"),
+            Some(Ctcp::Action("waves".to_string()))
+        );
+        assert_eq!(
+            Ctcp::parse("\x01PING 123"),
+            Some(Ctcp::Ping(Some("123".to_string())))
+        );
+        assert_eq!(Ctcp::parse("\x01TIME\x01 trailing"), Some(Ctcp::Time(None)));
+        assert_eq!(Ctcp::parse("not a ctcp"), None);
+        assert_eq!(Ctcp::parse("\x01\x01"), None);
+        assert_eq!(Ctcp::parse("\x01"), None);
+    }
+
+    #[test]
+    fn mixed_case() {
+        assert_eq!(Ctcp::parse("\x01Version\x01"), Some(Ctcp::Version(None)));
+        assert_eq!(
+            Ctcp::parse("\x01action dances\x01"),
+            Some(Ctcp::Action("dances".to_string()))
+        );
+        let other = Ctcp::parse("\x01Source\x01").unwrap();
+        assert_eq!(
+            other,
+            Ctcp::Other {
+                command: "Source".to_string(),
+                params: None
+            }
+        );
+        assert_eq!(other.get_command(), "Source");
+        assert_eq!(Ctcp::new("ping", None).encode(), "\x01PING\x01");
+    }
+
+    #[test]
+    fn round_trips() {
+        for ctcp in &[
+            Ctcp::Action("waves\r\nhello".to_string()),
+            Ctcp::Version(None),
+            Ctcp::Version(Some("HexChat 2.14.2".to_string())),
+            Ctcp::Ping(Some("12345".to_string())),
+            Ctcp::ClientInfo(Some("ACTION PING".to_string())),
+            Ctcp::Dcc("SEND file.txt 3232235777 5000 1024".to_string()),
+            Ctcp::Other {
+                command: "SOURCE".to_string(),
+                params: Some("\x10quoted\0".to_string()),
+            },
+        ] {
+            assert_eq!(Ctcp::parse(&ctcp.encode()).as_ref(), Some(ctcp));
+        }
+        assert_eq!(
+            Ctcp::Action("a\x01b".to_string()).encode(),
+            "\x01ACTION ab\x01"
+        );
+    }
+
+    #[test]
+    fn invalid_target() {
+        for target in &["", "a b", ":a", "nick\r\nQUIT", "nick\0"] {
+            assert_eq!(
+                send_ctcp(target, &Ctcp::Version(None)),
+                Err(InvalidMessageError::InvalidParam(target.to_string()))
+            );
+            assert_eq!(
+                send_ctcp_reply(target, &Ctcp::Version(None)),
+                Err(InvalidMessageError::InvalidParam(target.to_string()))
+            );
+        }
+    }
+}
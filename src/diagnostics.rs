@@ -0,0 +1,184 @@
+use crate::call;
+use chrono::Utc;
+use parking_lot::Mutex;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static TARGET: Mutex<Option<ReportTarget>> = Mutex::new(None);
+pub(crate) static FAKE_PLUGINS: AtomicUsize = AtomicUsize::new(0);
+
+/// Where the unload report is written. Set with `set_report_target`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ReportTarget {
+    /// Print the report to the current tab. This is the default.
+    Print,
+    /// Append the report to a file, along with the time it was written.
+    File(PathBuf),
+}
+
+/// Sets where the unload report is written.
+///
+/// The target is reset when the plugin is unloaded.
+pub fn set_report_target(target: ReportTarget) {
+    *TARGET.lock() = Some(target);
+}
+
+/// The numbers of live hooks and other resources, of each kind, registered by your plugin.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct HookCounts {
+    /// Commands registered with `register_command` and friends.
+    pub commands: usize,
+    /// Listeners added with `add_print_event_listener` or `add_typed_print_listener`.
+    pub print_event_listeners: usize,
+    /// Listeners added with `add_window_event_listener`.
+    pub window_event_listeners: usize,
+    /// Listeners added with `add_raw_server_event_listener`.
+    pub raw_server_event_listeners: usize,
+    /// Listeners added with `add_server_event_listener`, `add_reply_listener`, and friends.
+    pub server_event_listeners: usize,
+    /// Tasks added with `add_timer_task` and friends.
+    pub timer_tasks: usize,
+    /// Watchers added with `add_fd_watcher`.
+    pub fd_watchers: usize,
+    /// Fake plugins added with `add_fake_plugin`.
+    pub fake_plugins: usize,
+    /// Threads spawned with `thread::spawn` that are still running.
+    pub threads: usize,
+}
+
+impl HookCounts {
+    /// Gets the total number of hooks, not counting fake plugins or threads.
+    pub fn hooks(&self) -> usize {
+        self.commands
+            + self.print_event_listeners
+            + self.window_event_listeners
+            + self.raw_server_event_listeners
+            + self.server_event_listeners
+            + self.timer_tasks
+            + self.fd_watchers
+    }
+}
+
+/// Gets the numbers of hooks and other resources currently registered by your plugin.
+pub fn get_hook_counts() -> HookCounts {
+    let plugin = call::get_plugin();
+    HookCounts {
+        commands: plugin
+            .commands
+            .iter()
+            .filter(|c| !call::is_internal_hook(c.0))
+            .count(),
+        print_event_listeners: plugin.print_events.len(),
+        window_event_listeners: plugin.window_events.len(),
+        raw_server_event_listeners: plugin
+            .server_events
+            .iter()
+            .filter(|l| !call::is_internal_hook(l.0))
+            .count(),
+        server_event_listeners: plugin
+            .typed_server_events
+            .iter()
+            .filter(|l| !call::is_internal_hook(l.0))
+            .count(),
+        timer_tasks: plugin.timer_tasks.len(),
+        fd_watchers: plugin.fd_watchers.len(),
+        fake_plugins: FAKE_PLUGINS.load(Ordering::SeqCst),
+        threads: plugin.threads.iter().filter(|t| !t.is_finished()).count(),
+    }
+}
+
+pub(crate) struct UnloadReport {
+    pub(crate) name: String,
+    pub(crate) hooks: HookCounts,
+    pub(crate) late_statics: usize,
+    pub(crate) threads: usize,
+}
+
+pub(crate) fn report(report: &UnloadReport) {
+    let hooks = &report.hooks;
+    let mut lines = Vec::new();
+    if hooks.hooks() > 0 {
+        let kinds = [
+            ("command", hooks.commands),
+            ("print event listener", hooks.print_event_listeners),
+            ("window event listener", hooks.window_event_listeners),
+            (
+                "raw server event listener",
+                hooks.raw_server_event_listeners,
+            ),
+            ("server event listener", hooks.server_event_listeners),
+            ("timer task", hooks.timer_tasks),
+            ("fd watcher", hooks.fd_watchers),
+        ];
+        let kinds = kinds
+            .iter()
+            .filter(|(_, n)| *n > 0)
+            .map(|(kind, n)| format!("{} {}(s)", n, kind))
+            .collect::<Vec<_>>();
+        lines.push(format!(
+            "{} hook(s) were still registered, and were removed automatically: {}",
+            hooks.hooks(),
+            kinds.join(", ")
+        ));
+    }
+    if hooks.fake_plugins > 0 {
+        lines.push(format!(
+            "{} fake plugin(s) were never removed",
+            hooks.fake_plugins
+        ));
+    }
+    if report.late_statics > 0 {
+        lines.push(format!(
+            "{} safe static(s) were first used after the plugin was dropped",
+            report.late_statics
+        ));
+    }
+    if report.threads > 0 {
+        lines.push(format!("{} thread(s) were still running", report.threads));
+    }
+    let mut text = format!("Unload diagnostics for {}: ", report.name);
+    if lines.is_empty() {
+        text.push_str("nothing was left behind");
+    } else {
+        text.push_str(&lines.join("; "));
+    }
+    let target = TARGET.lock().take();
+    match target {
+        Some(ReportTarget::File(path)) => {
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                writeln!(file, "[{}] {}", Utc::now().to_rfc3339(), text).ok();
+            }
+        }
+        Some(ReportTarget::Print) | None => crate::print_plain(&text),
+    }
+    FAKE_PLUGINS.store(0, Ordering::SeqCst);
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::MockHexchat;
+    use crate::{register_command, EatMode, Priority};
+    use std::time::Duration;
+
+    #[test]
+    fn hook_counts() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        mock.advance_time(Duration::from_millis(0));
+        register_command("test", "", Priority::NORMAL, |_| EatMode::All);
+        let counts = get_hook_counts();
+        assert_eq!(counts.commands, 1);
+        assert_eq!(counts.hooks(), 1);
+        assert!(mock.unload());
+        assert_eq!(
+            mock.printed(),
+            [
+                "Unload diagnostics for test: 1 hook(s) were still registered, and were removed \
+              automatically: 1 command(s)"
+            ]
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use chrono::Utc;
+use parking_lot::Mutex;
+use std::any::Any;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+static ERROR_SINK: Mutex<SinkSlot> = Mutex::new(SinkSlot(None));
+
+struct SinkSlot(Option<Rc<ErrorSink>>);
+// The sink is only ever touched from the main thread.
+unsafe impl Send for SinkSlot {}
+
+/// Where errors and panics from your plugin's callbacks are reported. Set with `set_error_sink`.
+///
+/// If no sink has been set, errors during plugin initialization are shown in a message box, and
+/// all others are printed to the current tab.
+pub enum ErrorSink {
+    /// Print the error to the current tab.
+    Print,
+    /// Show the error in a message box.
+    MessageBox,
+    /// Append the error to a file, along with the time it occurred.
+    LogFile(PathBuf),
+    /// Pass the error to a function.
+    Custom(Box<dyn Fn(&CallbackError)>),
+}
+
+/// Sets where errors and panics from your plugin's callbacks are reported. See `ErrorSink` for
+/// more details.
+///
+/// The sink is reset when the plugin is unloaded.
+pub fn set_error_sink(sink: ErrorSink) {
+    ERROR_SINK.lock().0 = Some(Rc::new(sink));
+}
+
+/// The kind of callback a `CallbackError` occurred in.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum CallbackKind {
+    /// A command registered with `register_command`.
+    Command,
+    /// A listener added with `add_print_event_listener`.
+    PrintEventListener,
+    /// A listener added with `add_window_event_listener`.
+    WindowEventListener,
+    /// A listener added with `add_raw_server_event_listener`.
+    RawServerEventListener,
+    /// A listener added with `add_server_event_listener`.
+    ServerEventListener,
+    /// A listener added with `add_reply_listener`.
+    ReplyListener,
+    /// A task added with `add_timer_task`.
+    TimerTask,
+    /// A watcher added with `add_fd_watcher`.
+    FdWatcher,
+    /// A future spawned with `spawn_local`.
+    Task,
+    /// A function run with `MainThreadHandle::run`.
+    MainThreadJob,
+    /// `Plugin::new`, `Plugin::init`, or `Plugin::on_loaded`.
+    PluginInit,
+    /// `Plugin::on_unloading`, `Plugin::on_unloaded`, or your plugin's `Drop` implementation.
+    PluginDeinit,
+}
+
+impl Display for CallbackKind {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.write_str(match self {
+            CallbackKind::Command => "command",
+            CallbackKind::PrintEventListener => "print event listener",
+            CallbackKind::WindowEventListener => "window event listener",
+            CallbackKind::RawServerEventListener => "raw server event listener",
+            CallbackKind::ServerEventListener => "server event listener",
+            CallbackKind::ReplyListener => "reply listener",
+            CallbackKind::TimerTask => "timer task",
+            CallbackKind::FdWatcher => "fd watcher",
+            CallbackKind::Task => "task",
+            CallbackKind::MainThreadJob => "main thread job",
+            CallbackKind::PluginInit => "plugin initialization",
+            CallbackKind::PluginDeinit => "plugin unloading",
+        })
+    }
+}
+
+/// An error returned from, or a panic in, one of your plugin's callbacks.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CallbackError {
+    kind: CallbackKind,
+    name: String,
+    message: Option<String>,
+    panicked: bool,
+}
+
+impl CallbackError {
+    /// Gets the kind of callback the error occurred in.
+    pub fn get_kind(&self) -> CallbackKind {
+        self.kind
+    }
+    /// Gets the name of the callback the error occurred in, e.g. the command or event name. This
+    /// is empty for callbacks that have no name, such as timer tasks.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    /// Gets the error message, or `None` if the callback panicked with something other than a
+    /// string.
+    pub fn get_message(&self) -> Option<&str> {
+        self.message.as_ref().map(|s| &**s)
+    }
+    /// Gets whether the callback panicked, as opposed to returning an error.
+    pub fn is_panic(&self) -> bool {
+        self.panicked
+    }
+}
+
+impl Display for CallbackError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let what = if self.panicked { "Panic" } else { "Error" };
+        write!(f, "{} in {}", what, self.kind)?;
+        if !self.name.is_empty() {
+            write!(f, " '{}'", self.name)?;
+        }
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+/// A value that can be returned from a callback: either the value itself, or a `Result` whose
+/// error is reported to the error sink set with `set_error_sink`.
+pub trait CallbackResult<T> {
+    #[doc(hidden)]
+    fn into_result(self) -> Result<T, String>;
+}
+
+impl<T> CallbackResult<T> for T {
+    fn into_result(self) -> Result<T, String> {
+        Ok(self)
+    }
+}
+
+impl<T, E> CallbackResult<T> for Result<T, E>
+where
+    E: Display,
+{
+    fn into_result(self) -> Result<T, String> {
+        self.map_err(|e| e.to_string())
+    }
+}
+
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> Option<String> {
+    if let Some(string) = payload.downcast_ref::<&str>() {
+        Some(string.to_string())
+    } else {
+        payload.downcast_ref::<String>().cloned()
+    }
+}
+
+/// Runs a callback's result through the error sink, returning the callback's value or `default`.
+pub(crate) fn handle<T>(
+    kind: CallbackKind,
+    name: &str,
+    result: std::thread::Result<Result<T, String>>,
+    default: T,
+) -> T {
+    match result {
+        Ok(Ok(t)) => t,
+        Ok(Err(message)) => {
+            report(kind, name, Some(message), false);
+            default
+        }
+        Err(payload) => {
+            report(kind, name, panic_message(&*payload), true);
+            default
+        }
+    }
+}
+
+pub(crate) fn report(kind: CallbackKind, name: &str, message: Option<String>, panicked: bool) {
+    let error = CallbackError {
+        kind,
+        name: name.to_string(),
+        message,
+        panicked,
+    };
+    // Cloned out so that the sink may itself call `set_error_sink`.
+    let sink = ERROR_SINK.lock().0.clone();
+    match sink.as_ref().map(|s| &**s) {
+        Some(ErrorSink::Print) => crate::print_plain(&error.to_string()),
+        Some(ErrorSink::MessageBox) => message_box(&error.to_string()),
+        Some(ErrorSink::LogFile(path)) => {
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                writeln!(file, "[{}] {}", Utc::now().to_rfc3339(), error).ok();
+            }
+        }
+        Some(ErrorSink::Custom(function)) => function(&error),
+        None if kind == CallbackKind::PluginInit => message_box(&error.to_string()),
+        None => crate::print_plain(&error.to_string()),
+    }
+}
+
+fn message_box(text: &str) {
+    crate::send_command(&format!(r#"GUI MSGBOX "{}""#, text.replace('"', "'")));
+}
+
+pub(crate) fn shutdown() {
+    let sink = ERROR_SINK.lock().0.take();
+    drop(sink);
+}
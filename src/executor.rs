@@ -0,0 +1,376 @@
+use crate::error;
+use crate::notify::{self, Notifier};
+use crate::reply::ServerReply;
+use crate::server_event::ServerEvent;
+use crate::{
+    add_reply_listener, add_server_event_listener, add_timer_once, remove_fd_watcher, CallbackKind,
+    EatMode, FdWatcher, Hook, HookGuard, Priority, ServerEventListener, TimerTask,
+};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::future::Future;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+
+static EXECUTOR: Mutex<Option<Executor>> = Mutex::new(None);
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+struct Executor {
+    // `None` while the task is being polled.
+    tasks: HashMap<usize, Option<Task>>,
+    next_id: usize,
+    shared: Arc<Shared>,
+    watcher: FdWatcher,
+}
+// Tasks are only ever touched from the main thread.
+unsafe impl Send for Executor {}
+
+struct Shared {
+    ready: Mutex<VecDeque<usize>>,
+    notifier: Notifier,
+}
+
+struct TaskWaker {
+    id: usize,
+    shared: Arc<Shared>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.shared.ready.lock().push_back(self.id);
+        self.shared.notifier.notify();
+    }
+}
+
+/// Spawns a future onto HexChat's main thread. The future will be polled in between HexChat's
+/// other events, and will be dropped if it has not completed by the time the plugin is unloaded.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use hexchat::reply::RPL_ENDOFWHOIS;
+/// use hexchat::{next_reply, print_plain, send_command, spawn_local, timeout, EatMode, Priority};
+/// use std::time::Duration;
+///
+/// spawn_local(async {
+///     let end = next_reply::<RPL_ENDOFWHOIS>(Priority::NORMAL, EatMode::None);
+///     send_command("WHOIS pie_flavor");
+///     match timeout(Duration::from_secs(10), end).await {
+///         Ok((reply, _)) => print_plain(&format!("Finished WHOIS for {}", reply.nick())),
+///         Err(_) => print_plain("WHOIS timed out"),
+///     }
+/// });
+/// ```
+///
+/// # Panics
+///
+/// Panics if the executor's wakeup socket could not be created.
+pub fn spawn_local(future: impl Future<Output = ()> + 'static) {
+    let mut lock = EXECUTOR.lock();
+    if lock.is_none() {
+        let (notifier, watcher) =
+            notify::notifier(run_ready).expect("Could not create executor socket");
+        *lock = Some(Executor {
+            tasks: HashMap::new(),
+            next_id: 0,
+            watcher,
+            shared: Arc::new(Shared {
+                ready: Mutex::new(VecDeque::new()),
+                notifier,
+            }),
+        });
+    }
+    let executor = lock.as_mut().unwrap();
+    let id = executor.next_id;
+    executor.next_id += 1;
+    executor.tasks.insert(id, Some(Box::pin(future)));
+    executor.shared.ready.lock().push_back(id);
+    executor.shared.notifier.notify();
+}
+
+fn run_ready() {
+    let shared = match &*EXECUTOR.lock() {
+        Some(executor) => executor.shared.clone(),
+        None => return,
+    };
+    loop {
+        let id = match shared.ready.lock().pop_front() {
+            Some(id) => id,
+            None => break,
+        };
+        let task = EXECUTOR
+            .lock()
+            .as_mut()
+            .and_then(|e| e.tasks.get_mut(&id))
+            .and_then(Option::take);
+        let mut task = match task {
+            Some(task) => task,
+            None => continue,
+        };
+        let waker = Waker::from(Arc::new(TaskWaker {
+            id,
+            shared: shared.clone(),
+        }));
+        let mut context = Context::from_waker(&waker);
+        let done = match panic::catch_unwind(AssertUnwindSafe(|| task.as_mut().poll(&mut context)))
+        {
+            Ok(poll) => poll.is_ready(),
+            Err(e) => {
+                error::report(CallbackKind::Task, "", error::panic_message(&*e), true);
+                true
+            }
+        };
+        let mut lock = EXECUTOR.lock();
+        if let Some(executor) = lock.as_mut() {
+            if done {
+                executor.tasks.remove(&id);
+            } else if let Some(slot) = executor.tasks.get_mut(&id) {
+                *slot = Some(task);
+                continue;
+            }
+        }
+        mem::drop(lock);
+    }
+}
+
+pub(crate) fn shutdown() {
+    let executor = EXECUTOR.lock().take();
+    if let Some(Executor { tasks, watcher, .. }) = executor {
+        remove_fd_watcher(watcher);
+        mem::drop(tasks);
+    }
+}
+
+/// Returns a future that completes after the specified duration.
+///
+/// # Note
+///
+/// Like `add_timer_once`, the duration cannot be more than `i32::max_value()` milliseconds, and
+/// will be truncated if it is.
+pub fn sleep(duration: Duration) -> Sleep {
+    let state = Rc::new(SleepState {
+        fired: Cell::new(false),
+        waker: RefCell::new(None),
+    });
+    let timer_state = state.clone();
+    let timer = add_timer_once(duration, move || {
+        timer_state.fired.set(true);
+        if let Some(waker) = timer_state.waker.borrow_mut().take() {
+            waker.wake();
+        }
+    });
+    Sleep {
+        state,
+        _timer: timer.scoped(),
+    }
+}
+
+/// A future returned by `sleep`.
+pub struct Sleep {
+    state: Rc<SleepState>,
+    _timer: HookGuard<TimerTask>,
+}
+
+struct SleepState {
+    fired: Cell<bool>,
+    waker: RefCell<Option<Waker>>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.state.fired.get() {
+            Poll::Ready(())
+        } else {
+            *self.state.waker.borrow_mut() = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Wraps a future such that it fails with `TimedOut` if it does not complete within the specified
+/// duration.
+pub fn timeout<F>(duration: Duration, future: F) -> Timeout<F>
+where
+    F: Future,
+{
+    Timeout {
+        future: Box::pin(future),
+        sleep: sleep(duration),
+    }
+}
+
+/// A future returned by `timeout`.
+pub struct Timeout<F>
+where
+    F: Future,
+{
+    future: Pin<Box<F>>,
+    sleep: Sleep,
+}
+
+impl<F> Future for Timeout<F>
+where
+    F: Future,
+{
+    type Output = Result<F::Output, TimedOut>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if let Poll::Ready(output) = self.future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        Pin::new(&mut self.sleep).poll(cx).map(|_| Err(TimedOut))
+    }
+}
+
+/// The error returned by a `Timeout` future that did not complete in time.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TimedOut;
+
+impl Display for TimedOut {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "future timed out")
+    }
+}
+
+impl Error for TimedOut {}
+
+/// A receiver for server events or replies, created by `receive_server_events` or
+/// `receive_replies`. The underlying listener is removed when the receiver is dropped.
+pub struct EventReceiver<T> {
+    state: Rc<ReceiverState<T>>,
+    _listener: HookGuard<ServerEventListener>,
+}
+
+struct ReceiverState<T> {
+    queue: RefCell<VecDeque<(T, DateTime<Utc>)>>,
+    waker: RefCell<Option<Waker>>,
+}
+
+impl<T> ReceiverState<T> {
+    fn new() -> Rc<Self> {
+        Rc::new(Self {
+            queue: RefCell::new(VecDeque::new()),
+            waker: RefCell::new(None),
+        })
+    }
+    fn push(&self, t: T, d: DateTime<Utc>) {
+        self.queue.borrow_mut().push_back((t, d));
+        if let Some(waker) = self.waker.borrow_mut().take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> EventReceiver<T> {
+    /// Returns a future that completes with the next event, followed by the time it was sent.
+    pub fn recv(&mut self) -> Recv<T> {
+        Recv { receiver: self }
+    }
+    fn poll_recv(&self, cx: &mut Context) -> Poll<(T, DateTime<Utc>)> {
+        if let Some(event) = self.state.queue.borrow_mut().pop_front() {
+            Poll::Ready(event)
+        } else {
+            *self.state.waker.borrow_mut() = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// A future returned by `EventReceiver::recv`.
+pub struct Recv<'a, T> {
+    receiver: &'a mut EventReceiver<T>,
+}
+
+impl<'a, T> Future for Recv<'a, T> {
+    type Output = (T, DateTime<Utc>);
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// A future returned by `next_server_event` or `next_reply`.
+pub struct NextEvent<T> {
+    receiver: EventReceiver<T>,
+}
+
+impl<T> Future for NextEvent<T> {
+    type Output = (T, DateTime<Utc>);
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// Adds a listener for server events, as defined in `add_server_event_listener`, whose events can
+/// be awaited through the returned `EventReceiver`. Every received event is hidden according to
+/// `eat_mode`.
+pub fn receive_server_events<T>(priority: Priority, eat_mode: EatMode) -> EventReceiver<T>
+where
+    T: ServerEvent + 'static,
+{
+    let state = ReceiverState::new();
+    let listener_state = state.clone();
+    let listener = add_server_event_listener(priority, move |t: T, d| {
+        listener_state.push(t, d);
+        eat_mode
+    });
+    EventReceiver {
+        state,
+        _listener: listener.scoped(),
+    }
+}
+
+/// Adds a listener for server replies, as defined in `add_reply_listener`, whose replies can be
+/// awaited through the returned `EventReceiver`. Every received reply is hidden according to
+/// `eat_mode`.
+pub fn receive_replies<T>(priority: Priority, eat_mode: EatMode) -> EventReceiver<T>
+where
+    T: ServerReply + 'static,
+{
+    let state = ReceiverState::new();
+    let listener_state = state.clone();
+    let listener = add_reply_listener(priority, move |t: T, d| {
+        listener_state.push(t, d);
+        eat_mode
+    });
+    EventReceiver {
+        state,
+        _listener: ServerEventListener(listener.0, listener.1).scoped(),
+    }
+}
+
+/// Returns a future that completes with the next server event of type `T`, as defined in
+/// `add_server_event_listener`. The listener is added immediately, not when the future is first
+/// polled, and is removed when the future is dropped.
+pub fn next_server_event<T>(priority: Priority, eat_mode: EatMode) -> NextEvent<T>
+where
+    T: ServerEvent + 'static,
+{
+    NextEvent {
+        receiver: receive_server_events(priority, eat_mode),
+    }
+}
+
+/// Returns a future that completes with the next server reply of type `T`, as defined in
+/// `add_reply_listener_once`. The listener is added immediately, not when the future is first
+/// polled, and is removed when the future is dropped.
+pub fn next_reply<T>(priority: Priority, eat_mode: EatMode) -> NextEvent<T>
+where
+    T: ServerReply + 'static,
+{
+    NextEvent {
+        receiver: receive_replies(priority, eat_mode),
+    }
+}
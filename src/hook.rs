@@ -1,51 +1,203 @@
 #![allow(clippy::type_complexity)] // todo fix when intellij-rust supports trait typedefs
 
 use crate::call;
-use crate::reply::ServerReply;
+use crate::error::{self, CallbackKind, CallbackResult};
+use crate::print_event::TypedPrintEvent;
+use crate::reply::{ServerError, ServerReply};
 use crate::server_event::ServerEvent;
-use crate::{c, from_cstring, to_cstring, ChannelRef, PrintEvent, WindowEvent};
+use crate::{c, from_cstring, from_cstring_opt, to_cstring, ChannelRef, PrintEvent, WindowEvent};
+use bitflags::bitflags;
 use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use std::cell::Cell;
 use std::ffi::c_void;
+use std::ops::Deref;
 use std::os::raw::{c_char, c_int};
 use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc;
 use std::time::Duration;
+#[cfg(feature = "profiling")]
+use std::time::Instant;
 
 /// A handle to a registered command.
 #[derive(Debug, Eq, PartialEq, Hash)]
-pub struct Command(pub(crate) *mut c::hexchat_hook);
+pub struct Command(pub(crate) *mut c::hexchat_hook, pub(crate) u64);
 unsafe impl Send for Command {}
 unsafe impl Sync for Command {}
 /// A handle to a registered print event listener.
 #[derive(Debug, Eq, PartialEq, Hash)]
-pub struct PrintEventListener(pub(crate) *mut c::hexchat_hook);
+pub struct PrintEventListener(pub(crate) *mut c::hexchat_hook, pub(crate) u64);
 unsafe impl Send for PrintEventListener {}
 unsafe impl Sync for PrintEventListener {}
 /// A handle to a registered window event listener.
 #[derive(Debug, Eq, PartialEq, Hash)]
-pub struct WindowEventListener(pub(crate) *mut c::hexchat_hook);
+pub struct WindowEventListener(pub(crate) *mut c::hexchat_hook, pub(crate) u64);
 unsafe impl Send for WindowEventListener {}
 unsafe impl Sync for WindowEventListener {}
 /// A handle to a registered raw server event listener.
 #[derive(Debug, Eq, PartialEq, Hash)]
-pub struct RawServerEventListener(pub(crate) *mut c::hexchat_hook);
+pub struct RawServerEventListener(pub(crate) *mut c::hexchat_hook, pub(crate) u64);
 unsafe impl Send for RawServerEventListener {}
 unsafe impl Sync for RawServerEventListener {}
 /// A handle to a registered timer task.
 #[derive(Debug, Eq, PartialEq, Hash)]
-pub struct TimerTask(pub(crate) *mut c::hexchat_hook);
+pub struct TimerTask(pub(crate) *mut TimerHookRef, pub(crate) u64);
 unsafe impl Send for TimerTask {}
 unsafe impl Sync for TimerTask {}
 /// A handle to a registered server event listener.
 #[derive(Debug, Eq, PartialEq, Hash)]
-pub struct ServerEventListener(pub(crate) *mut c::hexchat_hook);
+pub struct ServerEventListener(pub(crate) *mut c::hexchat_hook, pub(crate) u64);
 unsafe impl Send for ServerEventListener {}
 unsafe impl Sync for ServerEventListener {}
 /// A handle to a registered reply listener.
 #[derive(Debug, Eq, PartialEq, Hash)]
-pub struct ReplyListener(pub(crate) *mut c::hexchat_hook);
+pub struct ReplyListener(pub(crate) *mut c::hexchat_hook, pub(crate) u64);
 unsafe impl Send for ReplyListener {}
 unsafe impl Sync for ReplyListener {}
+/// A handle to a registered server error listener, made up of one hook per `ERR_*` response.
+#[derive(Debug, Eq, PartialEq, Hash)]
+pub struct ServerErrorListener(pub(crate) Vec<ServerEventListener>);
+unsafe impl Send for ServerErrorListener {}
+unsafe impl Sync for ServerErrorListener {}
+/// A handle to a registered file descriptor watcher.
+#[derive(Debug, Eq, PartialEq, Hash)]
+pub struct FdWatcher(pub(crate) *mut c::hexchat_hook, pub(crate) u64);
+unsafe impl Send for FdWatcher {}
+unsafe impl Sync for FdWatcher {}
+
+static NEXT_HOOK_ID: AtomicU64 = AtomicU64::new(0);
+
+// HexChat may reuse a hook's address once it is removed, including by HexChat itself, so handles
+// are told apart by an ID that is never reused.
+fn next_hook_id() -> u64 {
+    NEXT_HOOK_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A handle to a registered hook, such as a `Command` or a `TimerTask`.
+pub trait Hook
+where
+    Self: Sized,
+{
+    /// Unhooks this hook, as though by its corresponding `remove_*` or `deregister_*` function.
+    /// Unhooking a hook that has already been removed does nothing.
+    fn unhook(self);
+    /// Wraps this hook in a `HookGuard`, which will unhook it when dropped.
+    fn scoped(self) -> HookGuard<Self> {
+        HookGuard { hook: Some(self) }
+    }
+    #[doc(hidden)]
+    fn is_registered(&self) -> bool;
+}
+
+macro_rules! hook {
+    ($t:ident, $field:ident, $remove:ident) => {
+        impl Hook for $t {
+            fn unhook(self) {
+                $remove(self)
+            }
+            fn is_registered(&self) -> bool {
+                call::is_registered(|p| p.$field.contains(self))
+            }
+        }
+    };
+}
+
+hook!(Command, commands, deregister_command);
+hook!(
+    PrintEventListener,
+    print_events,
+    remove_print_event_listener
+);
+hook!(
+    WindowEventListener,
+    window_events,
+    remove_window_event_listener
+);
+hook!(
+    RawServerEventListener,
+    server_events,
+    remove_raw_server_event_listener
+);
+hook!(TimerTask, timer_tasks, remove_timer_task);
+hook!(
+    ServerEventListener,
+    typed_server_events,
+    remove_server_event_listener
+);
+hook!(FdWatcher, fd_watchers, remove_fd_watcher);
+
+impl Hook for ReplyListener {
+    fn unhook(self) {
+        remove_reply_listener(self)
+    }
+    fn is_registered(&self) -> bool {
+        call::is_registered(|p| {
+            p.typed_server_events
+                .contains(&ServerEventListener(self.0, self.1))
+        })
+    }
+}
+
+impl Hook for ServerErrorListener {
+    fn unhook(self) {
+        remove_server_error_listener(self)
+    }
+    fn is_registered(&self) -> bool {
+        call::is_registered(|p| {
+            self.0
+                .iter()
+                .any(|hook| p.typed_server_events.contains(hook))
+        })
+    }
+}
+
+/// A guard around a hook that unhooks it when dropped. Created by `Hook::scoped`.
+///
+/// If the plugin is unloaded before the guard is dropped, the hook is cleaned up with the rest of
+/// the plugin's hooks and the guard does nothing, so it is safe to store guards in your plugin's
+/// struct.
+#[derive(Debug)]
+pub struct HookGuard<T>
+where
+    T: Hook,
+{
+    hook: Option<T>,
+}
+
+impl<T> HookGuard<T>
+where
+    T: Hook,
+{
+    /// Consumes the guard without unhooking, returning the inner hook.
+    pub fn into_inner(mut self) -> T {
+        self.hook.take().unwrap()
+    }
+}
+
+impl<T> Deref for HookGuard<T>
+where
+    T: Hook,
+{
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.hook.as_ref().unwrap()
+    }
+}
+
+impl<T> Drop for HookGuard<T>
+where
+    T: Hook,
+{
+    fn drop(&mut self) {
+        if let Some(hook) = self.hook.take() {
+            if hook.is_registered() {
+                hook.unhook();
+            }
+        }
+    }
+}
 
 /// Registers a new command accessible to the user via `/<COMMAND> [args]`. Returns a
 /// corresponding object that can be passed to `deregister_command`.
@@ -55,14 +207,41 @@ unsafe impl Sync for ReplyListener {}
 /// The callback's signature is a slice of all the command arguments. If you intend to get command
 /// arguments, you should probably start at 1; argument 0 is the name of the command. The callback
 /// should return who the command event should be hidden from.
-pub fn register_command(
+pub fn register_command<R>(
+    name: &str,
+    help_text: &str,
+    priority: Priority,
+    function: impl Fn(&[String]) -> R + 'static,
+) -> Command
+where
+    R: CallbackResult<EatMode>,
+{
+    register_command_with_eol(name, help_text, priority, move |args| function(&args.words))
+}
+
+/// Registers a new command as defined in `register_command`, whose callback can also get the
+/// remainder of the command line with its original spacing intact.
+///
+/// Returns a corresponding object that can be passed to `deregister_command`.
+///
+/// # Callback
+///
+/// The callback's signature is the command's `HookArgs`, where argument 0 is the name of the
+/// command. The callback should return who the command event should be hidden from.
+pub fn register_command_with_eol<R>(
     name: &str,
     help_text: &str,
     priority: Priority,
-    function: impl Fn(&[String]) -> EatMode + 'static,
-) -> Command {
+    function: impl Fn(&HookArgs) -> R + 'static,
+) -> Command
+where
+    R: CallbackResult<EatMode>,
+{
+    let id = next_hook_id();
     let hook_ref = CommandHookRef {
-        function: Box::new(function),
+        id,
+        name: name.to_string(),
+        function: Box::new(move |args| function(args).into_result()),
     };
     let boxed = Box::new(hook_ref);
     let ptr = Box::into_raw(boxed);
@@ -78,15 +257,17 @@ pub fn register_command(
             ptr as _,
         )
     };
-    call::get_plugin().commands.insert(Command(hook_ptr));
-    Command(hook_ptr)
+    call::get_plugin().commands.insert(Command(hook_ptr, id));
+    Command(hook_ptr, id)
 }
 
 /// Deregisters a command registered by `register_command`.
 #[allow(clippy::needless_pass_by_value)]
 pub fn deregister_command(command: Command) {
-    dealloc_command(command.0);
-    call::get_plugin().commands.remove(&command);
+    let registered = call::get_plugin().commands.remove(&command);
+    if registered {
+        dealloc_command(command.0);
+    }
 }
 
 pub(crate) fn dealloc_command(command: *mut c::hexchat_hook) {
@@ -107,13 +288,19 @@ pub(crate) fn dealloc_command(command: *mut c::hexchat_hook) {
 /// The callback's signature is a slice of all the print event's arguments, followed by the time
 /// this message was printed. Note that the argument `$1` corresponds to `args[0]` and so forth. The
 /// callback should return who the event should be hidden from.
-pub fn add_print_event_listener(
+pub fn add_print_event_listener<R>(
     event: PrintEvent,
     priority: Priority,
-    function: impl Fn(&[String], DateTime<Utc>) -> EatMode + 'static,
-) -> PrintEventListener {
+    function: impl Fn(&[String], DateTime<Utc>) -> R + 'static,
+) -> PrintEventListener
+where
+    R: CallbackResult<EatMode>,
+{
+    let id = next_hook_id();
     let hook_ref = PrintHookRef {
-        function: Box::new(function),
+        id,
+        name: event.0,
+        function: Box::new(move |args, d| function(args, d).into_result()),
     };
     let boxed = Box::new(hook_ref);
     let ptr = Box::into_raw(boxed);
@@ -129,15 +316,17 @@ pub fn add_print_event_listener(
     };
     call::get_plugin()
         .print_events
-        .insert(PrintEventListener(hook_ptr));
-    PrintEventListener(hook_ptr)
+        .insert(PrintEventListener(hook_ptr, id));
+    PrintEventListener(hook_ptr, id)
 }
 
 /// Removes a listener added by `add_print_event_listener`.
 #[allow(clippy::needless_pass_by_value)]
 pub fn remove_print_event_listener(listener: PrintEventListener) {
-    dealloc_print_event_listener(listener.0);
-    call::get_plugin().print_events.remove(&listener);
+    let registered = call::get_plugin().print_events.remove(&listener);
+    if registered {
+        dealloc_print_event_listener(listener.0);
+    }
 }
 
 pub(crate) fn dealloc_print_event_listener(listener: *mut c::hexchat_hook) {
@@ -148,6 +337,28 @@ pub(crate) fn dealloc_print_event_listener(listener: *mut c::hexchat_hook) {
     }
 }
 
+/// Adds a listener for the print event corresponding to `T`, as defined in
+/// `add_print_event_listener`.
+///
+/// Returns a corresponding object that can be passed to `remove_print_event_listener`.
+///
+/// # Callback
+///
+/// The callback's signature is the event's arguments, followed by the time this message was
+/// printed. The callback should return who the event should be hidden from.
+pub fn add_typed_print_listener<T, R>(
+    priority: Priority,
+    function: impl Fn(T, DateTime<Utc>) -> R + 'static,
+) -> PrintEventListener
+where
+    T: TypedPrintEvent,
+    R: CallbackResult<EatMode>,
+{
+    add_print_event_listener(T::EVENT, priority, move |args, d| {
+        function(T::from_args(args), d)
+    })
+}
+
 /// Adds a listener for a particular `WindowEvent`. See `WindowEvent`'s docs for more details.
 ///
 /// Returns a corresponding object that can be passed to `remove_window_event_listener`.
@@ -157,13 +368,19 @@ pub(crate) fn dealloc_print_event_listener(listener: *mut c::hexchat_hook) {
 /// The callback's signature is a `ChannelRef` corresponding to the channel this event is regarding
 /// or the current channel if none applies. The callback should return who the event should be
 /// hidden from.
-pub fn add_window_event_listener(
+pub fn add_window_event_listener<R>(
     event: WindowEvent,
     priority: Priority,
-    function: impl Fn(ChannelRef) -> EatMode + 'static,
-) -> WindowEventListener {
+    function: impl Fn(ChannelRef) -> R + 'static,
+) -> WindowEventListener
+where
+    R: CallbackResult<EatMode>,
+{
+    let id = next_hook_id();
     let context_ref = ContextHookRef {
-        function: Box::new(function),
+        id,
+        name: event.0,
+        function: Box::new(move |c| function(c).into_result()),
     };
     let boxed = Box::new(context_ref);
     let ptr = Box::into_raw(boxed);
@@ -179,15 +396,17 @@ pub fn add_window_event_listener(
     };
     call::get_plugin()
         .window_events
-        .insert(WindowEventListener(hook_ptr));
-    WindowEventListener(hook_ptr)
+        .insert(WindowEventListener(hook_ptr, id));
+    WindowEventListener(hook_ptr, id)
 }
 
 /// Removes a listener added by `add_window_event_listener`.
 #[allow(clippy::needless_pass_by_value)]
 pub fn remove_window_event_listener(listener: WindowEventListener) {
-    dealloc_window_event_listener(listener.0);
-    call::get_plugin().window_events.remove(&listener);
+    let registered = call::get_plugin().window_events.remove(&listener);
+    if registered {
+        dealloc_window_event_listener(listener.0);
+    }
 }
 
 pub(crate) fn dealloc_window_event_listener(listener: *mut c::hexchat_hook) {
@@ -209,13 +428,40 @@ pub(crate) fn dealloc_window_event_listener(listener: *mut c::hexchat_hook) {
 /// event was sent. If you intend to get event arguments, you probably should start at 2, since
 /// argument 0 is the sender and argument 1 is the event name. The callback should return who the
 /// event should be hidden from.
-pub fn add_raw_server_event_listener(
+pub fn add_raw_server_event_listener<R>(
+    event: &str,
+    priority: Priority,
+    function: impl Fn(&[String], DateTime<Utc>) -> R + 'static,
+) -> RawServerEventListener
+where
+    R: CallbackResult<EatMode>,
+{
+    add_raw_server_event_listener_with_eol(event, priority, move |args, d| function(&args.words, d))
+}
+
+/// Adds a listener for raw server events as defined in `add_raw_server_event_listener`, whose
+/// callback can also get the untouched remainder of the line, such as the trailing parameter.
+///
+/// Returns a corresponding object suitable for passing to `remove_raw_server_event_listener`.
+///
+/// # Callback
+///
+/// The callback's signature is the event's `HookArgs`, followed by the time this event was sent.
+/// Argument 0 is the sender and argument 1 is the event name. The callback should return who the
+/// event should be hidden from.
+pub fn add_raw_server_event_listener_with_eol<R>(
     event: &str,
     priority: Priority,
-    function: impl Fn(&[String], DateTime<Utc>) -> EatMode + 'static,
-) -> RawServerEventListener {
+    function: impl Fn(&HookArgs, DateTime<Utc>) -> R + 'static,
+) -> RawServerEventListener
+where
+    R: CallbackResult<EatMode>,
+{
+    let id = next_hook_id();
     let server_ref = ServerHookRef {
-        function: Box::new(function),
+        id,
+        name: event.to_string(),
+        function: Box::new(move |args, d| function(args, d).into_result()),
     };
     let boxed = Box::new(server_ref);
     let ptr = Box::into_raw(boxed);
@@ -231,15 +477,17 @@ pub fn add_raw_server_event_listener(
     };
     call::get_plugin()
         .server_events
-        .insert(RawServerEventListener(hook_ptr));
-    RawServerEventListener(hook_ptr)
+        .insert(RawServerEventListener(hook_ptr, id));
+    RawServerEventListener(hook_ptr, id)
 }
 
 /// Removes a listener added by `add_raw_server_event_listener`.
 #[allow(clippy::needless_pass_by_value)]
 pub fn remove_raw_server_event_listener(listener: RawServerEventListener) {
-    dealloc_raw_server_event_listener(listener.0);
-    call::get_plugin().server_events.remove(&listener);
+    let registered = call::get_plugin().server_events.remove(&listener);
+    if registered {
+        dealloc_raw_server_event_listener(listener.0);
+    }
 }
 
 pub(crate) fn dealloc_raw_server_event_listener(listener: *mut c::hexchat_hook) {
@@ -259,38 +507,193 @@ pub(crate) fn dealloc_raw_server_event_listener(listener: *mut c::hexchat_hook)
 /// Right now the interval cannot be more than `i32::max_value()` milliseconds. If it is more
 /// than `i32::max_value()` milliseconds, it will be truncated to `i32::max_value()`
 /// milliseconds. This restriction will be lifted in the future.
-pub fn add_timer_task(interval: Duration, task: impl Fn() + 'static) -> TimerTask {
+pub fn add_timer_task<R>(interval: Duration, task: impl Fn() -> R + 'static) -> TimerTask
+where
+    R: CallbackResult<()>,
+{
+    add_timer_task_with_control(interval, move || {
+        task().into_result().map(|()| TimerControl::Continue)
+    })
+}
+
+/// Registers a task to be run once after a specified delay, as defined in `add_timer_task`.
+///
+/// Returns a corresponding object suitable for passing to `remove_timer_task`, if the task needs
+/// to be cancelled before it runs. The task is not run again even if it fails or panics.
+pub fn add_timer_once<R>(delay: Duration, task: impl FnOnce() -> R + 'static) -> TimerTask
+where
+    R: CallbackResult<()>,
+{
+    let task = Cell::new(Some(task));
+    add_timer(
+        delay,
+        TimerControl::Stop,
+        Box::new(move || {
+            if let Some(task) = task.take() {
+                task().into_result()?;
+            }
+            Ok(TimerControl::Stop)
+        }),
+    )
+}
+
+/// Registers a task to be run repeatedly, as defined in `add_timer_task`, which decides after
+/// each run whether to keep running and when.
+///
+/// Returns a corresponding object suitable for passing to `remove_timer_task`. The object stays
+/// valid if the task is rescheduled.
+///
+/// # Callback
+///
+/// The callback should return whether the task should run again after the same interval, after a
+/// different interval, or not at all. If the callback fails or panics, the task continues with the
+/// same interval.
+pub fn add_timer_task_with_control<R>(
+    interval: Duration,
+    task: impl Fn() -> R + 'static,
+) -> TimerTask
+where
+    R: CallbackResult<TimerControl>,
+{
+    add_timer(
+        interval,
+        TimerControl::Continue,
+        Box::new(move || task().into_result()),
+    )
+}
+
+fn add_timer(
+    interval: Duration,
+    fallback: TimerControl,
+    function: Box<dyn Fn() -> Result<TimerControl, String>>,
+) -> TimerTask {
+    let id = next_hook_id();
     let timer_ref = TimerHookRef {
-        function: Box::new(task),
+        id,
+        hook: Cell::new(ptr::null_mut()),
+        running: Cell::new(false),
+        removed: Cell::new(false),
+        fallback,
+        function,
     };
     let boxed = Box::new(timer_ref);
     let ptr = Box::into_raw(boxed);
+    let hook_ptr = unsafe { c!(hexchat_hook_timer, timer_ms(interval), timer_hook, ptr as _) };
+    unsafe {
+        (*ptr).hook.set(hook_ptr);
+    }
+    call::get_plugin().timer_tasks.insert(TimerTask(ptr, id));
+    TimerTask(ptr, id)
+}
+
+fn timer_ms(interval: Duration) -> c_int {
     let ms = interval.as_millis();
-    let ms = if ms > i32::max_value() as u128 {
+    if ms > i32::max_value() as u128 {
         i32::max_value()
     } else {
         ms as i32
-    }; //todo implement a way to handle u128-length timeouts
-    let hook_ptr = unsafe { c!(hexchat_hook_timer, ms, timer_hook, ptr as _) };
-    call::get_plugin().timer_tasks.insert(TimerTask(hook_ptr));
-    TimerTask(hook_ptr)
+    } //todo implement a way to handle u128-length timeouts
 }
 
-/// Removes a timer task added by `add_timer_task`.
+/// Removes a timer task added by `add_timer_task` or its variants. Removing a task that has
+/// already stopped itself does nothing.
 #[allow(clippy::needless_pass_by_value)]
 pub fn remove_timer_task(task: TimerTask) {
-    dealloc_timer_task(task.0);
-    call::get_plugin().timer_tasks.remove(&task);
+    let registered = call::get_plugin().timer_tasks.remove(&task);
+    if registered {
+        dealloc_timer_task(task.0);
+    }
 }
 
-pub(crate) fn dealloc_timer_task(task: *mut c::hexchat_hook) {
+pub(crate) fn dealloc_timer_task(task: *mut TimerHookRef) {
     unsafe {
-        let ptr = c!(hexchat_unhook, task);
-        let ptr = ptr as *mut TimerHookRef;
+        c!(hexchat_unhook, (*task).hook.get());
+        // A task removed from within its own callback is freed once the callback returns.
+        if (*task).running.get() {
+            (*task).removed.set(true);
+        } else {
+            Box::from_raw(task);
+        }
+    }
+}
+
+/// What a timer task added with `add_timer_task_with_control` should do after it runs.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TimerControl {
+    /// Run again after the same interval.
+    Continue,
+    /// Stop running, as though by `remove_timer_task`.
+    Stop,
+    /// Run again after a new interval, which is used from then on.
+    Reschedule(Duration),
+}
+
+/// Registers a watcher on a file descriptor or socket, which will be called whenever one of the
+/// conditions in `flags` is met.
+///
+/// Returns a corresponding object suitable for passing to `remove_fd_watcher`.
+///
+/// # Callback
+///
+/// The callback's signature is the file descriptor being watched, followed by the conditions that
+/// were met. The watcher stays registered until `remove_fd_watcher` is called.
+///
+/// # Note
+///
+/// On Windows, `FdFlags::NOT_SOCKET` must be set if `fd` is not a socket.
+pub fn add_fd_watcher<R>(
+    fd: i32,
+    flags: FdFlags,
+    callback: impl Fn(i32, FdFlags) -> R + 'static,
+) -> FdWatcher
+where
+    R: CallbackResult<()>,
+{
+    let id = next_hook_id();
+    let fd_ref = FdHookRef {
+        id,
+        function: Box::new(move |fd, flags| callback(fd, flags).into_result()),
+    };
+    let boxed = Box::new(fd_ref);
+    let ptr = Box::into_raw(boxed);
+    let hook_ptr = unsafe { c!(hexchat_hook_fd, fd, flags.bits(), fd_hook, ptr as _) };
+    call::get_plugin()
+        .fd_watchers
+        .insert(FdWatcher(hook_ptr, id));
+    FdWatcher(hook_ptr, id)
+}
+
+/// Removes a file descriptor watcher added by `add_fd_watcher`.
+#[allow(clippy::needless_pass_by_value)]
+pub fn remove_fd_watcher(watcher: FdWatcher) {
+    let registered = call::get_plugin().fd_watchers.remove(&watcher);
+    if registered {
+        dealloc_fd_watcher(watcher.0);
+    }
+}
+
+pub(crate) fn dealloc_fd_watcher(watcher: *mut c::hexchat_hook) {
+    unsafe {
+        let ptr = c!(hexchat_unhook, watcher);
+        let ptr = ptr as *mut FdHookRef;
         Box::from_raw(ptr);
     }
 }
 
+bitflags! {
+    /// The conditions a file descriptor watcher can wait for.
+    pub struct FdFlags: i32 {
+        /// The file descriptor has data available to read.
+        const READ = 1;
+        /// The file descriptor can be written to.
+        const WRITE = 1 << 1;
+        /// An exceptional condition has occurred on the file descriptor.
+        const EXCEPTION = 1 << 2;
+        /// The file descriptor is not a socket. Only meaningful on Windows.
+        const NOT_SOCKET = 1 << 3;
+    }
+}
+
 /// Adds a listener for server events, i.e. commands coming from the server.
 ///
 /// Returns a corresponding object suitable for passing to `remove_server_event_listener`.
@@ -299,17 +702,22 @@ pub(crate) fn dealloc_timer_task(task: *mut c::hexchat_hook) {
 ///
 /// The callback's signature is the event itself, followed by the time this event was sent. The
 /// callback should return who the event should be hidden from.
-pub fn add_server_event_listener<T>(
+pub fn add_server_event_listener<T, R>(
     priority: Priority,
-    function: impl Fn(T, DateTime<Utc>) -> EatMode + 'static,
+    function: impl Fn(T, DateTime<Utc>) -> R + 'static,
 ) -> ServerEventListener
 where
     T: ServerEvent,
+    R: CallbackResult<EatMode>,
 {
+    let id = next_hook_id();
     let server_ref = TypedServerHookRef {
+        id,
+        kind: CallbackKind::ServerEventListener,
+        name: T::NAME,
         function: Box::new(move |w, l, d| unsafe {
             let t = T::create(w, l);
-            function(t, d)
+            function(t, d).into_result()
         }),
     };
     let boxed = Box::new(server_ref);
@@ -326,15 +734,17 @@ where
     };
     call::get_plugin()
         .typed_server_events
-        .insert(ServerEventListener(hook_ptr));
-    ServerEventListener(hook_ptr)
+        .insert(ServerEventListener(hook_ptr, id));
+    ServerEventListener(hook_ptr, id)
 }
 
 /// Removes a server event listener added by `add_server_event_listener`.
 #[allow(clippy::needless_pass_by_value)]
 pub fn remove_server_event_listener(listener: ServerEventListener) {
-    dealloc_server_event_listener(listener.0);
-    call::get_plugin().typed_server_events.remove(&listener);
+    let registered = call::get_plugin().typed_server_events.remove(&listener);
+    if registered {
+        dealloc_server_event_listener(listener.0);
+    }
 }
 
 pub(crate) fn dealloc_server_event_listener(listener: *mut c::hexchat_hook) {
@@ -353,61 +763,49 @@ pub(crate) fn dealloc_server_event_listener(listener: *mut c::hexchat_hook) {
 ///
 /// The callback's signature is the reply itself, followed by the time this reply was sent. The
 /// callback should return who the reply should be hidden from.
-pub fn add_reply_listener<T>(
+pub fn add_reply_listener<T, R>(
     priority: Priority,
-    function: impl Fn(T, DateTime<Utc>) -> EatMode + 'static,
+    function: impl Fn(T, DateTime<Utc>) -> R + 'static,
 ) -> ReplyListener
 where
     T: ServerReply,
+    R: CallbackResult<EatMode>,
 {
-    let server_ref = TypedServerHookRef {
-        function: Box::new(move |w, l, d| unsafe {
+    let listener = hook_reply(
+        priority,
+        T::ID,
+        Box::new(move |w, l, d| unsafe {
             let t = T::create(w, l);
             if let Some(t) = t {
-                function(t, d)
+                function(t, d).into_result()
             } else {
-                eprintln!("Invalid response '{}'", from_cstring(*l));
-                EatMode::None
+                Err(format!("Invalid response '{}'", from_cstring(*l.offset(1))))
             }
         }),
-    };
-    let boxed = Box::new(server_ref);
-    let ptr = Box::into_raw(boxed);
-    let event = to_cstring(T::ID);
-    let hook_ptr = unsafe {
-        c!(
-            hexchat_hook_server_attrs,
-            event.as_ptr(),
-            c_int::from(priority.0),
-            server_event_hook,
-            ptr as _,
-        )
-    };
-    call::get_plugin()
-        .typed_server_events
-        .insert(ServerEventListener(hook_ptr));
-    ReplyListener(hook_ptr)
+    );
+    ReplyListener(listener.0, listener.1)
 }
 
 /// Removes a reply listener added by `add_reply_listener`.
 #[allow(clippy::needless_pass_by_value)]
 pub fn remove_reply_listener(listener: ReplyListener) {
-    remove_server_event_listener(ServerEventListener(listener.0));
+    remove_server_event_listener(ServerEventListener(listener.0, listener.1));
 }
 
 /// Adds a reply listener as defined in `add_reply_listener`, and removes it after receiving a
 /// single reply.
-pub fn add_reply_listener_once<T>(
+pub fn add_reply_listener_once<T, R>(
     priority: Priority,
-    function: impl Fn(T, DateTime<Utc>) -> EatMode + 'static,
+    function: impl Fn(T, DateTime<Utc>) -> R + 'static,
 ) where
     T: ServerReply,
+    R: CallbackResult<EatMode>,
 {
     let (tx, rx) = mpsc::channel();
     let listener = add_reply_listener(priority, move |t, d| {
         let listener = rx.recv().unwrap();
         remove_reply_listener(listener);
-        function(t, d)
+        function(t, d).into_result()
     });
     tx.send(listener).ok();
 }
@@ -417,71 +815,158 @@ pub fn add_reply_listener_once<T>(
 ///
 /// This will not eat the `U` event, and if you wish to listen to it it must be
 /// listened to separately (likely using `add_reply_listener_once`).
-pub fn add_reply_listener_until<T, U, F>(priority: Priority, function: F)
+pub fn add_reply_listener_until<T, U, F, R>(priority: Priority, function: F)
 where
     T: ServerReply,
     U: ServerReply,
-    F: Fn(T, DateTime<Utc>) -> EatMode + 'static,
+    F: Fn(T, DateTime<Utc>) -> R + 'static,
+    R: CallbackResult<EatMode>,
 {
     let listener = add_reply_listener(priority, function);
     add_reply_listener_once(priority, move |_t: U, _d| {
-        remove_reply_listener(ReplyListener(listener.0));
+        remove_reply_listener(ReplyListener(listener.0, listener.1));
         EatMode::None
     });
 }
 
+/// Adds a listener for all the server errors in `ServerError`, i.e. the numeric `ERR_*` messages.
+///
+/// Returns a corresponding object suitable for passing to `remove_server_error_listener`.
+///
+/// # Callback
+///
+/// The callback's signature is the error itself, followed by the time this error was sent. The
+/// callback should return who the error should be hidden from.
+pub fn add_server_error_listener<R>(
+    priority: Priority,
+    function: impl Fn(ServerError, DateTime<Utc>) -> R + 'static,
+) -> ServerErrorListener
+where
+    R: CallbackResult<EatMode>,
+{
+    let function = Rc::new(function);
+    let hooks = ServerError::IDS
+        .iter()
+        .map(|&id| {
+            let function = function.clone();
+            hook_reply(
+                priority,
+                id,
+                Box::new(move |w, l, d| unsafe {
+                    match ServerError::create(w, l) {
+                        Some(e) => function(e, d).into_result(),
+                        None => Err(format!("Invalid response '{}'", from_cstring(*l.offset(1)))),
+                    }
+                }),
+            )
+        })
+        .collect();
+    ServerErrorListener(hooks)
+}
+
+/// Removes a server error listener added by `add_server_error_listener`.
+#[allow(clippy::needless_pass_by_value)]
+pub fn remove_server_error_listener(listener: ServerErrorListener) {
+    for hook in listener.0 {
+        remove_server_event_listener(hook);
+    }
+}
+
+/// Hooks the server message `id` with a typed callback, recording it with the plugin's server
+/// event listeners.
+fn hook_reply(
+    priority: Priority,
+    id: &'static str,
+    function: TypedServerFn,
+) -> ServerEventListener {
+    let id = next_hook_id();
+    let server_ref = TypedServerHookRef {
+        id,
+        kind: CallbackKind::ReplyListener,
+        name: id,
+        function,
+    };
+    let boxed = Box::new(server_ref);
+    let ptr = Box::into_raw(boxed);
+    let event = to_cstring(id);
+    let hook_ptr = unsafe {
+        c!(
+            hexchat_hook_server_attrs,
+            event.as_ptr(),
+            c_int::from(priority.0),
+            server_event_hook,
+            ptr as _,
+        )
+    };
+    call::get_plugin()
+        .typed_server_events
+        .insert(ServerEventListener(hook_ptr, id));
+    ServerEventListener(hook_ptr, id)
+}
+
 struct CommandHookRef {
-    function: Box<dyn Fn(&[String]) -> EatMode>,
+    id: u64,
+    name: String,
+    function: Box<dyn Fn(&HookArgs) -> Result<EatMode, String>>,
 }
 
 struct PrintHookRef {
-    function: Box<dyn Fn(&[String], DateTime<Utc>) -> EatMode>,
+    id: u64,
+    name: &'static str,
+    function: Box<dyn Fn(&[String], DateTime<Utc>) -> Result<EatMode, String>>,
 }
 
 struct ContextHookRef {
-    function: Box<dyn Fn(ChannelRef) -> EatMode>,
+    id: u64,
+    name: &'static str,
+    function: Box<dyn Fn(ChannelRef) -> Result<EatMode, String>>,
 }
 
 struct ServerHookRef {
-    function: Box<dyn Fn(&[String], DateTime<Utc>) -> EatMode>,
+    id: u64,
+    name: String,
+    function: Box<dyn Fn(&HookArgs, DateTime<Utc>) -> Result<EatMode, String>>,
 }
 
-struct TimerHookRef {
-    function: Box<dyn Fn()>,
+pub(crate) struct TimerHookRef {
+    id: u64,
+    hook: Cell<*mut c::hexchat_hook>,
+    running: Cell<bool>,
+    removed: Cell<bool>,
+    fallback: TimerControl,
+    function: Box<dyn Fn() -> Result<TimerControl, String>>,
 }
 
+struct FdHookRef {
+    id: u64,
+    function: Box<dyn Fn(i32, FdFlags) -> Result<(), String>>,
+}
+
+type TypedServerFn =
+    Box<dyn Fn(*mut *mut c_char, *mut *mut c_char, DateTime<Utc>) -> Result<EatMode, String>>;
+
 struct TypedServerHookRef {
-    function: Box<dyn Fn(*mut *mut c_char, *mut *mut c_char, DateTime<Utc>) -> EatMode>,
+    id: u64,
+    kind: CallbackKind,
+    name: &'static str,
+    function: TypedServerFn,
 }
 
 unsafe extern "C" fn command_hook(
     word: *mut *mut c_char,
-    _word_eol: *mut *mut c_char,
+    word_eol: *mut *mut c_char,
     user_data: *mut c_void,
 ) -> c_int {
     let user_data = user_data as *mut CommandHookRef;
-    let mut vec = Vec::new();
-    for i in 1..32 {
-        let offset = word.offset(i);
-        if !offset.is_null() {
-            let ptr = *offset;
-            if !ptr.is_null() {
-                let cstr = from_cstring(ptr);
-                vec.push(cstr);
-            }
-        }
-    }
-    let res = match panic::catch_unwind(AssertUnwindSafe(|| ((*user_data).function)(&vec))) {
-        Ok(eat) => eat,
-        Err(e) => {
-            crate::print_plain(&format!("Error in command '/{}'", &vec.join(" ")));
-            if let Some(string) = (*e).downcast_ref::<&str>() {
-                crate::print_plain(&format!("Error message: {}", string));
-            }
-            EatMode::All
-        }
-    };
-    res as _
+    let args = HookArgs::new(word, word_eol);
+    let function = &(*user_data).function;
+    run_callback(
+        CallbackKind::Command,
+        &(*user_data).name,
+        (*user_data).id,
+        || function(&args),
+        EatMode::All,
+    ) as _
 }
 
 unsafe extern "C" fn print_hook(
@@ -503,49 +988,96 @@ unsafe extern "C" fn print_hook(
     }
     let naive = NaiveDateTime::from_timestamp((*attrs).server_time_utc as _, 0);
     let utc = Utc.from_utc_datetime(&naive);
-    panic::catch_unwind(AssertUnwindSafe(|| ((*user_data).function)(&vec, utc)))
-        .unwrap_or(EatMode::None) as _
+    run_callback(
+        CallbackKind::PrintEventListener,
+        (*user_data).name,
+        (*user_data).id,
+        || ((*user_data).function)(&vec, utc),
+        EatMode::None,
+    ) as _
 }
 
 unsafe extern "C" fn context_hook(_word: *mut *mut c_char, user_data: *mut c_void) -> c_int {
     let user_data = user_data as *mut ContextHookRef;
     let ctx = c!(hexchat_get_context);
     let cref = ChannelRef { handle: ctx };
-    panic::catch_unwind(AssertUnwindSafe(|| ((*user_data).function)(cref))).unwrap_or(EatMode::None)
-        as _
+    run_callback(
+        CallbackKind::WindowEventListener,
+        (*user_data).name,
+        (*user_data).id,
+        || ((*user_data).function)(cref),
+        EatMode::None,
+    ) as _
 }
 
 unsafe extern "C" fn server_hook(
     word: *mut *mut c_char,
-    _word_eol: *mut *mut c_char,
+    word_eol: *mut *mut c_char,
     attrs: *mut c::hexchat_event_attrs,
     user_data: *mut c_void,
 ) -> c_int {
     let user_data = user_data as *mut ServerHookRef;
-    let mut vec = Vec::new();
-    for i in 1..32 {
-        let offset = word.offset(i);
-        if !offset.is_null() {
-            let ptr = *offset;
-            if !ptr.is_null() {
-                let cstr = from_cstring(ptr);
-                vec.push(cstr);
-            }
-        }
-    }
+    let args = HookArgs::new(word, word_eol);
     let naive = NaiveDateTime::from_timestamp((*attrs).server_time_utc as _, 0);
     let utc = Utc.from_utc_datetime(&naive);
-    panic::catch_unwind(AssertUnwindSafe(|| ((*user_data).function)(&vec, utc)))
-        .unwrap_or(EatMode::None) as _
+    run_callback(
+        CallbackKind::RawServerEventListener,
+        &(*user_data).name,
+        (*user_data).id,
+        || ((*user_data).function)(&args, utc),
+        EatMode::None,
+    ) as _
 }
 
 unsafe extern "C" fn timer_hook(user_data: *mut c_void) -> c_int {
     let user_data = user_data as *mut TimerHookRef;
-    panic::catch_unwind(AssertUnwindSafe(|| {
-        ((*user_data).function)();
-    }))
-    .ok();
-    EatMode::All as _
+    (*user_data).running.set(true);
+    let control = run_callback(
+        CallbackKind::TimerTask,
+        "",
+        (*user_data).id,
+        || ((*user_data).function)(),
+        (*user_data).fallback,
+    );
+    (*user_data).running.set(false);
+    if (*user_data).removed.get() {
+        Box::from_raw(user_data);
+        return 0;
+    }
+    match control {
+        TimerControl::Continue => 1,
+        TimerControl::Stop => {
+            // Returning 0 makes HexChat remove the hook itself.
+            call::get_plugin()
+                .timer_tasks
+                .remove(&TimerTask(user_data, (*user_data).id));
+            Box::from_raw(user_data);
+            0
+        }
+        TimerControl::Reschedule(interval) => {
+            let hook_ptr = c!(
+                hexchat_hook_timer,
+                timer_ms(interval),
+                timer_hook,
+                user_data as _
+            );
+            (*user_data).hook.set(hook_ptr);
+            0
+        }
+    }
+}
+
+unsafe extern "C" fn fd_hook(fd: c_int, flags: c_int, user_data: *mut c_void) -> c_int {
+    let user_data = user_data as *mut FdHookRef;
+    let flags = FdFlags::from_bits_truncate(flags);
+    run_callback(
+        CallbackKind::FdWatcher,
+        &fd.to_string(),
+        (*user_data).id,
+        || ((*user_data).function)(fd, flags),
+        (),
+    );
+    1
 }
 
 unsafe extern "C" fn server_event_hook(
@@ -557,10 +1089,90 @@ unsafe extern "C" fn server_event_hook(
     let user_data = user_data as *mut TypedServerHookRef;
     let naive = NaiveDateTime::from_timestamp((*attrs).server_time_utc as _, 0);
     let utc = Utc.from_utc_datetime(&naive);
-    panic::catch_unwind(AssertUnwindSafe(|| {
-        ((*user_data).function)(word, word_eol, utc) as c_int
-    }))
-    .unwrap_or(EatMode::None as c_int)
+    run_callback(
+        (*user_data).kind,
+        (*user_data).name,
+        (*user_data).id,
+        || ((*user_data).function)(word, word_eol, utc),
+        EatMode::None,
+    ) as _
+}
+
+/// Runs a hook's callback, reporting any error or panic to the error sink, and recording how long
+/// it took if the `profiling` feature is enabled. `id` identifies the hook for profiling.
+fn run_callback<T>(
+    kind: CallbackKind,
+    name: &str,
+    id: u64,
+    function: impl FnOnce() -> Result<T, String>,
+    default: T,
+) -> T {
+    #[cfg(feature = "profiling")]
+    let start = Instant::now();
+    let res = panic::catch_unwind(AssertUnwindSafe(function));
+    #[cfg(feature = "profiling")]
+    crate::profiling::record(kind, name, id, start.elapsed());
+    #[cfg(not(feature = "profiling"))]
+    let _ = id;
+    error::handle(kind, name, res, default)
+}
+
+/// The arguments to a command or raw server event.
+///
+/// HexChat splits the line into at most 31 words; `words` holds them, and `rest_from` gives the
+/// remainder of the line starting at any given word, including anything past the 31st word.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HookArgs {
+    words: Vec<String>,
+    word_eol: Vec<String>,
+    len: usize,
+}
+
+impl HookArgs {
+    unsafe fn new(word: *mut *mut c_char, word_eol: *mut *mut c_char) -> Self {
+        let mut words = Vec::with_capacity(31);
+        let mut eols = Vec::with_capacity(31);
+        // Both arrays always have 32 slots, with unused ones set to empty strings; slot 0 is
+        // reserved.
+        for i in 1..32 {
+            words.push(from_cstring_opt(*word.offset(i)).unwrap_or_default());
+            eols.push(from_cstring_opt(*word_eol.offset(i)).unwrap_or_default());
+        }
+        let len = words
+            .iter()
+            .rposition(|w| !w.is_empty())
+            .map_or(0, |i| i + 1);
+        Self {
+            words,
+            word_eol: eols,
+            len,
+        }
+    }
+    /// Gets all the words, not including any unused trailing slots.
+    pub fn words(&self) -> &[String] {
+        &self.words[..self.len]
+    }
+    /// Gets a particular word, or `None` if there are not that many.
+    pub fn word(&self, index: usize) -> Option<&str> {
+        self.words().get(index).map(String::as_str)
+    }
+    /// Gets the remainder of the line starting at a particular word, exactly as it was received,
+    /// or `None` if there are not that many words. For server events this includes the leading
+    /// `:` of the trailing parameter.
+    pub fn rest_from(&self, index: usize) -> Option<&str> {
+        self.word_eol[..self.len].get(index).map(String::as_str)
+    }
+    /// Gets the number of words.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    /// Gets whether there are no words at all.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub(crate) fn word_eols(&self) -> &[String] {
+        &self.word_eol[..self.len]
+    }
 }
 
 /// The priority of an event listener or command.
@@ -586,6 +1198,7 @@ impl Priority {
 }
 
 /// Represents who to hide an event or command from.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum EatMode {
     /// Do not hide the event or command from anyone. Plugins and HexChat will continue to receive
     /// this event or command, even if you have already processed it.
@@ -601,3 +1214,85 @@ pub enum EatMode {
     /// in most cases.
     All,
 }
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::print_plain;
+    use crate::testing::MockHexchat;
+    use std::time::Duration;
+
+    #[test]
+    fn server_error_listener() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let before = mock.hook_count();
+        let listener = add_server_error_listener(Priority::NORMAL, |e, _| {
+            print_plain(&format!("{} {}", e.id(), e.message()));
+            EatMode::Hexchat
+        });
+        assert_eq!(mock.hook_count(), before + ServerError::IDS.len());
+        assert!(listener.is_registered());
+        let eat =
+            mock.inject_server_line(":irc.example.com 433 * tester :Nickname is already in use");
+        assert_eq!(eat, EatMode::Hexchat);
+        let eat = mock.inject_server_line(":irc.example.com 372 tester :- Welcome");
+        assert_eq!(eat, EatMode::None);
+        assert_eq!(mock.printed(), ["433 Nickname is already in use"]);
+        remove_server_error_listener(listener);
+        assert_eq!(mock.hook_count(), before);
+        mock.clear();
+        mock.inject_server_line(":irc.example.com 401 tester ghost :No such nick/channel");
+        assert!(mock.printed().is_empty());
+    }
+
+    #[test]
+    fn stale_timer_task() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        mock.advance_time(Duration::from_millis(0));
+        let old = add_timer_once(Duration::from_secs(1), || print_plain("once"));
+        mock.advance_time(Duration::from_secs(1));
+        assert!(!old.is_registered());
+        // Likely to be given the box that `old` pointed to.
+        let new = add_timer_task(Duration::from_secs(1), || print_plain("tick"));
+        remove_timer_task(old);
+        assert!(new.is_registered());
+        mock.advance_time(Duration::from_secs(1));
+        assert_eq!(mock.printed(), ["once", "tick"]);
+    }
+
+    #[test]
+    fn stale_hook_guard() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let old = add_raw_server_event_listener("PING", Priority::NORMAL, |_, _| EatMode::None);
+        let stale = RawServerEventListener(old.0, old.1).scoped();
+        remove_raw_server_event_listener(old);
+        // Likely to be given the hook that `old` pointed to.
+        let new = add_raw_server_event_listener("PING", Priority::NORMAL, |_, _| {
+            print_plain("ping");
+            EatMode::None
+        });
+        assert!(!stale.is_registered());
+        drop(stale);
+        assert!(new.is_registered());
+        mock.inject_server_line("PING :irc.example.com");
+        assert_eq!(mock.printed(), ["ping"]);
+    }
+
+    #[test]
+    fn failed_timer_once() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        mock.advance_time(Duration::from_millis(0));
+        let task = add_timer_once(Duration::from_secs(1), || {
+            print_plain("once");
+            Err::<(), _>("failed")
+        });
+        mock.advance_time(Duration::from_secs(1));
+        assert!(!task.is_registered());
+        mock.advance_time(Duration::from_secs(1));
+        assert_eq!(mock.printed().iter().filter(|l| *l == "once").count(), 1);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::print_event::TypedPrintEvent;
+use crate::reply::ServerReply;
+use crate::server_event::ServerEvent;
+use crate::{
+    add_fd_watcher, add_print_event_listener, add_raw_server_event_listener, add_reply_listener,
+    add_server_event_listener, add_timer_task, add_typed_print_listener, add_window_event_listener,
+    register_command, CallbackResult, ChannelRef, Command, EatMode, FdFlags, FdWatcher, PrintEvent,
+    PrintEventListener, Priority, RawServerEventListener, ReplyListener, ServerEventListener,
+    TimerTask, WindowEvent, WindowEventListener,
+};
+use chrono::{DateTime, Utc};
+use std::cell::RefCell;
+use std::time::Duration;
+
+/// Registers a new command as defined in `register_command`, whose callback may mutate its state.
+///
+/// If the command is run again from within its own callback, the inner call is not passed to the
+/// callback, and is instead left for HexChat and other plugins to handle.
+pub fn register_command_mut<R>(
+    name: &str,
+    help_text: &str,
+    priority: Priority,
+    function: impl FnMut(&[String]) -> R + 'static,
+) -> Command
+where
+    R: CallbackResult<EatMode>,
+{
+    let function = RefCell::new(function);
+    register_command(name, help_text, priority, move |word| {
+        match function.try_borrow_mut() {
+            Ok(mut function) => function(word).into_result(),
+            Err(_) => Ok(EatMode::None),
+        }
+    })
+}
+
+/// Adds a listener as defined in `add_print_event_listener`, whose callback may mutate its state.
+///
+/// If the event is printed again from within the callback, the inner event is not passed to the
+/// callback.
+pub fn add_print_event_listener_mut<R>(
+    event: PrintEvent,
+    priority: Priority,
+    function: impl FnMut(&[String], DateTime<Utc>) -> R + 'static,
+) -> PrintEventListener
+where
+    R: CallbackResult<EatMode>,
+{
+    let function = RefCell::new(function);
+    add_print_event_listener(event, priority, move |args, d| {
+        match function.try_borrow_mut() {
+            Ok(mut function) => function(args, d).into_result(),
+            Err(_) => Ok(EatMode::None),
+        }
+    })
+}
+
+/// Adds a listener as defined in `add_typed_print_listener`, whose callback may mutate its state.
+///
+/// If the event is printed again from within the callback, the inner event is not passed to the
+/// callback.
+pub fn add_typed_print_listener_mut<T, R>(
+    priority: Priority,
+    function: impl FnMut(T, DateTime<Utc>) -> R + 'static,
+) -> PrintEventListener
+where
+    T: TypedPrintEvent,
+    R: CallbackResult<EatMode>,
+{
+    let function = RefCell::new(function);
+    add_typed_print_listener(priority, move |t: T, d| match function.try_borrow_mut() {
+        Ok(mut function) => function(t, d).into_result(),
+        Err(_) => Ok(EatMode::None),
+    })
+}
+
+/// Adds a listener as defined in `add_window_event_listener`, whose callback may mutate its state.
+///
+/// If the event fires again from within the callback, the inner event is not passed to the
+/// callback.
+pub fn add_window_event_listener_mut<R>(
+    event: WindowEvent,
+    priority: Priority,
+    function: impl FnMut(ChannelRef) -> R + 'static,
+) -> WindowEventListener
+where
+    R: CallbackResult<EatMode>,
+{
+    let function = RefCell::new(function);
+    add_window_event_listener(event, priority, move |channel| {
+        match function.try_borrow_mut() {
+            Ok(mut function) => function(channel).into_result(),
+            Err(_) => Ok(EatMode::None),
+        }
+    })
+}
+
+/// Adds a listener as defined in `add_raw_server_event_listener`, whose callback may mutate its
+/// state.
+///
+/// If the event is received again from within the callback, the inner event is not passed to the
+/// callback.
+pub fn add_raw_server_event_listener_mut<R>(
+    event: &str,
+    priority: Priority,
+    function: impl FnMut(&[String], DateTime<Utc>) -> R + 'static,
+) -> RawServerEventListener
+where
+    R: CallbackResult<EatMode>,
+{
+    let function = RefCell::new(function);
+    add_raw_server_event_listener(event, priority, move |word, d| {
+        match function.try_borrow_mut() {
+            Ok(mut function) => function(word, d).into_result(),
+            Err(_) => Ok(EatMode::None),
+        }
+    })
+}
+
+/// Adds a listener as defined in `add_server_event_listener`, whose callback may mutate its state.
+///
+/// If the event is received again from within the callback, the inner event is not passed to the
+/// callback.
+pub fn add_server_event_listener_mut<T, R>(
+    priority: Priority,
+    function: impl FnMut(T, DateTime<Utc>) -> R + 'static,
+) -> ServerEventListener
+where
+    T: ServerEvent,
+    R: CallbackResult<EatMode>,
+{
+    let function = RefCell::new(function);
+    add_server_event_listener(priority, move |t: T, d| match function.try_borrow_mut() {
+        Ok(mut function) => function(t, d).into_result(),
+        Err(_) => Ok(EatMode::None),
+    })
+}
+
+/// Adds a listener as defined in `add_reply_listener`, whose callback may mutate its state.
+///
+/// If the reply is received again from within the callback, the inner reply is not passed to the
+/// callback.
+pub fn add_reply_listener_mut<T, R>(
+    priority: Priority,
+    function: impl FnMut(T, DateTime<Utc>) -> R + 'static,
+) -> ReplyListener
+where
+    T: ServerReply,
+    R: CallbackResult<EatMode>,
+{
+    let function = RefCell::new(function);
+    add_reply_listener(priority, move |t: T, d| match function.try_borrow_mut() {
+        Ok(mut function) => function(t, d).into_result(),
+        Err(_) => Ok(EatMode::None),
+    })
+}
+
+/// Registers a task as defined in `add_timer_task`, which may mutate its state.
+pub fn add_timer_task_mut<R>(interval: Duration, task: impl FnMut() -> R + 'static) -> TimerTask
+where
+    R: CallbackResult<()>,
+{
+    let task = RefCell::new(task);
+    add_timer_task(interval, move || match task.try_borrow_mut() {
+        Ok(mut task) => task().into_result(),
+        Err(_) => Ok(()),
+    })
+}
+
+/// Registers a watcher as defined in `add_fd_watcher`, whose callback may mutate its state.
+pub fn add_fd_watcher_mut<R>(
+    fd: i32,
+    flags: FdFlags,
+    callback: impl FnMut(i32, FdFlags) -> R + 'static,
+) -> FdWatcher
+where
+    R: CallbackResult<()>,
+{
+    let callback = RefCell::new(callback);
+    add_fd_watcher(fd, flags, move |fd, flags| {
+        match callback.try_borrow_mut() {
+            Ok(mut callback) => callback(fd, flags).into_result(),
+            Err(_) => Ok(()),
+        }
+    })
+}
@@ -0,0 +1,602 @@
+use crate::{IrcIdent, UserString};
+use std::error::Error;
+use std::ffi::CString;
+use std::fmt::{Display, Formatter, Result as FmtResult, Write};
+use std::os::raw::c_char;
+use std::str::FromStr;
+
+/// A single IRC message, as defined by RFC 1459 and 2812, along with any IRCv3 message tags.
+///
+/// Messages can be parsed from a line with `FromStr`, and converted back to one with `Display`.
+///
+/// # Example
+///
+/// ```rust
+/// use hexchat::irc::Message;
+///
+/// let message: Message = "@time=2019-01-01T00:00:00.000Z :nick!user@irc.example.com PRIVMSG #channel :Hello!"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(message.get_tag("time"), Some("2019-01-01T00:00:00.000Z"));
+/// assert_eq!(message.get_command(), "PRIVMSG");
+/// assert_eq!(message.get_params(), &["#channel", "Hello!"]);
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Message {
+    tags: Vec<(String, String)>,
+    prefix: Option<Prefix>,
+    command: String,
+    params: Vec<String>,
+}
+
+/// The source of a `Message`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Prefix {
+    /// The message was sent by a user.
+    User(UserString),
+    /// The message was sent by a user, but the prefix only has their nick.
+    Nick(IrcIdent),
+    /// The message was sent by a server.
+    Server(String),
+    /// A prefix in the `nick!user@address` form which could not be parsed as a `UserString`.
+    Other(String),
+}
+
+impl Prefix {
+    /// Parses a prefix, without the leading `:`. A prefix without a `!` or `@` is a server name if
+    /// it contains a `.`, and a nick otherwise.
+    pub fn new(prefix: impl Into<String>) -> Self {
+        let prefix = prefix.into();
+        if prefix.contains('!') || prefix.contains('@') {
+            match UserString::new(prefix.as_str()) {
+                Some(user) => Prefix::User(user),
+                None => Prefix::Other(prefix),
+            }
+        } else if prefix.contains('.') {
+            Prefix::Server(prefix)
+        } else {
+            Prefix::Nick(IrcIdent(prefix))
+        }
+    }
+    /// Gets the nick of the user the message was sent by, or the name of the server.
+    pub fn get_name(&self) -> &str {
+        match self {
+            Prefix::User(user) => user.get_nick().0,
+            Prefix::Nick(nick) => nick,
+            Prefix::Server(server) => server,
+            Prefix::Other(other) => other.split('!').next().unwrap(),
+        }
+    }
+}
+
+impl Display for Prefix {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Prefix::User(user) => Display::fmt(user, f),
+            Prefix::Nick(nick) => f.write_str(nick),
+            Prefix::Server(prefix) | Prefix::Other(prefix) => f.write_str(prefix),
+        }
+    }
+}
+
+impl Message {
+    /// Creates a new message with no tags or prefix.
+    ///
+    /// Returns an error if the command is not a word of letters or a three-digit numeric, if there
+    /// are more than 15 parameters, if any parameter contains a CR, LF or NUL, or if any parameter
+    /// but the last is empty, contains a space, or starts with `:`, since the message couldn't be
+    /// sent as it is.
+    pub fn new(
+        command: impl Into<String>,
+        params: Vec<String>,
+    ) -> Result<Self, InvalidMessageError> {
+        let command = command.into();
+        let is_numeric = command.len() == 3 && command.bytes().all(|b| b.is_ascii_digit());
+        if !is_numeric && (command.is_empty() || !command.bytes().all(|b| b.is_ascii_alphabetic()))
+        {
+            return Err(InvalidMessageError::InvalidCommand(command));
+        }
+        if params.len() > 15 {
+            return Err(InvalidMessageError::TooManyParams);
+        }
+        if let Some(param) = params
+            .iter()
+            .find(|p| p.contains(|c| c == '\r' || c == '\n' || c == '\0'))
+        {
+            return Err(InvalidMessageError::InvalidParam(param.clone()));
+        }
+        if let Some((_, middle)) = params.split_last() {
+            if let Some(param) = middle
+                .iter()
+                .find(|p| p.is_empty() || p.contains(' ') || p.starts_with(':'))
+            {
+                return Err(InvalidMessageError::InvalidParam(param.clone()));
+            }
+        }
+        Ok(Self {
+            tags: Vec::new(),
+            prefix: None,
+            command,
+            params,
+        })
+    }
+    /// Sets the prefix of this message.
+    pub fn with_prefix(mut self, prefix: Prefix) -> Self {
+        self.prefix = Some(prefix);
+        self
+    }
+    /// Adds a tag to this message, replacing any existing tag with the same key. An empty value is
+    /// the same as no value.
+    pub fn with_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        let value = value.into();
+        match self.tags.iter_mut().find(|(k, _)| *k == key) {
+            Some(tag) => tag.1 = value,
+            None => self.tags.push((key, value)),
+        }
+        self
+    }
+    /// Gets the message's tags, in the order they appeared, as key-value pairs. Values are
+    /// unescaped, and tags without a value have an empty one.
+    pub fn get_tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+    /// Gets the value of a tag, or `None` if the message doesn't have it.
+    pub fn get_tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+    /// Gets the prefix of the message, or `None` if it didn't have one.
+    pub fn get_prefix(&self) -> Option<&Prefix> {
+        self.prefix.as_ref()
+    }
+    /// Gets the command, e.g. `PRIVMSG` or `001`.
+    pub fn get_command(&self) -> &str {
+        &self.command
+    }
+    /// Gets the parameters, including the trailing one, without its leading `:`.
+    pub fn get_params(&self) -> &[String] {
+        &self.params
+    }
+    fn fmt_untagged(&self, f: &mut dyn Write, always_colon: bool) -> FmtResult {
+        if let Some(prefix) = &self.prefix {
+            write!(f, ":{} ", prefix)?;
+        }
+        f.write_str(&self.command)?;
+        if let Some((last, middle)) = self.params.split_last() {
+            for param in middle {
+                write!(f, " {}", param)?;
+            }
+            if always_colon || last.is_empty() || last.contains(' ') || last.starts_with(':') {
+                write!(f, " :{}", last)?;
+            } else {
+                write!(f, " {}", last)?;
+            }
+        }
+        Ok(())
+    }
+    /// Gets the message as HexChat would see it: without tags, split into its `word` and
+    /// `word_eol` arrays. The last parameter always gets a `:`, as servers send it.
+    pub(crate) fn to_words(&self) -> (WordArray, WordArray) {
+        let mut line = String::new();
+        self.fmt_untagged(&mut line, true).unwrap();
+        let (word, word_eol) = split_words(&line);
+        (WordArray::new(&word), WordArray::new(&word_eol))
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if !self.tags.is_empty() {
+            f.write_str("@")?;
+            for (i, (key, value)) in self.tags.iter().enumerate() {
+                if i != 0 {
+                    f.write_str(";")?;
+                }
+                f.write_str(key)?;
+                if !value.is_empty() {
+                    write!(f, "={}", escape_tag_value(value))?;
+                }
+            }
+            f.write_str(" ")?;
+        }
+        self.fmt_untagged(f, false)
+    }
+}
+
+impl FromStr for Message {
+    type Err = MessageParseError;
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut rest = line.trim_end_matches(|c| c == '\r' || c == '\n');
+        let mut tags = Vec::new();
+        if rest.starts_with('@') {
+            let (tag_string, after) = split_token(&rest[1..]);
+            for tag in tag_string.split(';').filter(|t| !t.is_empty()) {
+                let (key, value) = match tag.find('=') {
+                    Some(i) => (&tag[..i], unescape_tag_value(&tag[(i + 1)..])),
+                    None => (tag, String::new()),
+                };
+                if key.is_empty() {
+                    return Err(MessageParseError::InvalidTag(tag.to_string()));
+                }
+                match tags
+                    .iter_mut()
+                    .find(|(k, _): &&mut (String, String)| k == key)
+                {
+                    Some(existing) => existing.1 = value,
+                    None => tags.push((key.to_string(), value)),
+                }
+            }
+            rest = after;
+        }
+        let mut prefix = None;
+        if rest.starts_with(':') {
+            let (prefix_string, after) = split_token(&rest[1..]);
+            if prefix_string.is_empty() {
+                return Err(MessageParseError::EmptyPrefix);
+            }
+            prefix = Some(Prefix::new(prefix_string));
+            rest = after;
+        }
+        let (command, mut rest) = split_token(rest);
+        if command.is_empty() {
+            return Err(MessageParseError::MissingCommand);
+        }
+        let mut params = Vec::new();
+        while !rest.is_empty() {
+            // RFC 2812 allows at most 15 parameters, the last of which may contain spaces even
+            // without a leading `:`.
+            if rest.starts_with(':') || params.len() == 14 {
+                let trailing = if rest.starts_with(':') {
+                    &rest[1..]
+                } else {
+                    rest
+                };
+                params.push(trailing.to_string());
+                break;
+            }
+            let (param, after) = split_token(rest);
+            params.push(param.to_string());
+            rest = after;
+        }
+        Ok(Self {
+            tags,
+            prefix,
+            command: command.to_string(),
+            params,
+        })
+    }
+}
+
+/// An error produced while parsing a `Message`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MessageParseError {
+    /// The message had no command.
+    MissingCommand,
+    /// The message had a `:` where the prefix would be, but no prefix after it.
+    EmptyPrefix,
+    /// A tag had an empty key.
+    InvalidTag(String),
+}
+
+impl Display for MessageParseError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            MessageParseError::MissingCommand => f.write_str("Message has no command"),
+            MessageParseError::EmptyPrefix => f.write_str("Message has an empty prefix"),
+            MessageParseError::InvalidTag(tag) => write!(f, "Invalid tag '{}'", tag),
+        }
+    }
+}
+
+impl Error for MessageParseError {}
+
+/// An error produced while creating a `Message` with `Message::new`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InvalidMessageError {
+    /// The command was neither a word of letters nor a three-digit numeric.
+    InvalidCommand(String),
+    /// The message had more than the 15 parameters RFC 2812 allows.
+    TooManyParams,
+    /// A parameter contained a CR, LF or NUL, or a parameter other than the last was empty,
+    /// contained a space, or started with `:`.
+    InvalidParam(String),
+}
+
+impl Display for InvalidMessageError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            InvalidMessageError::InvalidCommand(command) => {
+                write!(f, "Invalid command '{}'", command)
+            }
+            InvalidMessageError::TooManyParams => f.write_str("Message has too many parameters"),
+            InvalidMessageError::InvalidParam(param) => {
+                write!(f, "Invalid parameter '{}'", param)
+            }
+        }
+    }
+}
+
+impl Error for InvalidMessageError {}
+
+/// Splits off the first space-delimited token, skipping any spaces after it.
+pub(crate) fn split_token(string: &str) -> (&str, &str) {
+    match string.find(' ') {
+        Some(i) => (&string[..i], string[i..].trim_start_matches(' ')),
+        None => (string, ""),
+    }
+}
+
+/// Escapes a tag value as described by the IRCv3 message-tags spec.
+pub fn escape_tag_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            ';' => escaped.push_str("\\:"),
+            ' ' => escaped.push_str("\\s"),
+            '\\' => escaped.push_str("\\\\"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Unescapes a tag value as described by the IRCv3 message-tags spec. Unknown escapes are replaced
+/// with the escaped character, and a trailing lone `\` is dropped.
+pub fn unescape_tag_value(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            unescaped.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => unescaped.push(';'),
+            Some('s') => unescaped.push(' '),
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// Splits a line into words and words-to-end-of-line the way HexChat does, for at most 31 words.
+pub(crate) fn split_words(line: &str) -> (Vec<String>, Vec<String>) {
+    let mut words = Vec::new();
+    let mut word_eol = Vec::new();
+    let mut start = None;
+    for (i, ch) in line.char_indices().chain(Some((line.len(), ' '))) {
+        if ch == ' ' {
+            if let Some(start) = start.take() {
+                if words.len() < 31 {
+                    words.push(line[start..i].to_string());
+                    word_eol.push(line[start..].to_string());
+                }
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    (words, word_eol)
+}
+
+/// A HexChat-style word array: 32 C strings, of which the first is reserved and the unused ones
+/// are empty.
+pub(crate) struct WordArray {
+    _strings: Vec<CString>,
+    ptrs: Vec<*mut c_char>,
+}
+
+impl WordArray {
+    pub(crate) fn new(words: &[String]) -> Self {
+        let mut strings = vec![CString::default()];
+        strings.extend(words.iter().take(31).map(|w| crate::to_cstring(w)));
+        while strings.len() < 32 {
+            strings.push(CString::default());
+        }
+        let ptrs = strings.iter().map(|s| s.as_ptr() as *mut _).collect();
+        Self {
+            _strings: strings,
+            ptrs,
+        }
+    }
+    pub(crate) fn ptr(&mut self) -> *mut *mut c_char {
+        self.ptrs.as_mut_ptr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reply::{ServerReply, RPL_AWAY};
+    use crate::server_event::{PrivmsgTarget, ServerEvent, PRIVMSG};
+    use crate::IrcIdent;
+
+    fn round_trip(line: &str) {
+        let message: Message = line.parse().unwrap();
+        assert_eq!(message.to_string(), line);
+        assert_eq!(message.to_string().parse::<Message>().unwrap(), message);
+    }
+
+    #[test]
+    fn parse() {
+        let message: Message =
+            "@time=2019-01-01T00:00:00.000Z;+draft/x :nick!user@host.example.com \
+                                PRIVMSG #channel :Hello there!\r\n"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            message.get_tags(),
+            &[
+                ("time".to_string(), "2019-01-01T00:00:00.000Z".to_string()),
+                ("+draft/x".to_string(), String::new())
+            ]
+        );
+        assert_eq!(
+            message.get_prefix(),
+            Some(&Prefix::User(
+                UserString::new("nick!user@host.example.com").unwrap()
+            ))
+        );
+        assert_eq!(message.get_command(), "PRIVMSG");
+        assert_eq!(message.get_params(), &["#channel", "Hello there!"]);
+        let message: Message = ":irc.example.com  001   me  :Welcome".parse().unwrap();
+        assert_eq!(
+            message.get_prefix(),
+            Some(&Prefix::Server("irc.example.com".to_string()))
+        );
+        assert_eq!(message.get_params(), &["me", "Welcome"]);
+    }
+
+    #[test]
+    fn prefixes() {
+        let nick = Prefix::new("tester");
+        assert_eq!(nick, Prefix::Nick(IrcIdent("tester".to_string())));
+        assert_eq!(nick.get_name(), "tester");
+        assert_eq!(nick.to_string(), "tester");
+        assert_eq!(
+            Prefix::new("irc.example.com"),
+            Prefix::Server("irc.example.com".to_string())
+        );
+        assert_eq!(
+            Prefix::new("nick!user"),
+            Prefix::Other("nick!user".to_string())
+        );
+        assert_eq!(Prefix::new("nick!user").get_name(), "nick");
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            "".parse::<Message>(),
+            Err(MessageParseError::MissingCommand)
+        );
+        assert_eq!(
+            ": PING".parse::<Message>(),
+            Err(MessageParseError::EmptyPrefix)
+        );
+        assert_eq!(
+            "@=x PING".parse::<Message>(),
+            Err(MessageParseError::InvalidTag("=x".to_string()))
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        round_trip("PING");
+        round_trip("PING irc.example.com");
+        round_trip(":irc.example.com 001 me :Welcome to IRC");
+        round_trip(":nick!user@host.example.com PRIVMSG #channel :");
+        round_trip(":nick!user@host.example.com PRIVMSG #channel ::)");
+        round_trip(r"@a=b\sc\:d;e :server MODE #channel +o nick");
+    }
+
+    #[test]
+    fn fifteen_params() {
+        let message: Message = "CMD 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16"
+            .parse()
+            .unwrap();
+        assert_eq!(message.get_params().len(), 15);
+        assert_eq!(message.get_params()[14], "15 16");
+        let message: Message = "CMD 1 2 3 4 5 6 7 8 9 10 11 12 13 14 :15 16"
+            .parse()
+            .unwrap();
+        assert_eq!(message.get_params()[14], "15 16");
+        let params = (1..=16).map(|i| i.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            Message::new("CMD", params),
+            Err(InvalidMessageError::TooManyParams)
+        );
+    }
+
+    #[test]
+    fn new() {
+        let message = Message::new(
+            "PRIVMSG",
+            vec!["#channel".to_string(), "hi there".to_string()],
+        )
+        .unwrap()
+        .with_tag("label", "a b");
+        assert_eq!(
+            message.to_string(),
+            "@label=a\\sb PRIVMSG #channel :hi there"
+        );
+        for param in &["", "a b", ":a", "a\r\nQUIT", "a\0"] {
+            assert_eq!(
+                Message::new("PRIVMSG", vec![param.to_string(), "hi".to_string()]),
+                Err(InvalidMessageError::InvalidParam(param.to_string()))
+            );
+        }
+        for param in &["hi\r\nQUIT", "hi\n", "hi\0"] {
+            assert_eq!(
+                Message::new("PRIVMSG", vec!["#channel".to_string(), param.to_string()]),
+                Err(InvalidMessageError::InvalidParam(param.to_string()))
+            );
+        }
+        for command in &["", "PRIV MSG", "PRIVMSG\r\n", "01", "1234", "P1"] {
+            assert_eq!(
+                Message::new(*command, Vec::new()),
+                Err(InvalidMessageError::InvalidCommand(command.to_string()))
+            );
+        }
+        assert!(Message::new("001", vec!["me".to_string()]).is_ok());
+        let message = Message::new("PRIVMSG", vec!["#channel".to_string(), String::new()]).unwrap();
+        assert_eq!(message.to_string(), "PRIVMSG #channel :");
+    }
+
+    #[test]
+    fn tag_values() {
+        assert_eq!(escape_tag_value(""), "");
+        assert_eq!(escape_tag_value("a; b\\\r\n"), "a\\:\\sb\\\\\\r\\n");
+        assert_eq!(unescape_tag_value(""), "");
+        assert_eq!(unescape_tag_value("a\\:\\sb\\\\\\r\\n"), "a; b\\\r\n");
+        assert_eq!(unescape_tag_value("a\\b"), "ab");
+        assert_eq!(unescape_tag_value("ab\\"), "ab");
+        for value in &[
+            "",
+            "plain",
+            "semi;colon",
+            "back\\slash",
+            "a\\",
+            "new\r\nline",
+        ] {
+            assert_eq!(unescape_tag_value(&escape_tag_value(value)), *value);
+        }
+    }
+
+    #[test]
+    fn server_event_from_message() {
+        let message: Message = ":nick!user@host.example.com PRIVMSG me hi".parse().unwrap();
+        let privmsg = PRIVMSG::from_message(&message).unwrap();
+        assert_eq!(privmsg.get_user().get_nick().0, "nick");
+        assert_eq!(
+            privmsg.get_target(),
+            &PrivmsgTarget::User(IrcIdent("me".to_string()))
+        );
+        assert_eq!(privmsg.get_message(), "hi");
+        let message: Message = ":nick!user@host.example.com NOTICE me :hi".parse().unwrap();
+        assert!(PRIVMSG::from_message(&message).is_none());
+    }
+
+    #[test]
+    fn server_reply_from_message() {
+        let message: Message = ":irc.example.com 301 me bob :Gone fishing".parse().unwrap();
+        let away = RPL_AWAY::from_message(&message).unwrap();
+        assert_eq!(away.nick().0, "bob");
+        assert_eq!(away.message(), "Gone fishing");
+        let message: Message = ":irc.example.com 301 me bob fishing".parse().unwrap();
+        assert_eq!(
+            RPL_AWAY::from_message(&message).unwrap().message(),
+            "fishing"
+        );
+        let message: Message = ":irc.example.com 305 me :Back".parse().unwrap();
+        assert!(RPL_AWAY::from_message(&message).is_none());
+    }
+}
@@ -4,17 +4,29 @@
 //! call `plugin!` on the struct.
 //!
 //! All plugins should be built as cdylibs, or if for some reason you have no other choice, dylibs.
-//! Do not attempt to define a `main()` symbol; `Plugin::new` is your plugin's 'entry point'. For that
-//! matter, do not attempt to define the HexChat C docs' described `extern fn`s - this is taken care
-//! of for you by the `plugin!` macro.
+//! Do not attempt to define a `main()` symbol; `Plugin::new` or `Plugin::init` is your plugin's
+//! 'entry point'. For that matter, do not attempt to define the HexChat C docs' described
+//! `extern fn`s - this is taken care of for you by the `plugin!` macro.
 //!
 //! If window manipulation is desired, the `window` feature should be enabled.
 //!
+//! To unit-test a plugin without running HexChat, enable the `testing` feature and use the mock
+//! host in the `testing` module. To find out what your plugin leaves behind when it is unloaded,
+//! enable the `diagnostics` feature; see the `diagnostics` module. To find out which of your hooks
+//! are slow, enable the `profiling` feature; see the `profiling` module.
+//!
 //! Static variables holding heap resources are discouraged and will cause memory leaks. This crate
 //! provides a `safe_static!` macro for this purpose. Please note that any thread that you create
 //! that accesses a safe static must be killed in your plugin's `Drop` implementation, and it's
 //! undefined not to. You should kill them anyway even if you don't use this, because they'll be a
-//! memory leak too otherwise.
+//! memory leak too otherwise. Threads spawned with `thread::spawn` are stopped for you when the
+//! plugin is unloaded.
+//!
+//! Any callback that returns an `EatMode` or `()` may instead return a `Result` of it, whose error
+//! is reported along with any panics to the sink set with `set_error_sink`.
+//!
+//! None of this crate's functions may be called from any thread but HexChat's main thread. If you
+//! need to call them from another thread, use a `MainThreadHandle`.
 
 #![deny(missing_docs, clippy::pedantic)]
 #![allow(
@@ -32,7 +44,9 @@
     proc_macro_hygiene,
     try_from
 )]
+#![cfg_attr(feature = "testing", feature(c_variadic))]
 
+use std::error::Error;
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 
@@ -43,20 +57,43 @@ mod get_info;
 pub use crate::get_info::*;
 mod lists;
 pub use crate::lists::*;
+mod context;
+pub use crate::context::*;
 mod hook;
 pub use crate::hook::*;
+mod hook_mut;
+pub use crate::hook_mut::*;
 mod other;
 pub use crate::other::*;
 mod msg;
 pub use crate::msg::*;
+mod ctcp;
+pub use crate::ctcp::*;
+mod server_features;
+pub use crate::server_features::*;
 mod prefs;
 pub use crate::prefs::*;
+mod reload;
+pub use crate::reload::*;
 mod chan;
 pub use crate::chan::*;
 mod subplugin;
 pub use crate::subplugin::*;
 mod mask;
 pub use crate::mask::*;
+mod args;
+pub use crate::args::*;
+mod executor;
+pub use crate::executor::*;
+mod whox;
+pub use crate::whox::*;
+mod whois;
+pub use crate::whois::*;
+mod main_thread;
+pub use crate::main_thread::*;
+mod error;
+pub use crate::error::*;
+mod notify;
 #[macro_use]
 mod safe_static;
 pub use crate::safe_static::*;
@@ -67,10 +104,39 @@ pub mod server_event;
 /// Server responses for use with `add_server_response_listener`.
 pub mod reply;
 
+/// Print event arguments for use with `add_typed_print_listener`.
+pub mod print_event;
+
+/// Parsing and serialization of raw IRC messages.
+pub mod irc;
+
+/// Threads that are stopped when the plugin is unloaded.
+pub mod thread;
+
 #[macro_use]
 #[doc(hidden)]
 pub mod call;
 
+/// A mock HexChat host for testing plugins.
+#[cfg(feature = "testing")]
+pub mod testing;
+
+/// Diagnostics for finding resources your plugin leaves behind when it is unloaded.
+///
+/// When the plugin is unloaded, a report is written listing any hooks that were still registered
+/// and had to be removed for it, any fake plugins that were never removed, any safe statics that
+/// were first used after the plugin was dropped, and any threads that failed to stop.
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+
+/// Profiling of how long your plugin's hooks take to run.
+///
+/// Every call to a command, listener, timer task, or fd watcher is timed, and the results can be
+/// queried with `get_profiles`, or printed with the automatically registered `/<plugin>_profile`
+/// command, where `<plugin>` is `Plugin::NAME` with any spaces replaced by underscores.
+#[cfg(feature = "profiling")]
+pub mod profiling;
+
 fn to_cstring(str: &str) -> CString {
     CString::new(str).unwrap_or_else(|_| CString::new(str.replace('\0', "")).unwrap())
 }
@@ -96,13 +162,56 @@ pub fn send_command(command: &str) {
 }
 
 /// This trait must be implemented on a type before the type is passed to `plugin!`.
-pub trait Plugin {
+pub trait Plugin
+where
+    Self: Sized,
+{
     /// The name of your plugin.
     const NAME: &'static str;
     /// A short description of your plugin.
     const DESC: &'static str = "";
     /// The version string of your plugin.
     const VERSION: &'static str = "";
-    /// Creates a new instance of your plugin. This is your 'entry point'.
+    /// The version of the state saved by `save_state`. A saved state is discarded instead of being
+    /// handed back if this has changed in the meantime, so change it whenever the format does.
+    const STATE_VERSION: u32 = 0;
+    /// Creates a new instance of your plugin. This is your 'entry point', unless you implement
+    /// `init`.
     fn new() -> Self;
+    /// Creates a new instance of your plugin, given information about how it was loaded. This is
+    /// called instead of `new`, and by default just calls it. If you implement this, `new` is only
+    /// called if you call it yourself.
+    ///
+    /// If this returns an error or panics, the plugin fails to load, anything it registered is
+    /// removed again, and the error is reported to the sink set with `set_error_sink`, which by
+    /// default shows it in a message box.
+    fn init(context: &PluginContext) -> Result<Self, Box<dyn Error>> {
+        let _ = context;
+        Ok(Self::new())
+    }
+    /// Called once HexChat has finished loading your plugin.
+    ///
+    /// If this unloads your plugin, everything it registered is removed and its safe statics are
+    /// freed straight away, but `save_state`, `on_unloading` and `on_unloaded` are not called. The
+    /// API can't be used for the rest of this function or in your plugin's `Drop` implementation,
+    /// which runs once this returns.
+    fn on_loaded(&mut self) {}
+    /// Called when your plugin is about to be unloaded, to save any state that should survive a
+    /// `/RELOAD`. The state is written to a file in HexChat's config directory, and is handed back
+    /// through `PluginContext::get_reload_state` the next time your plugin is loaded.
+    ///
+    /// Note that this is also called when HexChat exits, so the state may be handed back on the
+    /// next startup as well.
+    fn save_state(&mut self) -> Option<ReloadState> {
+        None
+    }
+    /// Called when your plugin is about to be unloaded, after `save_state` and before any of its
+    /// hooks are removed.
+    fn on_unloading(&mut self) {}
+    /// Called when your plugin is being unloaded, after its hooks have been removed. The instance
+    /// is dropped after this returns.
+    ///
+    /// The API remains usable here and in your plugin's `Drop` implementation, but anything newly
+    /// hooked is immediately removed again.
+    fn on_unloaded(self) {}
 }
@@ -102,7 +102,7 @@ where
     type Item = T;
     fn next(&mut self) -> Option<Self::Item> {
         let res = unsafe { c!(hexchat_list_next, self.handle) };
-        if res == 0 {
+        if res != 0 {
             Some(self.get_current())
         } else {
             None
@@ -215,7 +215,7 @@ impl FromXList for ChannelInfo {
             nick_modes: list.get_item_string("nickmodes").unwrap_or_default(),
             send_queue_size: list.get_item_int("queue") as _,
             server_name: list.get_item_string("server").unwrap_or_default(),
-            channel_type: ChannelType::VALUES[list.get_item_int("type") as usize + 1],
+            channel_type: ChannelType::VALUES[list.get_item_int("type") as usize - 1],
             user_count: list.get_item_int("users") as _,
         }
     }
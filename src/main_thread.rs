@@ -0,0 +1,108 @@
+use crate::error;
+use crate::notify::{self, Notifier};
+use crate::{remove_fd_watcher, CallbackKind, FdWatcher};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+
+static DISPATCHER: Mutex<Option<Arc<Dispatcher>>> = Mutex::new(None);
+static WATCHER: Mutex<Option<FdWatcher>> = Mutex::new(None);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Dispatcher {
+    // `None` once the plugin has been unloaded.
+    queue: Mutex<Option<VecDeque<Job>>>,
+    notifier: Notifier,
+}
+
+/// A handle that can be used from any thread to run code on HexChat's main thread, where the rest
+/// of this crate's functions may be safely called. Obtained from `get_main_thread_handle`.
+#[derive(Clone)]
+pub struct MainThreadHandle {
+    dispatcher: Arc<Dispatcher>,
+}
+
+impl MainThreadHandle {
+    /// Queues a function to be run on the main thread.
+    ///
+    /// Returns a channel which will receive the function's result once it has been run. If the
+    /// plugin is unloaded before the function gets to run, or the function panics, the function is
+    /// dropped and the channel is disconnected instead.
+    pub fn run<T>(&self, function: impl FnOnce() -> T + Send + 'static) -> Receiver<T>
+    where
+        T: Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let job = Box::new(move || {
+            tx.send(function()).ok();
+        });
+        if let Some(queue) = &mut *self.dispatcher.queue.lock() {
+            queue.push_back(job);
+        } else {
+            return rx;
+        }
+        self.dispatcher.notifier.notify();
+        rx
+    }
+}
+
+/// Gets a `MainThreadHandle` for this plugin. This function must itself be called from the main
+/// thread, e.g. before spawning the thread the handle will be sent to.
+///
+/// # Panics
+///
+/// Panics if the dispatcher's wakeup socket could not be created.
+pub fn get_main_thread_handle() -> MainThreadHandle {
+    let mut lock = DISPATCHER.lock();
+    if let Some(dispatcher) = &*lock {
+        return MainThreadHandle {
+            dispatcher: dispatcher.clone(),
+        };
+    }
+    let (notifier, watcher) =
+        notify::notifier(run_queued).expect("Could not create dispatcher socket");
+    *WATCHER.lock() = Some(watcher);
+    let dispatcher = Arc::new(Dispatcher {
+        queue: Mutex::new(Some(VecDeque::new())),
+        notifier,
+    });
+    *lock = Some(dispatcher.clone());
+    MainThreadHandle { dispatcher }
+}
+
+fn run_queued() {
+    let dispatcher = match &*DISPATCHER.lock() {
+        Some(dispatcher) => dispatcher.clone(),
+        None => return,
+    };
+    let jobs = match &mut *dispatcher.queue.lock() {
+        Some(queue) => mem::replace(queue, VecDeque::new()),
+        None => return,
+    };
+    for job in jobs {
+        if let Err(e) = panic::catch_unwind(AssertUnwindSafe(job)) {
+            error::report(
+                CallbackKind::MainThreadJob,
+                "",
+                error::panic_message(&*e),
+                true,
+            );
+        }
+    }
+}
+
+pub(crate) fn shutdown() {
+    let dispatcher = DISPATCHER.lock().take();
+    if let Some(dispatcher) = dispatcher {
+        let queue = dispatcher.queue.lock().take();
+        mem::drop(queue);
+    }
+    let watcher = WATCHER.lock().take();
+    if let Some(watcher) = watcher {
+        remove_fd_watcher(watcher);
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{add_fd_watcher, FdFlags, FdWatcher};
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::Arc;
+
+/// The sending half of a self-pipe. Can be used from any thread to wake up the main thread.
+#[derive(Clone)]
+pub(crate) struct Notifier(Arc<TcpStream>);
+
+impl Notifier {
+    pub(crate) fn notify(&self) {
+        (&*self.0).write(&[0]).ok();
+    }
+}
+
+/// Creates a self-pipe out of a loopback socket pair and registers `callback` to be run on the main
+/// thread whenever `Notifier::notify` is called. Sockets are used instead of a pipe because
+/// HexChat on Windows can only watch sockets.
+pub(crate) fn notifier(callback: impl Fn() + 'static) -> io::Result<(Notifier, FdWatcher)> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let sender = TcpStream::connect(listener.local_addr()?)?;
+    let receiver = loop {
+        let (receiver, addr) = listener.accept()?;
+        if addr == sender.local_addr()? {
+            break receiver;
+        }
+    };
+    sender.set_nonblocking(true)?;
+    sender.set_nodelay(true)?;
+    receiver.set_nonblocking(true)?;
+    let fd = raw_fd(&receiver);
+    let watcher = add_fd_watcher(fd, FdFlags::READ, move |_, _| {
+        let mut buf = [0; 64];
+        while let Ok(len) = (&receiver).read(&mut buf) {
+            if len == 0 {
+                break;
+            }
+        }
+        callback();
+    });
+    Ok((Notifier(Arc::new(sender)), watcher))
+}
+
+#[cfg(unix)]
+fn raw_fd(stream: &TcpStream) -> i32 {
+    use std::os::unix::io::AsRawFd;
+    stream.as_raw_fd()
+}
+
+#[cfg(windows)]
+fn raw_fd(stream: &TcpStream) -> i32 {
+    use std::os::windows::io::AsRawSocket;
+    stream.as_raw_socket() as _
+}
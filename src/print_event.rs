@@ -0,0 +1,1184 @@
+use crate::{ChannelRef, PrintEvent};
+use chrono::{DateTime, TimeZone};
+
+/// A type representing the arguments of a print event. Used with `add_typed_print_listener`. It is
+/// not recommended you implement this on your own types.
+pub trait TypedPrintEvent
+where
+    Self: Sized,
+{
+    /// The print event these are the arguments of.
+    const EVENT: PrintEvent;
+    /// Creates the event from its arguments, where `args[0]` corresponds to `$1` and so forth.
+    /// Missing arguments are left empty.
+    fn from_args(args: &[String]) -> Self;
+    /// Converts the event back into its arguments, in the order `from_args` takes them.
+    fn to_args(&self) -> Vec<String>;
+    /// Prints this event to the current tab.
+    ///
+    /// Returns whether or not it succeeded.
+    fn emit(&self) -> bool {
+        crate::print_event(Self::EVENT, &self.to_args())
+    }
+    /// Prints this event to the current tab with a specified timestamp.
+    ///
+    /// Returns whether or not it succeeded.
+    fn emit_at(&self, timestamp: &DateTime<impl TimeZone>) -> bool {
+        crate::print_event_at(Self::EVENT, timestamp, &self.to_args())
+    }
+    /// Prints this event to a particular `ChannelRef`.
+    ///
+    /// Returns whether or not it succeeded.
+    fn emit_to_channel(&self, channel: &ChannelRef) -> bool {
+        crate::print_event_to_channel(channel, Self::EVENT, &self.to_args())
+    }
+}
+
+macro_rules! print_events {
+    ($(
+        $(#[doc = $doc:expr])*
+        $N:ident = $E:ident {
+            $(
+                $(#[doc = $field_doc:expr])*
+                $field:ident,
+            )*
+        }
+    )*) => {
+        $(
+            $(#[doc = $doc])*
+            #[derive(Debug, Clone, Eq, PartialEq, Default)]
+            pub struct $N {
+                $(
+                    $(#[doc = $field_doc])*
+                    pub $field: String,
+                )*
+            }
+
+            impl TypedPrintEvent for $N {
+                const EVENT: PrintEvent = PrintEvent::$E;
+                fn from_args(args: &[String]) -> Self {
+                    let mut _args = args.iter();
+                    Self {
+                        $($field: _args.next().cloned().unwrap_or_default(),)*
+                    }
+                }
+                fn to_args(&self) -> Vec<String> {
+                    vec![$(self.$field.clone()),*]
+                }
+            }
+        )*
+    };
+}
+
+print_events! {
+    /// The arguments of the `Add Notify` print event.
+    AddNotify = ADD_NOTIFY {
+        /// The nick that was added.
+        nick,
+    }
+    /// The arguments of the `Ban List` print event.
+    BanList = BAN_LIST {
+        /// The channel.
+        channel,
+        /// The banned mask.
+        mask,
+        /// Who set the ban.
+        setter,
+        /// When the ban was set.
+        time,
+    }
+    /// The arguments of the `Banned` print event.
+    Banned = BANNED {
+        /// The channel the client is banned from.
+        channel,
+    }
+    /// The arguments of the `Beep` print event.
+    Beep = BEEP {}
+    /// The arguments of the `Capability Acknowledgement` print event.
+    CapabilityAcknowledgement = CAPABILITY_ACKNOWLEDGEMENT {
+        /// The server name.
+        server,
+        /// The acknowledged capabilities.
+        capabilities,
+    }
+    /// The arguments of the `Capability Deleted` print event.
+    CapabilityDeleted = CAPABILITY_DELETED {
+        /// The server name.
+        server,
+        /// The deleted capabilities.
+        capabilities,
+    }
+    /// The arguments of the `Capability List` print event.
+    CapabilityList = CAPABILITY_LIST {
+        /// The server name.
+        server,
+        /// The available capabilities.
+        capabilities,
+    }
+    /// The arguments of the `Capability Request` print event.
+    CapabilityRequest = CAPABILITY_REQUEST {
+        /// The requested capabilities.
+        capabilities,
+    }
+    /// The arguments of the `Change Nick` print event.
+    ChangeNick = CHANGE_NICK {
+        /// The old nick.
+        old_nick,
+        /// The new nick.
+        new_nick,
+    }
+    /// The arguments of the `Channel Action` print event.
+    ChannelAction = CHANNEL_ACTION {
+        /// The nick of the sender.
+        nick,
+        /// The action text.
+        text,
+        /// The sender's mode character, if any.
+        mode,
+        /// The text shown if the sender is identified.
+        identified,
+    }
+    /// The arguments of the `Channel Action Hilight` print event.
+    ChannelActionHilight = CHANNEL_ACTION_HILIGHT {
+        /// The nick of the sender.
+        nick,
+        /// The action text.
+        text,
+        /// The sender's mode character, if any.
+        mode,
+        /// The text shown if the sender is identified.
+        identified,
+    }
+    /// The arguments of the `Channel Ban` print event.
+    ChannelBan = CHANNEL_BAN {
+        /// The nick of the user who set the ban.
+        nick,
+        /// The banned mask.
+        mask,
+    }
+    /// The arguments of the `Channel Creation` print event.
+    ChannelCreation = CHANNEL_CREATION {
+        /// The channel.
+        channel,
+        /// When the channel was created.
+        time,
+    }
+    /// The arguments of the `Channel DeHalfOp` print event.
+    ChannelDehalfop = CHANNEL_DEHALFOP {
+        /// The nick of the user who removed the mode.
+        nick,
+        /// The nick of the user who lost the mode.
+        target,
+    }
+    /// The arguments of the `Channel DeOp` print event.
+    ChannelDeop = CHANNEL_DEOP {
+        /// The nick of the user who removed the mode.
+        nick,
+        /// The nick of the user who lost the mode.
+        target,
+    }
+    /// The arguments of the `Channel DeVoice` print event.
+    ChannelDevoice = CHANNEL_DEVOICE {
+        /// The nick of the user who removed the mode.
+        nick,
+        /// The nick of the user who lost the mode.
+        target,
+    }
+    /// The arguments of the `Channel Exempt` print event.
+    ChannelExempt = CHANNEL_EXEMPT {
+        /// The nick of the user who set the exemption.
+        nick,
+        /// The exempted mask.
+        mask,
+    }
+    /// The arguments of the `Channel Half-Operator` print event.
+    ChannelHalfop = CHANNEL_HALFOP {
+        /// The nick of the user who set the mode.
+        nick,
+        /// The nick of the user who gained the mode.
+        target,
+    }
+    /// The arguments of the `Channel INVITE` print event.
+    ChannelInvite = CHANNEL_INVITE {
+        /// The nick of the user who set the invite exemption.
+        nick,
+        /// The exempted mask.
+        mask,
+    }
+    /// The arguments of the `Channel List` print event.
+    ChannelList = CHANNEL_LIST {}
+    /// The arguments of the `Channel Message` print event.
+    ChannelMessage = CHANNEL_MESSAGE {
+        /// The nick of the sender.
+        nick,
+        /// The message text.
+        text,
+        /// The sender's mode character, if any.
+        mode,
+        /// The text shown if the sender is identified.
+        identified,
+    }
+    /// The arguments of the `Channel Mode Generic` print event.
+    ChannelModeGeneric = CHANNEL_MODE_GENERIC {
+        /// The nick of the user who set the mode.
+        nick,
+        /// The mode's sign, `+` or `-`.
+        sign,
+        /// The mode character.
+        mode,
+        /// The channel.
+        channel,
+    }
+    /// The arguments of the `Channel Modes` print event.
+    ChannelModes = CHANNEL_MODES {
+        /// The channel.
+        channel,
+        /// The channel's mode string.
+        modes,
+    }
+    /// The arguments of the `Channel Msg Hilight` print event.
+    ChannelMsgHilight = CHANNEL_MSG_HILIGHT {
+        /// The nick of the sender.
+        nick,
+        /// The message text.
+        text,
+        /// The sender's mode character, if any.
+        mode,
+        /// The text shown if the sender is identified.
+        identified,
+    }
+    /// The arguments of the `Channel Notice` print event.
+    ChannelNotice = CHANNEL_NOTICE {
+        /// The nick of the sender.
+        nick,
+        /// The channel.
+        channel,
+        /// The notice text.
+        text,
+    }
+    /// The arguments of the `Channel Operator` print event.
+    ChannelOperator = CHANNEL_OPERATOR {
+        /// The nick of the user who set the mode.
+        nick,
+        /// The nick of the user who gained the mode.
+        target,
+    }
+    /// The arguments of the `Channel Quiet` print event.
+    ChannelQuiet = CHANNEL_QUIET {
+        /// The nick of the user who set the quiet.
+        nick,
+        /// The quieted mask.
+        mask,
+    }
+    /// The arguments of the `Channel Remove Exempt` print event.
+    ChannelRemoveExempt = CHANNEL_REMOVE_EXEMPT {
+        /// The nick of the user who removed the exemption.
+        nick,
+        /// The mask that is no longer exempt.
+        mask,
+    }
+    /// The arguments of the `Channel Remove Invite` print event.
+    ChannelRemoveInvite = CHANNEL_REMOVE_INVITE {
+        /// The nick of the user who removed the invite exemption.
+        nick,
+        /// The mask that is no longer exempt.
+        mask,
+    }
+    /// The arguments of the `Channel Remove Keyword` print event.
+    ChannelRemoveKeyword = CHANNEL_REMOVE_KEYWORD {
+        /// The nick of the user who removed the key.
+        nick,
+    }
+    /// The arguments of the `Channel Remove Limit` print event.
+    ChannelRemoveLimit = CHANNEL_REMOVE_LIMIT {
+        /// The nick of the user who removed the limit.
+        nick,
+    }
+    /// The arguments of the `Channel Set Key` print event.
+    ChannelSetKey = CHANNEL_SET_KEY {
+        /// The nick of the user who set the key.
+        nick,
+        /// The new key.
+        key,
+    }
+    /// The arguments of the `Channel Set Limit` print event.
+    ChannelSetLimit = CHANNEL_SET_LIMIT {
+        /// The nick of the user who set the limit.
+        nick,
+        /// The new user limit.
+        limit,
+    }
+    /// The arguments of the `Channel UnBan` print event.
+    ChannelUnban = CHANNEL_UNBAN {
+        /// The nick of the user who removed the ban.
+        nick,
+        /// The unbanned mask.
+        mask,
+    }
+    /// The arguments of the `Channel UnQuiet` print event.
+    ChannelUnquiet = CHANNEL_UNQUIET {
+        /// The nick of the user who removed the quiet.
+        nick,
+        /// The unquieted mask.
+        mask,
+    }
+    /// The arguments of the `Channel Url` print event.
+    ChannelUrl = CHANNEL_URL {
+        /// The channel.
+        channel,
+        /// The channel's URL.
+        url,
+    }
+    /// The arguments of the `Channel Voice` print event.
+    ChannelVoice = CHANNEL_VOICE {
+        /// The nick of the user who set the mode.
+        nick,
+        /// The nick of the user who gained the mode.
+        target,
+    }
+    /// The arguments of the `Connected` print event.
+    Connected = CONNECTED {}
+    /// The arguments of the `Connecting` print event.
+    Connecting = CONNECTING {
+        /// The host being connected to.
+        host,
+        /// The IP address being connected to.
+        ip,
+        /// The port being connected to.
+        port,
+    }
+    /// The arguments of the `Connection Failed` print event.
+    ConnectionFailed = CONNECTION_FAILED {
+        /// The error message.
+        error,
+    }
+    /// The arguments of the `CTCP Generic` print event.
+    CtcpGeneric = CTCP_GENERIC {
+        /// The CTCP message.
+        ctcp,
+        /// The nick of the sender.
+        nick,
+    }
+    /// The arguments of the `CTCP Generic to Channel` print event.
+    CtcpGenericToChannel = CTCP_GENERIC_TO_CHANNEL {
+        /// The CTCP message.
+        ctcp,
+        /// The nick of the sender.
+        nick,
+        /// The channel.
+        channel,
+    }
+    /// The arguments of the `CTCP Send` print event.
+    CtcpSend = CTCP_SEND {
+        /// Who the CTCP was sent to.
+        receiver,
+        /// The CTCP message.
+        ctcp,
+    }
+    /// The arguments of the `CTCP Sound` print event.
+    CtcpSound = CTCP_SOUND {
+        /// The sound file.
+        sound,
+        /// The nick of the sender.
+        nick,
+    }
+    /// The arguments of the `CTCP Sound to Channel` print event.
+    CtcpSoundToChannel = CTCP_SOUND_TO_CHANNEL {
+        /// The sound file.
+        sound,
+        /// The nick of the sender.
+        nick,
+        /// The channel.
+        channel,
+    }
+    /// The arguments of the `DCC CHAT Abort` print event.
+    DccChatAbort = DCC_CHAT_ABORT {
+        /// The nick of the other user.
+        nick,
+    }
+    /// The arguments of the `DCC CHAT Connect` print event.
+    DccChatConnect = DCC_CHAT_CONNECT {
+        /// The nick of the other user.
+        nick,
+        /// The IP address of the other user.
+        ip,
+    }
+    /// The arguments of the `DCC CHAT Failed` print event.
+    DccChatFailed = DCC_CHAT_FAILED {
+        /// The nick of the other user.
+        nick,
+        /// The IP address of the other user.
+        ip,
+        /// The port.
+        port,
+        /// The error message.
+        error,
+    }
+    /// The arguments of the `DCC CHAT Offer` print event.
+    DccChatOffer = DCC_CHAT_OFFER {
+        /// The nick of the user offering the chat.
+        nick,
+    }
+    /// The arguments of the `DCC CHAT Offering` print event.
+    DccChatOffering = DCC_CHAT_OFFERING {
+        /// The nick of the user being offered the chat.
+        nick,
+    }
+    /// The arguments of the `DCC CHAT Reoffer` print event.
+    DccChatReoffer = DCC_CHAT_REOFFER {
+        /// The nick of the user being offered the chat.
+        nick,
+    }
+    /// The arguments of the `DCC Conection Failed` print event.
+    DccConectionFailed = DCC_CONECTION_FAILED {
+        /// The type of DCC.
+        dcc_type,
+        /// The nick of the other user.
+        nick,
+        /// The error message.
+        error,
+    }
+    /// The arguments of the `DCC Generic Offer` print event.
+    DccGenericOffer = DCC_GENERIC_OFFER {
+        /// The offer text.
+        text,
+        /// The nick of the user making the offer.
+        nick,
+    }
+    /// The arguments of the `DCC Header` print event.
+    DccHeader = DCC_HEADER {}
+    /// The arguments of the `DCC Malformed` print event.
+    DccMalformed = DCC_MALFORMED {
+        /// The nick of the sender.
+        nick,
+        /// The malformed packet.
+        packet,
+    }
+    /// The arguments of the `DCC Offer` print event.
+    DccOffer = DCC_OFFER {
+        /// The name of the file.
+        filename,
+        /// The nick of the user being offered the file.
+        nick,
+        /// The IP address of the user.
+        ip,
+    }
+    /// The arguments of the `DCC Offer Not Valid` print event.
+    DccOfferNotValid = DCC_OFFER_NOT_VALID {}
+    /// The arguments of the `DCC RECV Abort` print event.
+    DccRecvAbort = DCC_RECV_ABORT {
+        /// The nick of the sender.
+        nick,
+        /// The name of the file.
+        filename,
+    }
+    /// The arguments of the `DCC RECV Complete` print event.
+    DccRecvComplete = DCC_RECV_COMPLETE {
+        /// The name of the file.
+        filename,
+        /// Where the file was saved.
+        destination,
+        /// The nick of the sender.
+        nick,
+        /// The average transfer speed, in bytes per second.
+        cps,
+    }
+    /// The arguments of the `DCC RECV Connect` print event.
+    DccRecvConnect = DCC_RECV_CONNECT {
+        /// The nick of the sender.
+        nick,
+        /// The IP address of the sender.
+        ip,
+        /// The name of the file.
+        filename,
+    }
+    /// The arguments of the `DCC RECV Failed` print event.
+    DccRecvFailed = DCC_RECV_FAILED {
+        /// The name of the file.
+        filename,
+        /// Where the file was being saved.
+        destination,
+        /// The nick of the sender.
+        nick,
+        /// The error message.
+        error,
+    }
+    /// The arguments of the `DCC RECV File Open Error` print event.
+    DccRecvFileOpenError = DCC_RECV_FILE_OPEN_ERROR {
+        /// The name of the file.
+        filename,
+        /// The error message.
+        error,
+    }
+    /// The arguments of the `DCC Rename` print event.
+    DccRename = DCC_RENAME {
+        /// The old name of the file.
+        old_filename,
+        /// The new name of the file.
+        new_filename,
+    }
+    /// The arguments of the `DCC RESUME Request` print event.
+    DccResumeRequest = DCC_RESUME_REQUEST {
+        /// The nick of the receiver.
+        nick,
+        /// The name of the file.
+        filename,
+        /// The position to resume from.
+        position,
+    }
+    /// The arguments of the `DCC SEND Abort` print event.
+    DccSendAbort = DCC_SEND_ABORT {
+        /// The nick of the receiver.
+        nick,
+        /// The name of the file.
+        filename,
+    }
+    /// The arguments of the `DCC SEND Complete` print event.
+    DccSendComplete = DCC_SEND_COMPLETE {
+        /// The name of the file.
+        filename,
+        /// The nick of the receiver.
+        nick,
+        /// The average transfer speed, in bytes per second.
+        cps,
+    }
+    /// The arguments of the `DCC SEND Connect` print event.
+    DccSendConnect = DCC_SEND_CONNECT {
+        /// The nick of the receiver.
+        nick,
+        /// The IP address of the receiver.
+        ip,
+        /// The name of the file.
+        filename,
+    }
+    /// The arguments of the `DCC SEND Failed` print event.
+    DccSendFailed = DCC_SEND_FAILED {
+        /// The name of the file.
+        filename,
+        /// The nick of the receiver.
+        nick,
+        /// The error message.
+        error,
+    }
+    /// The arguments of the `DCC SEND Offer` print event.
+    DccSendOffer = DCC_SEND_OFFER {
+        /// The nick of the sender.
+        nick,
+        /// The name of the file.
+        filename,
+        /// The size of the file.
+        size,
+        /// The IP address of the sender.
+        ip,
+    }
+    /// The arguments of the `DCC Stall` print event.
+    DccStall = DCC_STALL {
+        /// The type of DCC.
+        dcc_type,
+        /// The name of the file.
+        filename,
+        /// The nick of the other user.
+        nick,
+    }
+    /// The arguments of the `DCC Timeout` print event.
+    DccTimeout = DCC_TIMEOUT {
+        /// The type of DCC.
+        dcc_type,
+        /// The name of the file.
+        filename,
+        /// The nick of the other user.
+        nick,
+    }
+    /// The arguments of the `Delete Notify` print event.
+    DeleteNotify = DELETE_NOTIFY {
+        /// The nick that was removed.
+        nick,
+    }
+    /// The arguments of the `Disconnected` print event.
+    Disconnected = DISCONNECTED {
+        /// The error message.
+        error,
+    }
+    /// The arguments of the `Found IP` print event.
+    FoundIp = FOUND_IP {
+        /// The IP address.
+        ip,
+    }
+    /// The arguments of the `Generic Message` print event.
+    GenericMessage = GENERIC_MESSAGE {
+        /// The text in the nick column.
+        left,
+        /// The text in the message column.
+        right,
+    }
+    /// The arguments of the `Ignore Add` print event.
+    IgnoreAdd = IGNORE_ADD {
+        /// The ignored mask.
+        mask,
+    }
+    /// The arguments of the `Ignore Changed` print event.
+    IgnoreChanged = IGNORE_CHANGED {
+        /// The ignored mask.
+        mask,
+    }
+    /// The arguments of the `Ignore Footer` print event.
+    IgnoreFooter = IGNORE_FOOTER {}
+    /// The arguments of the `Ignore Header` print event.
+    IgnoreHeader = IGNORE_HEADER {}
+    /// The arguments of the `Ignore Remove` print event.
+    IgnoreRemove = IGNORE_REMOVE {
+        /// The mask that is no longer ignored.
+        mask,
+    }
+    /// The arguments of the `Ignorelist Empty` print event.
+    IgnorelistEmpty = IGNORELIST_EMPTY {}
+    /// The arguments of the `Invite` print event.
+    Invite = INVITE {
+        /// The channel that cannot be joined without an invite.
+        channel,
+    }
+    /// The arguments of the `Invited` print event.
+    Invited = INVITED {
+        /// The channel the client was invited to.
+        channel,
+        /// The nick of the user who sent the invite.
+        nick,
+        /// The server name.
+        server,
+    }
+    /// The arguments of the `Join` print event.
+    Join = JOIN {
+        /// The nick of the user who joined.
+        nick,
+        /// The channel.
+        channel,
+        /// The host of the user.
+        host,
+        /// The account of the user, if known.
+        account,
+    }
+    /// The arguments of the `Keyword` print event.
+    Keyword = KEYWORD {
+        /// The text.
+        text,
+    }
+    /// The arguments of the `Kick` print event.
+    Kick = KICK {
+        /// The nick of the user who did the kicking.
+        kicker,
+        /// The nick of the user who was kicked.
+        nick,
+        /// The channel.
+        channel,
+        /// The kick reason.
+        reason,
+    }
+    /// The arguments of the `Killed` print event.
+    Killed = KILLED {
+        /// The nick of the user who did the killing.
+        nick,
+        /// The kill reason.
+        reason,
+    }
+    /// The arguments of the `Message Send` print event.
+    MessageSend = MESSAGE_SEND {
+        /// Who the message was sent to.
+        receiver,
+        /// The message text.
+        text,
+    }
+    /// The arguments of the `Motd` print event.
+    Motd = MOTD {
+        /// The line of the MOTD.
+        text,
+    }
+    /// The arguments of the `MOTD Skipped` print event.
+    MotdSkipped = MOTD_SKIPPED {}
+    /// The arguments of the `Nick Clash` print event.
+    NickClash = NICK_CLASH {
+        /// The nick that is in use.
+        nick,
+        /// The nick that will be tried instead.
+        new_nick,
+    }
+    /// The arguments of the `Nick Erroneous` print event.
+    NickErroneous = NICK_ERRONEOUS {
+        /// The erroneous nick.
+        nick,
+    }
+    /// The arguments of the `Nick Failed` print event.
+    NickFailed = NICK_FAILED {}
+    /// The arguments of the `No DCC` print event.
+    NoDcc = NO_DCC {}
+    /// The arguments of the `No Running Process` print event.
+    NoRunningProcess = NO_RUNNING_PROCESS {}
+    /// The arguments of the `Notice` print event.
+    Notice = NOTICE {
+        /// The nick of the sender.
+        nick,
+        /// The notice text.
+        text,
+    }
+    /// The arguments of the `Notice Send` print event.
+    NoticeSend = NOTICE_SEND {
+        /// Who the notice was sent to.
+        receiver,
+        /// The notice text.
+        text,
+    }
+    /// The arguments of the `Notify Away` print event.
+    NotifyAway = NOTIFY_AWAY {
+        /// The nick of the user.
+        nick,
+        /// The away reason.
+        reason,
+    }
+    /// The arguments of the `Notify Back` print event.
+    NotifyBack = NOTIFY_BACK {
+        /// The nick of the user.
+        nick,
+    }
+    /// The arguments of the `Notify Empty` print event.
+    NotifyEmpty = NOTIFY_EMPTY {}
+    /// The arguments of the `Notify Header` print event.
+    NotifyHeader = NOTIFY_HEADER {}
+    /// The arguments of the `Notify Number` print event.
+    NotifyNumber = NOTIFY_NUMBER {
+        /// The number of users in the notify list.
+        count,
+    }
+    /// The arguments of the `Notify Offline` print event.
+    NotifyOffline = NOTIFY_OFFLINE {
+        /// The nick of the user.
+        nick,
+        /// The server name.
+        server,
+        /// The network name.
+        network,
+    }
+    /// The arguments of the `Notify Online` print event.
+    NotifyOnline = NOTIFY_ONLINE {
+        /// The nick of the user.
+        nick,
+        /// The server name.
+        server,
+        /// The network name.
+        network,
+    }
+    /// The arguments of the `Open Dialog` print event.
+    OpenDialog = OPEN_DIALOG {}
+    /// The arguments of the `Part` print event.
+    Part = PART {
+        /// The nick of the user who left.
+        nick,
+        /// The host of the user.
+        host,
+        /// The channel.
+        channel,
+    }
+    /// The arguments of the `Part with Reason` print event.
+    PartWithReason = PART_WITH_REASON {
+        /// The nick of the user who left.
+        nick,
+        /// The host of the user.
+        host,
+        /// The channel.
+        channel,
+        /// The part reason.
+        reason,
+    }
+    /// The arguments of the `Ping Reply` print event.
+    PingReply = PING_REPLY {
+        /// Who replied.
+        nick,
+        /// The round trip time, in seconds.
+        seconds,
+    }
+    /// The arguments of the `Ping Timeout` print event.
+    PingTimeout = PING_TIMEOUT {
+        /// How long the server has not responded for, in seconds.
+        seconds,
+    }
+    /// The arguments of the `Private Action` print event.
+    PrivateAction = PRIVATE_ACTION {
+        /// The nick of the sender.
+        nick,
+        /// The action text.
+        text,
+        /// The text shown if the sender is identified.
+        identified,
+    }
+    /// The arguments of the `Private Action to Dialog` print event.
+    PrivateActionToDialog = PRIVATE_ACTION_TO_DIALOG {
+        /// The nick of the sender.
+        nick,
+        /// The action text.
+        text,
+        /// The text shown if the sender is identified.
+        identified,
+    }
+    /// The arguments of the `Private Message` print event.
+    PrivateMessage = PRIVATE_MESSAGE {
+        /// The nick of the sender.
+        nick,
+        /// The message text.
+        text,
+        /// The text shown if the sender is identified.
+        identified,
+    }
+    /// The arguments of the `Private Message to Dialog` print event.
+    PrivateMessageToDialog = PRIVATE_MESSAGE_TO_DIALOG {
+        /// The nick of the sender.
+        nick,
+        /// The message text.
+        text,
+        /// The text shown if the sender is identified.
+        identified,
+    }
+    /// The arguments of the `Process Already Running` print event.
+    ProcessAlreadyRunning = PROCESS_ALREADY_RUNNING {}
+    /// The arguments of the `Quit` print event.
+    Quit = QUIT {
+        /// The nick of the user who quit.
+        nick,
+        /// The quit reason.
+        reason,
+        /// The host of the user.
+        host,
+    }
+    /// The arguments of the `Raw Modes` print event.
+    RawModes = RAW_MODES {
+        /// The nick of the user who set the modes.
+        nick,
+        /// The raw mode string.
+        modes,
+    }
+    /// The arguments of the `Receive Wallops` print event.
+    ReceiveWallops = RECEIVE_WALLOPS {
+        /// The nick of the sender.
+        nick,
+        /// The message text.
+        text,
+    }
+    /// The arguments of the `Resolving User` print event.
+    ResolvingUser = RESOLVING_USER {
+        /// The nick of the user.
+        nick,
+        /// The host being resolved.
+        host,
+    }
+    /// The arguments of the `SASL Authenticating` print event.
+    SaslAuthenticating = SASL_AUTHENTICATING {
+        /// The username.
+        username,
+        /// The SASL mechanism.
+        mechanism,
+    }
+    /// The arguments of the `SASL Response` print event.
+    SaslResponse = SASL_RESPONSE {
+        /// The server name.
+        server,
+        /// The raw numeric.
+        numeric,
+        /// The username.
+        username,
+        /// The response text.
+        text,
+    }
+    /// The arguments of the `Server Connected` print event.
+    ServerConnected = SERVER_CONNECTED {}
+    /// The arguments of the `Server Error` print event.
+    ServerError = SERVER_ERROR {
+        /// The error text.
+        text,
+    }
+    /// The arguments of the `Server Lookup` print event.
+    ServerLookup = SERVER_LOOKUP {
+        /// The server being looked up.
+        server,
+    }
+    /// The arguments of the `Server Notice` print event.
+    ServerNotice = SERVER_NOTICE {
+        /// The notice text.
+        text,
+        /// The server name.
+        server,
+    }
+    /// The arguments of the `Server Text` print event.
+    ServerText = SERVER_TEXT {
+        /// The text.
+        text,
+        /// The server name.
+        server,
+        /// The raw numeric.
+        numeric,
+    }
+    /// The arguments of the `SSL Message` print event.
+    SslMessage = SSL_MESSAGE {
+        /// The message text.
+        text,
+        /// The server name.
+        server,
+    }
+    /// The arguments of the `Stop Connection` print event.
+    StopConnection = STOP_CONNECTION {
+        /// The IP address that was being connected to.
+        ip,
+    }
+    /// The arguments of the `Topic` print event.
+    Topic = TOPIC {
+        /// The channel.
+        channel,
+        /// The topic.
+        topic,
+    }
+    /// The arguments of the `Topic Change` print event.
+    TopicChange = TOPIC_CHANGE {
+        /// The nick of the user who changed the topic.
+        nick,
+        /// The new topic.
+        topic,
+        /// The channel.
+        channel,
+    }
+    /// The arguments of the `Topic Creation` print event.
+    TopicCreation = TOPIC_CREATION {
+        /// The channel.
+        channel,
+        /// Who set the topic.
+        creator,
+        /// When the topic was set.
+        time,
+    }
+    /// The arguments of the `Unknown Host` print event.
+    UnknownHost = UNKNOWN_HOST {}
+    /// The arguments of the `User Limit` print event.
+    UserLimit = USER_LIMIT {
+        /// The channel that is full.
+        channel,
+    }
+    /// The arguments of the `Users On Channel` print event.
+    UsersOnChannel = USERS_ON_CHANNEL {
+        /// The channel.
+        channel,
+        /// The space-separated list of users.
+        users,
+    }
+    /// The arguments of the `WhoIs Authenticated` print event.
+    WhoisAuthenticated = WHOIS_AUTHENTICATED {
+        /// The nick of the user.
+        nick,
+        /// The message text.
+        text,
+        /// The account of the user.
+        account,
+    }
+    /// The arguments of the `WhoIs Away Line` print event.
+    WhoisAwayLine = WHOIS_AWAY_LINE {
+        /// The nick of the user.
+        nick,
+        /// The away reason.
+        reason,
+    }
+    /// The arguments of the `WhoIs Channel/Oper Line` print event.
+    WhoisChannelOrOperLine = WHOIS_CHANNEL_OR_OPER_LINE {
+        /// The nick of the user.
+        nick,
+        /// The channel list or oper message.
+        text,
+    }
+    /// The arguments of the `WhoIs End` print event.
+    WhoisEnd = WHOIS_END {
+        /// The nick of the user.
+        nick,
+    }
+    /// The arguments of the `WhoIs Identified` print event.
+    WhoisIdentified = WHOIS_IDENTIFIED {
+        /// The nick of the user.
+        nick,
+        /// The message text.
+        text,
+    }
+    /// The arguments of the `WhoIs Idle Line` print event.
+    WhoisIdleLine = WHOIS_IDLE_LINE {
+        /// The nick of the user.
+        nick,
+        /// How long the user has been idle.
+        idle,
+    }
+    /// The arguments of the `WhoIs Idle Line with Signon` print event.
+    WhoisIdleLineWithSignon = WHOIS_IDLE_LINE_WITH_SIGNON {
+        /// The nick of the user.
+        nick,
+        /// How long the user has been idle.
+        idle,
+        /// When the user signed on.
+        signon,
+    }
+    /// The arguments of the `WhoIs Name Line` print event.
+    WhoisNameLine = WHOIS_NAME_LINE {
+        /// The nick of the user.
+        nick,
+        /// The username of the user.
+        username,
+        /// The host of the user.
+        host,
+        /// The real name of the user.
+        real_name,
+    }
+    /// The arguments of the `WhoIs Real Host` print event.
+    WhoisRealHost = WHOIS_REAL_HOST {
+        /// The nick of the user.
+        nick,
+        /// The real `user@host` of the user.
+        real_host,
+        /// The real IP address of the user.
+        real_ip,
+        /// The message text.
+        text,
+    }
+    /// The arguments of the `WhoIs Server Line` print event.
+    WhoisServerLine = WHOIS_SERVER_LINE {
+        /// The nick of the user.
+        nick,
+        /// The server the user is connected to.
+        server,
+    }
+    /// The arguments of the `WhoIs Special` print event.
+    WhoisSpecial = WHOIS_SPECIAL {
+        /// The nick of the user.
+        nick,
+        /// The message text.
+        text,
+        /// The raw numeric.
+        numeric,
+    }
+    /// The arguments of the `You Join` print event.
+    YouJoin = YOU_JOIN {
+        /// The client's nick.
+        nick,
+        /// The channel.
+        channel,
+        /// The client's host.
+        host,
+        /// The client's account, if known.
+        account,
+    }
+    /// The arguments of the `You Kicked` print event.
+    YouKicked = YOU_KICKED {
+        /// The client's nick.
+        nick,
+        /// The channel.
+        channel,
+        /// The nick of the user who did the kicking.
+        kicker,
+        /// The kick reason.
+        reason,
+    }
+    /// The arguments of the `You Part` print event.
+    YouPart = YOU_PART {
+        /// The client's nick.
+        nick,
+        /// The client's host.
+        host,
+        /// The channel.
+        channel,
+    }
+    /// The arguments of the `You Part with Reason` print event.
+    YouPartWithReason = YOU_PART_WITH_REASON {
+        /// The client's nick.
+        nick,
+        /// The client's host.
+        host,
+        /// The channel.
+        channel,
+        /// The part reason.
+        reason,
+    }
+    /// The arguments of the `Your Action` print event.
+    YourAction = YOUR_ACTION {
+        /// The client's nick.
+        nick,
+        /// The action text.
+        text,
+        /// The client's mode character, if any.
+        mode,
+    }
+    /// The arguments of the `Your Invitation` print event.
+    YourInvitation = YOUR_INVITATION {
+        /// The nick of the user who was invited.
+        nick,
+        /// The channel.
+        channel,
+        /// The server name.
+        server,
+    }
+    /// The arguments of the `Your Message` print event.
+    YourMessage = YOUR_MESSAGE {
+        /// The client's nick.
+        nick,
+        /// The message text.
+        text,
+        /// The client's mode character, if any.
+        mode,
+        /// The text shown if the client is identified.
+        identified,
+    }
+    /// The arguments of the `Your Nick Changing` print event.
+    YourNickChanging = YOUR_NICK_CHANGING {
+        /// The old nick.
+        old_nick,
+        /// The new nick.
+        new_nick,
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::MockHexchat;
+    use crate::{add_typed_print_listener, print_plain, EatMode, Priority};
+
+    #[test]
+    fn typed_listener() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        add_typed_print_listener(Priority::NORMAL, |m: ChannelMessage, d| {
+            print_plain(&format!(
+                "{}|{}|{}|{}|{}",
+                m.nick,
+                m.text,
+                m.mode,
+                m.identified,
+                d.timestamp()
+            ));
+            if m.text.starts_with('!') {
+                EatMode::Hexchat
+            } else {
+                EatMode::None
+            }
+        });
+        let eat = mock.inject_print_event_at("Channel Message", &["bob", "!ping", "@"], 1000);
+        assert_eq!(eat, EatMode::Hexchat);
+        let eat = mock.inject_print_event("Channel Message", &["bob", "hi"]);
+        assert_eq!(eat, EatMode::None);
+        mock.inject_print_event("Join", &["bob", "#test", "bob@example.com"]);
+        assert_eq!(mock.printed(), ["bob|!ping|@||1000", "bob|hi|||0"]);
+    }
+
+    #[test]
+    fn emit() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let event = Join {
+            nick: "bob".to_string(),
+            channel: "#test".to_string(),
+            host: "bob@example.com".to_string(),
+            ..Join::default()
+        };
+        assert!(event.emit());
+        let (name, args) = mock.emitted().remove(0);
+        assert_eq!(name, "Join");
+        assert_eq!(Join::from_args(&args), event);
+    }
+}
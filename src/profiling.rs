@@ -0,0 +1,199 @@
+use crate::{deregister_command, register_command, CallbackKind, Command, EatMode, Priority};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Keyed by hook ID, and only `Some` while the plugin is loaded.
+static PROFILES: Mutex<Option<HashMap<u64, Stats>>> = Mutex::new(None);
+static COMMAND: Mutex<Option<Command>> = Mutex::new(None);
+
+struct Stats {
+    kind: CallbackKind,
+    name: String,
+    calls: u64,
+    total: Duration,
+    max: Duration,
+}
+
+/// How often, and for how long, one of your plugin's hooks has been called.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HookProfile {
+    kind: CallbackKind,
+    name: String,
+    calls: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl HookProfile {
+    /// Gets the kind of hook.
+    pub fn get_kind(&self) -> CallbackKind {
+        self.kind
+    }
+    /// Gets the name of the hook, e.g. the command or event name. This is empty for hooks that
+    /// have no name, such as timer tasks.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    /// Gets the number of times the hook has been called.
+    pub fn get_calls(&self) -> u64 {
+        self.calls
+    }
+    /// Gets the total time spent in the hook.
+    pub fn get_total_time(&self) -> Duration {
+        self.total
+    }
+    /// Gets the longest time spent in a single call to the hook.
+    pub fn get_max_time(&self) -> Duration {
+        self.max
+    }
+    /// Gets the average time spent in a single call to the hook.
+    pub fn get_mean_time(&self) -> Duration {
+        if self.calls == 0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_nanos((self.total.as_nanos() / u128::from(self.calls)) as u64)
+        }
+    }
+}
+
+/// Gets the profiles of every hook that has been called since the plugin was loaded, or since
+/// `reset_profiles` was last called, in descending order of total time.
+pub fn get_profiles() -> Vec<HookProfile> {
+    let lock = PROFILES.lock();
+    let mut profiles = lock
+        .iter()
+        .flat_map(|map| map.values())
+        .map(|stats| HookProfile {
+            kind: stats.kind,
+            name: stats.name.clone(),
+            calls: stats.calls,
+            total: stats.total,
+            max: stats.max,
+        })
+        .collect::<Vec<_>>();
+    profiles.sort_by(|a, b| b.total.cmp(&a.total));
+    profiles
+}
+
+/// Clears all recorded profiles.
+pub fn reset_profiles() {
+    if let Some(profiles) = PROFILES.lock().as_mut() {
+        profiles.clear();
+    }
+}
+
+pub(crate) fn record(kind: CallbackKind, name: &str, id: u64, elapsed: Duration) {
+    let mut lock = PROFILES.lock();
+    // The callback may have unloaded the plugin.
+    let profiles = match lock.as_mut() {
+        Some(profiles) => profiles,
+        None => return,
+    };
+    let stats = profiles.entry(id).or_insert_with(|| Stats {
+        kind,
+        name: name.to_string(),
+        calls: 0,
+        total: Duration::from_secs(0),
+        max: Duration::from_secs(0),
+    });
+    stats.calls += 1;
+    stats.total += elapsed;
+    if elapsed > stats.max {
+        stats.max = elapsed;
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_micros() as f64 / 1000.0
+}
+
+/// Registers `/<plugin>_profile`, which prints the profiles, or clears them with `reset`.
+pub(crate) fn init(plugin_name: &str) {
+    *PROFILES.lock() = Some(HashMap::new());
+    let name = format!("{}_profile", plugin_name.replace(' ', "_"));
+    let help_text = format!(
+        "Usage: {}, prints how long each of {}'s hooks has taken to run\n{} RESET, clears the \
+         recorded times",
+        name.to_uppercase(),
+        plugin_name,
+        name.to_uppercase()
+    );
+    let command = register_command(&name, &help_text, Priority::NORMAL, |word| {
+        if word
+            .get(1)
+            .map_or(false, |w| w.eq_ignore_ascii_case("reset"))
+        {
+            reset_profiles();
+            crate::print_plain("Profiles cleared.");
+            return EatMode::All;
+        }
+        let profiles = get_profiles();
+        if profiles.is_empty() {
+            crate::print_plain("No hooks have been called yet.");
+        }
+        for profile in profiles {
+            let label = if profile.name.is_empty() {
+                profile.kind.to_string()
+            } else {
+                format!("{} '{}'", profile.kind, profile.name)
+            };
+            crate::print_plain(&format!(
+                "{}: {} calls, {:.3}ms total, {:.3}ms mean, {:.3}ms max",
+                label,
+                profile.calls,
+                millis(profile.total),
+                millis(profile.get_mean_time()),
+                millis(profile.max)
+            ));
+        }
+        EatMode::All
+    });
+    *COMMAND.lock() = Some(command);
+}
+
+/// Gets whether a hook is the command registered by `init`.
+#[cfg(any(feature = "diagnostics", feature = "testing"))]
+pub(crate) fn is_internal(hook: *mut crate::c::hexchat_hook) -> bool {
+    COMMAND
+        .lock()
+        .as_ref()
+        .map_or(false, |command| hook == command.0)
+}
+
+pub(crate) fn shutdown() {
+    let command = COMMAND.lock().take();
+    if let Some(command) = command {
+        deregister_command(command);
+    }
+    let profiles = PROFILES.lock().take();
+    drop(profiles);
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::MockHexchat;
+
+    #[test]
+    fn profiles() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let old = register_command("greet", "", Priority::NORMAL, |_| EatMode::All);
+        mock.run_command("greet");
+        deregister_command(old);
+        // Likely to be given the hook that `old` pointed to, but still profiled separately.
+        register_command("greet", "", Priority::NORMAL, |_| EatMode::All);
+        mock.run_command("greet");
+        mock.run_command("greet");
+        let mut calls = get_profiles()
+            .iter()
+            .filter(|p| p.get_kind() == CallbackKind::Command && p.get_name() == "greet")
+            .map(HookProfile::get_calls)
+            .collect::<Vec<_>>();
+        calls.sort_unstable();
+        assert_eq!(calls, [1, 2]);
+        mock.unload();
+        assert!(get_profiles().is_empty());
+    }
+}
@@ -0,0 +1,70 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const MAGIC: &[u8; 4] = b"HCRS";
+
+/// A snapshot of your plugin's state, saved by `Plugin::save_state` when the plugin is unloaded
+/// and handed back through `PluginContext::get_reload_state` the next time it is loaded.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ReloadState {
+    data: Vec<u8>,
+}
+
+impl ReloadState {
+    /// Creates a new `ReloadState` from serialized data, in whatever format you like.
+    pub fn new(data: impl Into<Vec<u8>>) -> Self {
+        Self { data: data.into() }
+    }
+    /// Gets the serialized data.
+    pub fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+    /// Gets the serialized data as a string, or `None` if it isn't valid UTF-8.
+    pub fn get_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.data).ok()
+    }
+    /// Converts this `ReloadState` into its serialized data.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+fn state_file(name: &str) -> PathBuf {
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    crate::get_config_dir().join(format!("addon_{}.state", name))
+}
+
+pub(crate) fn save(name: &str, version: u32, state: &ReloadState) -> io::Result<()> {
+    let mut bytes = Vec::with_capacity(8 + state.data.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&version.to_le_bytes());
+    bytes.extend_from_slice(&state.data);
+    fs::write(state_file(name), bytes)
+}
+
+/// Loads and deletes the saved state, discarding it if it was saved with a different version.
+pub(crate) fn load(name: &str, version: u32) -> Option<ReloadState> {
+    let path = state_file(name);
+    let bytes = fs::read(&path).ok()?;
+    fs::remove_file(&path).ok();
+    if bytes.len() < 8 || &bytes[..4] != MAGIC {
+        return None;
+    }
+    let mut saved_version = [0; 4];
+    saved_version.copy_from_slice(&bytes[4..8]);
+    if u32::from_le_bytes(saved_version) == version {
+        Some(ReloadState::new(&bytes[8..]))
+    } else {
+        None
+    }
+}
@@ -1,8 +1,11 @@
 #![allow(non_camel_case_types)]
 
+use crate::irc::Message;
 use crate::{from_cstring, IrcIdent, IrcIdentRef, UserMask, UserString};
+use bitflags::bitflags;
 use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
 use std::os::raw::c_char;
+use std::str;
 
 /// A type representing a server response. Used with `add_server_response_listener`. It is
 /// not recommended you implement this on your own types.
@@ -14,6 +17,17 @@ where
     const ID: &'static str;
     #[doc(hidden)]
     unsafe fn create(word: *mut *mut c_char, word_eol: *mut *mut c_char) -> Option<Self>;
+    /// Creates the response from a parsed message, as though it had been received from the
+    /// server.
+    ///
+    /// Returns `None` if the message is a different response, or could not be parsed as this one.
+    fn from_message(message: &Message) -> Option<Self> {
+        if message.get_command() != Self::ID {
+            return None;
+        }
+        let (mut word, mut word_eol) = message.to_words();
+        unsafe { Self::create(word.ptr(), word_eol.ptr()) }
+    }
 }
 
 ///// A `ServerResponse` corresponding to `RPL_WELCOME` (`001`).
@@ -36,18 +50,19 @@ where
 //}
 
 macro_rules! rpl {
-    ($t:ident[$e:expr] { global($word:ident $word_eol:ident) { $($s:stmt;)* } ($this:ident) $([$desc:expr] $name:ident : $ftype:ty [$rtype:ty] get $getter:block parse $parser:block)* }) => {
-        rpl!(@RPL (stringify!($t), stringify!($e)) $t[$e] { global($word $word_eol) { $($s;)* } ($this) $([$desc] $name : $ftype [$rtype] get $getter parse $parser)* });
+    ($(#[$attr:meta])* $t:ident[$e:expr] { global($word:ident $word_eol:ident) { $($s:stmt;)* } ($this:ident) $([$desc:expr] $name:ident : $ftype:ty [$rtype:ty] get $getter:block parse $parser:block)* }) => {
+        rpl!(@RPL (stringify!($t), stringify!($e)) $(#[$attr])* $t[$e] { global($word $word_eol) { $($s;)* } ($this) $([$desc] $name : $ftype [$rtype] get $getter parse $parser)* });
     };
     ($t:ident[$e:expr] empty) => {
         rpl!($t[$e] { global(_a _b) {} (_c) });
     };
-    (@RPL ($te:expr, $ee:expr) $t:ident[$e:expr] { global($word:ident $word_eol:ident) { $($s:stmt;)* } ($this:ident) $([$desc:expr] $name:ident : $ftype:ty [$rtype:ty] get $getter:block parse $parser:block)* }) => {
+    (@RPL ($te:expr, $ee:expr) $(#[$attr:meta])* $t:ident[$e:expr] { global($word:ident $word_eol:ident) { $($s:stmt;)* } ($this:ident) $([$desc:expr] $name:ident : $ftype:ty [$rtype:ty] get $getter:block parse $parser:block)* }) => {
         #[doc = "A `ServerResponse` corresponding to `"]
         #[doc = $te]
         #[doc = "` (`"]
         #[doc = $ee]
         #[doc = "`)"]
+        $(#[$attr])*
         pub struct $t {
             server: IrcIdent,
             target: IrcIdent,
@@ -56,6 +71,7 @@ macro_rules! rpl {
             )*
         }
 
+        #[allow(deprecated)]
         impl $t {
             #[doc = "The server that sent the response."]
             pub fn server(&self) -> IrcIdentRef {
@@ -74,6 +90,7 @@ macro_rules! rpl {
             )*
         }
 
+        #[allow(deprecated)]
         impl ServerReply for $t {
             const ID: &'static str = stringify!($e);
             unsafe fn create(
@@ -185,7 +202,9 @@ rpl!(RPL_MYINFO[004] {
         parse { from_cstring(*msg.offset(3)) }
 });
 
-rpl!(RPL_BOUNCE[005] {
+rpl!(
+#[deprecated(note = "servers send `005` as `RPL_ISUPPORT` instead, so this almost never parses")]
+RPL_BOUNCE[005] {
     global(msg _a) {
         let server_string = from_cstring(*msg.offset(2));
     }
@@ -193,13 +212,98 @@ rpl!(RPL_BOUNCE[005] {
     ["The alternative server to use."]
     server_name: String [&str]
         get { &this.server_name }
-        parse { server_string[..(server_string.len() - 1)].to_string() }
+        parse { server_string.trim_end_matches(',').to_string() }
     ["The port number of the alternative server."]
     port_number: u16 [u16]
         get { this.port_number }
         parse { from_cstring(*msg.offset(4)).parse().ok()? }
 });
 
+rpl!(RPL_ISUPPORT[005] {
+    global(msg _a) {
+        let mut vec = Vec::new();
+        for i in 0..28 {
+            let token = from_cstring(*msg.offset(i));
+            if token.is_empty() || token.starts_with(':') {
+                break;
+            }
+            vec.push(ISupportToken::new(&token));
+        };
+    }
+    (this)
+    ["The features the server advertised, in order."]
+    tokens: Vec<ISupportToken> [&[ISupportToken]]
+        get { &this.tokens }
+        parse { vec }
+});
+
+impl RPL_ISUPPORT {
+    /// The token for a feature, if it was advertised in this reply. Names are case-insensitive.
+    pub fn token(&self, name: &str) -> Option<&ISupportToken> {
+        self.tokens
+            .iter()
+            .find(|t| t.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A token in `RPL_ISUPPORT`, advertising a feature of the server.
+pub struct ISupportToken {
+    name: String,
+    value: Option<String>,
+    negated: bool,
+}
+
+impl ISupportToken {
+    fn new(token: &str) -> Self {
+        let (negated, token) = if token.starts_with('-') {
+            (true, &token[1..])
+        } else {
+            (false, token)
+        };
+        let mut split = token.splitn(2, '=');
+        let name = split.next().unwrap().to_string();
+        let value = split.next().map(unescape_isupport_value);
+        Self {
+            name,
+            value,
+            negated,
+        }
+    }
+    /// The name of the feature, e.g. `CHANTYPES`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// The value of the feature, with any `\xHH` escapes decoded, or `None` if it has none.
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_ref().map(String::as_str)
+    }
+    /// Whether the server is withdrawing the feature, i.e. the token was `-NAME`.
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+}
+
+fn unescape_isupport_value(value: &str) -> String {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        if byte == b'\\' && after.len() >= 3 && after[0] == b'x' {
+            let escaped = str::from_utf8(&after[1..3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(escaped) = escaped {
+                bytes.push(escaped);
+                rest = &after[3..];
+                continue;
+            }
+        }
+        bytes.push(byte);
+        rest = after;
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
 rpl!(RPL_USERHOST[302] {
     global(_a msg) {
         let string = from_cstring((*msg).offset(1));
@@ -360,23 +464,44 @@ rpl!(RPL_WHOISCHANNELS[319] {
     channels: Vec<ChannelEntry> [&[ChannelEntry]]
         get { &this.channels }
         parse {
+            let features = crate::get_server_features().unwrap_or_default();
+            let prefixes = features.get_prefix();
+            let chantypes = features.get_chantypes();
+            let mode_of = |c| prefixes.iter().find(|(_, prefix)| *prefix == c).map(|(m, _)| *m);
             let mut vec = Vec::new();
             let channels = from_cstring((*eol.offset(1)).offset(1));
-            for channel in channels.split(' ') {
-                let (operator, channel) = if &channel[..1] == "@" { (true, &channel[1..]) } else { (false, channel) };
-                let (voice, channel) = if &channel[..1] == "+" { (true, &channel[1..]) } else { (false, channel) };
-                let channel = IrcIdent(channel.to_string());
-                vec.push(ChannelEntry { channel, operator, voice });
+            for channel in channels.split(' ').filter(|c| !c.is_empty()) {
+                let mut modes = String::new();
+                let mut rest = channel;
+                // Prefixes such as `&` and `+` can also start a channel name, so one is only
+                // stripped if a prefix or channel name still follows it.
+                while let Some(c) = rest.chars().next() {
+                    let after = &rest[c.len_utf8()..];
+                    let followed = after
+                        .chars()
+                        .next()
+                        .map_or(false, |n| mode_of(n).is_some() || chantypes.contains(n));
+                    match mode_of(c) {
+                        Some(mode) if followed => {
+                            modes.push(mode);
+                            rest = after;
+                        }
+                        _ => break,
+                    }
+                }
+                let channel = IrcIdent(rest.to_string());
+                vec.push(ChannelEntry { channel, modes });
             }
             vec
         }
 });
 
-/// An entry for `RPL_WHOISCHANNELS`.
+/// An entry for `RPL_WHOISCHANNELS`. Its prefixes are interpreted according to the server's
+/// `PREFIX`, as returned by `ServerFeatures::get_prefix`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct ChannelEntry {
     channel: IrcIdent,
-    operator: bool,
-    voice: bool,
+    modes: String,
 }
 
 impl ChannelEntry {
@@ -384,16 +509,47 @@ impl ChannelEntry {
     pub fn channel(&self) -> IrcIdentRef {
         self.channel.as_ref()
     }
-    /// Whether the user is an operator.
+    /// The prefix modes the user has in the channel, e.g. `ov`. Servers without `multi-prefix`
+    /// only send the highest one.
+    pub fn modes(&self) -> &str {
+        &self.modes
+    }
+    /// Whether the user has a prefix mode in the channel.
+    pub fn has_mode(&self, mode: char) -> bool {
+        self.modes.contains(mode)
+    }
+    /// Whether the user is an operator, i.e. has mode `o`.
     pub fn operator(&self) -> bool {
-        self.operator
+        self.has_mode('o')
     }
-    /// Whether the user has voice.
+    /// Whether the user has voice, i.e. has mode `v`.
     pub fn voice(&self) -> bool {
-        self.voice
+        self.has_mode('v')
     }
 }
 
+rpl!(RPL_WHOISACCOUNT[330] {
+    global(msg _a) {}
+    (this)
+    ["The nick of the user."]
+    nick: IrcIdent [IrcIdentRef]
+        get { this.nick.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The account the user is logged in to."]
+    account: IrcIdent [IrcIdentRef]
+        get { this.account.as_ref() }
+        parse { IrcIdent(from_cstring(*msg.offset(1))) }
+});
+
+rpl!(RPL_WHOISSECURE[671] {
+    global(msg _a) {}
+    (this)
+    ["The nick of the user, who is using a secure connection."]
+    nick: IrcIdent [IrcIdentRef]
+        get { this.nick.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+});
+
 rpl!(RPL_WHOWASUSER[314] {
     global(msg eol) {
         let nick = from_cstring(*msg);
@@ -633,6 +789,204 @@ rpl!(RPL_ENDOFWHO[315] {
         parse { from_cstring(*msg) }
 });
 
+rpl!(RPL_WHOSPCRPL[354] {
+    global(msg eol) {
+        let mut vec = Vec::new();
+        for i in 0..28 {
+            let param = from_cstring(*msg.offset(i));
+            if param.starts_with(':') {
+                vec.push(trailing(*eol.offset(i)));
+                break;
+            } else if param.is_empty() {
+                break;
+            }
+            vec.push(param);
+        };
+    }
+    (this)
+    ["The raw parameters, which `parse` interprets according to the requested `WhoxFields`."]
+    params: Vec<String> [&[String]]
+        get { &this.params }
+        parse { vec }
+});
+
+impl RPL_WHOSPCRPL {
+    /// Interprets the reply's parameters as the specified fields.
+    ///
+    /// Returns `None` if the reply has too few parameters, or a number is invalid.
+    pub fn parse(&self, fields: WhoxFields) -> Option<WhoxEntry> {
+        let mut params = self.params.iter().cloned();
+        let mut entry = WhoxEntry::default();
+        for (field, _) in WhoxFields::LETTERS
+            .iter()
+            .filter(|(f, _)| fields.contains(*f))
+        {
+            let param = params.next()?;
+            match *field {
+                WhoxFields::TOKEN => entry.token = Some(param),
+                WhoxFields::CHANNEL => {
+                    entry.channel = Some(param).filter(|c| c != "*").map(IrcIdent)
+                }
+                WhoxFields::USERNAME => entry.username = Some(param),
+                WhoxFields::IP => entry.ip = Some(param),
+                WhoxFields::HOST => entry.host = Some(param),
+                WhoxFields::SERVER => entry.server = Some(param),
+                WhoxFields::NICK => entry.nick = Some(IrcIdent(param)),
+                WhoxFields::FLAGS => entry.flags = Some(param),
+                WhoxFields::HOPCOUNT => entry.hopcount = Some(param.parse().ok()?),
+                WhoxFields::IDLE => entry.idle = Some(param.parse().ok()?),
+                WhoxFields::ACCOUNT => {
+                    entry.account = Some(param).filter(|a| a != "0").map(IrcIdent)
+                }
+                WhoxFields::OPLEVEL => entry.oplevel = Some(param),
+                _ => entry.realname = Some(param),
+            }
+        }
+        Some(entry)
+    }
+}
+
+bitflags! {
+    /// The fields requested by a WHOX query, i.e. the letters after the `%` in
+    /// `WHO <mask> %<fields>`.
+    pub struct WhoxFields: u16 {
+        /// `t`: the query token.
+        const TOKEN = 1;
+        /// `c`: a channel the user is in.
+        const CHANNEL = 1 << 1;
+        /// `u`: the user's username.
+        const USERNAME = 1 << 2;
+        /// `i`: the user's IP address.
+        const IP = 1 << 3;
+        /// `h`: the user's hostname.
+        const HOST = 1 << 4;
+        /// `s`: the server the user is on.
+        const SERVER = 1 << 5;
+        /// `n`: the user's nick.
+        const NICK = 1 << 6;
+        /// `f`: the user's flags, e.g. `H@`.
+        const FLAGS = 1 << 7;
+        /// `d`: the user's hopcount.
+        const HOPCOUNT = 1 << 8;
+        /// `l`: the user's idle time, in seconds.
+        const IDLE = 1 << 9;
+        /// `a`: the user's account name.
+        const ACCOUNT = 1 << 10;
+        /// `o`: the user's op level in the channel.
+        const OPLEVEL = 1 << 11;
+        /// `r`: the user's real name.
+        const REALNAME = 1 << 12;
+    }
+}
+
+impl WhoxFields {
+    // In the order their values appear in `RPL_WHOSPCRPL`.
+    const LETTERS: [(WhoxFields, char); 13] = [
+        (WhoxFields::TOKEN, 't'),
+        (WhoxFields::CHANNEL, 'c'),
+        (WhoxFields::USERNAME, 'u'),
+        (WhoxFields::IP, 'i'),
+        (WhoxFields::HOST, 'h'),
+        (WhoxFields::SERVER, 's'),
+        (WhoxFields::NICK, 'n'),
+        (WhoxFields::FLAGS, 'f'),
+        (WhoxFields::HOPCOUNT, 'd'),
+        (WhoxFields::IDLE, 'l'),
+        (WhoxFields::ACCOUNT, 'a'),
+        (WhoxFields::OPLEVEL, 'o'),
+        (WhoxFields::REALNAME, 'r'),
+    ];
+    /// Gets the fields as the letters used in a WHOX query, e.g. `tna`.
+    pub fn letters(self) -> String {
+        Self::LETTERS
+            .iter()
+            .filter(|(f, _)| self.contains(*f))
+            .map(|(_, c)| c)
+            .collect()
+    }
+}
+
+/// A user entry in `RPL_WHOSPCRPL`. Fields that were not requested are `None`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WhoxEntry {
+    token: Option<String>,
+    channel: Option<IrcIdent>,
+    username: Option<String>,
+    ip: Option<String>,
+    host: Option<String>,
+    server: Option<String>,
+    nick: Option<IrcIdent>,
+    flags: Option<String>,
+    hopcount: Option<u32>,
+    idle: Option<u32>,
+    account: Option<IrcIdent>,
+    oplevel: Option<String>,
+    realname: Option<String>,
+}
+
+impl WhoxEntry {
+    /// The query token.
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_ref().map(String::as_str)
+    }
+    /// A channel the user is in, or `None` if they aren't in any visible channels.
+    pub fn channel(&self) -> Option<IrcIdentRef> {
+        self.channel.as_ref().map(IrcIdent::as_ref)
+    }
+    /// The username of the user.
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_ref().map(String::as_str)
+    }
+    /// The IP address of the user. Servers hiding it send `255.255.255.255`.
+    pub fn ip(&self) -> Option<&str> {
+        self.ip.as_ref().map(String::as_str)
+    }
+    /// The hostname of the user.
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_ref().map(String::as_str)
+    }
+    /// The server the user is on.
+    pub fn server(&self) -> Option<&str> {
+        self.server.as_ref().map(String::as_str)
+    }
+    /// The nick of the user.
+    pub fn nick(&self) -> Option<IrcIdentRef> {
+        self.nick.as_ref().map(IrcIdent::as_ref)
+    }
+    /// The flags of the user. Can include `H`, `G`, `*`, `@`, `+`, `&`
+    pub fn flags(&self) -> Option<&str> {
+        self.flags.as_ref().map(String::as_str)
+    }
+    /// The hopcount of the user.
+    pub fn hopcount(&self) -> Option<u32> {
+        self.hopcount
+    }
+    /// How long the user has been idle, in seconds.
+    pub fn idle(&self) -> Option<u32> {
+        self.idle
+    }
+    /// The account the user is logged in to, or `None` if they aren't logged in.
+    pub fn account(&self) -> Option<IrcIdentRef> {
+        self.account.as_ref().map(IrcIdent::as_ref)
+    }
+    /// The op level of the user in the channel.
+    pub fn oplevel(&self) -> Option<&str> {
+        self.oplevel.as_ref().map(String::as_str)
+    }
+    /// The real name of the user.
+    pub fn realname(&self) -> Option<&str> {
+        self.realname.as_ref().map(String::as_str)
+    }
+    /// The user's full userstring, if the nick, username, and host were all requested.
+    pub fn user(&self) -> Option<UserString> {
+        UserString::from_parts(
+            self.nick.as_ref()?,
+            self.username.as_ref()?,
+            self.host.as_ref()?,
+        )
+    }
+}
+
 /// The visibility of an IRC channel.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum ChannelVisibility {
@@ -1028,3 +1382,865 @@ rpl!(RPL_TRYAGAIN[263] {
         get { &this.command }
         parse { from_cstring(*msg) }
 });
+
+/// Strips the leading `:` from a trailing parameter, if it has one.
+unsafe fn trailing(ptr: *const c_char) -> String {
+    let string = from_cstring(ptr);
+    if string.starts_with(':') {
+        string[1..].to_string()
+    } else {
+        string
+    }
+}
+
+rpl!(ERR_NOSUCHNICK[401] {
+    global(msg eol) {}
+    (this)
+    ["The nick that doesn't exist."]
+    nick: IrcIdent [IrcIdentRef]
+        get { this.nick.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_NOSUCHSERVER[402] {
+    global(msg eol) {}
+    (this)
+    ["The server that doesn't exist."]
+    server_name: String [&str]
+        get { &this.server_name }
+        parse { from_cstring(*msg) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_NOSUCHCHANNEL[403] {
+    global(msg eol) {}
+    (this)
+    ["The channel that doesn't exist."]
+    channel: IrcIdent [IrcIdentRef]
+        get { this.channel.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_CANNOTSENDTOCHAN[404] {
+    global(msg eol) {}
+    (this)
+    ["The channel that couldn't be sent to."]
+    channel: IrcIdent [IrcIdentRef]
+        get { this.channel.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_TOOMANYCHANNELS[405] {
+    global(msg eol) {}
+    (this)
+    ["The channel that couldn't be joined."]
+    channel: IrcIdent [IrcIdentRef]
+        get { this.channel.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_WASNOSUCHNICK[406] {
+    global(msg eol) {}
+    (this)
+    ["The nick that was never used."]
+    nick: IrcIdent [IrcIdentRef]
+        get { this.nick.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_TOOMANYTARGETS[407] {
+    global(msg eol) {}
+    (this)
+    ["The target that was duplicated or exceeded the limit."]
+    target_name: String [&str]
+        get { &this.target_name }
+        parse { from_cstring(*msg) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_NOSUCHSERVICE[408] {
+    global(msg eol) {}
+    (this)
+    ["The service that doesn't exist."]
+    service: IrcIdent [IrcIdentRef]
+        get { this.service.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_NOORIGIN[409] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_INVALIDCAPCMD[410] {
+    global(msg eol) {}
+    (this)
+    ["The unknown `CAP` subcommand."]
+    command: String [&str]
+        get { &this.command }
+        parse { from_cstring(*msg) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_NORECIPIENT[411] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_NOTEXTTOSEND[412] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_NOTOPLEVEL[413] {
+    global(msg eol) {}
+    (this)
+    ["The mask with no top-level domain."]
+    mask: String [&str]
+        get { &this.mask }
+        parse { from_cstring(*msg) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_WILDTOPLEVEL[414] {
+    global(msg eol) {}
+    (this)
+    ["The mask with a wildcard top-level domain."]
+    mask: String [&str]
+        get { &this.mask }
+        parse { from_cstring(*msg) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_INPUTTOOLONG[417] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_UNKNOWNCOMMAND[421] {
+    global(msg eol) {}
+    (this)
+    ["The unknown command."]
+    command: String [&str]
+        get { &this.command }
+        parse { from_cstring(*msg) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_NOMOTD[422] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_NONICKNAMEGIVEN[431] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_ERRONEUSNICKNAME[432] {
+    global(msg eol) {}
+    (this)
+    ["The invalid nick."]
+    nick: IrcIdent [IrcIdentRef]
+        get { this.nick.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_NICKNAMEINUSE[433] {
+    global(msg eol) {}
+    (this)
+    ["The nick that is already in use."]
+    nick: IrcIdent [IrcIdentRef]
+        get { this.nick.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_NICKCOLLISION[436] {
+    global(msg eol) {}
+    (this)
+    ["The nick that collided."]
+    nick: IrcIdent [IrcIdentRef]
+        get { this.nick.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_UNAVAILRESOURCE[437] {
+    global(msg eol) {}
+    (this)
+    ["The nick or channel that is temporarily unavailable."]
+    resource: IrcIdent [IrcIdentRef]
+        get { this.resource.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_USERNOTINCHANNEL[441] {
+    global(msg eol) {}
+    (this)
+    ["The nick that isn't on the channel."]
+    nick: IrcIdent [IrcIdentRef]
+        get { this.nick.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The channel."]
+    channel: IrcIdent [IrcIdentRef]
+        get { this.channel.as_ref() }
+        parse { IrcIdent(from_cstring(*msg.offset(1))) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(2)) }
+});
+
+rpl!(ERR_NOTONCHANNEL[442] {
+    global(msg eol) {}
+    (this)
+    ["The channel you aren't on."]
+    channel: IrcIdent [IrcIdentRef]
+        get { this.channel.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_USERONCHANNEL[443] {
+    global(msg eol) {}
+    (this)
+    ["The nick that is already on the channel."]
+    nick: IrcIdent [IrcIdentRef]
+        get { this.nick.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The channel."]
+    channel: IrcIdent [IrcIdentRef]
+        get { this.channel.as_ref() }
+        parse { IrcIdent(from_cstring(*msg.offset(1))) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(2)) }
+});
+
+rpl!(ERR_NOTREGISTERED[451] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_NEEDMOREPARAMS[461] {
+    global(msg eol) {}
+    (this)
+    ["The command that was missing parameters."]
+    command: String [&str]
+        get { &this.command }
+        parse { from_cstring(*msg) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_ALREADYREGISTRED[462] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_PASSWDMISMATCH[464] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_YOUREBANNEDCREEP[465] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_KEYSET[467] {
+    global(msg eol) {}
+    (this)
+    ["The channel whose key is already set."]
+    channel: IrcIdent [IrcIdentRef]
+        get { this.channel.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_CHANNELISFULL[471] {
+    global(msg eol) {}
+    (this)
+    ["The channel that is full."]
+    channel: IrcIdent [IrcIdentRef]
+        get { this.channel.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_UNKNOWNMODE[472] {
+    global(msg eol) {}
+    (this)
+    ["The unknown mode."]
+    mode: char [char]
+        get { this.mode }
+        parse { from_cstring(*msg).chars().next()? }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_INVITEONLYCHAN[473] {
+    global(msg eol) {}
+    (this)
+    ["The channel that is invite-only."]
+    channel: IrcIdent [IrcIdentRef]
+        get { this.channel.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_BANNEDFROMCHAN[474] {
+    global(msg eol) {}
+    (this)
+    ["The channel you are banned from."]
+    channel: IrcIdent [IrcIdentRef]
+        get { this.channel.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_BADCHANNELKEY[475] {
+    global(msg eol) {}
+    (this)
+    ["The channel whose key was wrong."]
+    channel: IrcIdent [IrcIdentRef]
+        get { this.channel.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_BADCHANMASK[476] {
+    global(msg eol) {}
+    (this)
+    ["The invalid channel name."]
+    channel: IrcIdent [IrcIdentRef]
+        get { this.channel.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_NOCHANMODES[477] {
+    global(msg eol) {}
+    (this)
+    ["The channel that doesn't support modes."]
+    channel: IrcIdent [IrcIdentRef]
+        get { this.channel.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_BANLISTFULL[478] {
+    global(msg eol) {}
+    (this)
+    ["The channel whose list is full."]
+    channel: IrcIdent [IrcIdentRef]
+        get { this.channel.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The list mode."]
+    mode: char [char]
+        get { this.mode }
+        parse { from_cstring(*msg.offset(1)).chars().next()? }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(2)) }
+});
+
+rpl!(ERR_NOPRIVILEGES[481] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_CHANOPRIVSNEEDED[482] {
+    global(msg eol) {}
+    (this)
+    ["The channel you aren't an operator of."]
+    channel: IrcIdent [IrcIdentRef]
+        get { this.channel.as_ref() }
+        parse { IrcIdent(from_cstring(*msg)) }
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol.offset(1)) }
+});
+
+rpl!(ERR_CANTKILLSERVER[483] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_RESTRICTED[484] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_NOOPERHOST[491] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_UMODEUNKNOWNFLAG[501] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_USERSDONTMATCH[502] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_SASLFAIL[904] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_SASLTOOLONG[905] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_SASLABORTED[906] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+rpl!(ERR_SASLALREADY[907] {
+    global(_a eol) {}
+    (this)
+    ["The error message."]
+    message: String [&str]
+        get { &this.message }
+        parse { trailing(*eol) }
+});
+
+macro_rules! server_error {
+    ($($variant:ident($t:ident),)*) => {
+        /// Any of the `ERR_*` responses. Used with `add_server_error_listener`.
+        pub enum ServerError {
+            $(
+            #[doc = "An `"]
+            #[doc = stringify!($t)]
+            #[doc = "`."]
+            $variant($t),
+            )*
+        }
+
+        impl ServerError {
+            #[doc(hidden)]
+            pub const IDS: &'static [&'static str] = &[$($t::ID,)*];
+            /// The numeric ID of the response.
+            pub fn id(&self) -> &'static str {
+                match self {
+                    $(ServerError::$variant(_) => $t::ID,)*
+                }
+            }
+            /// The server that sent the response.
+            pub fn server(&self) -> IrcIdentRef {
+                match self {
+                    $(ServerError::$variant(e) => e.server(),)*
+                }
+            }
+            /// The target of the response.
+            pub fn target(&self) -> IrcIdentRef {
+                match self {
+                    $(ServerError::$variant(e) => e.target(),)*
+                }
+            }
+            /// The error message.
+            pub fn message(&self) -> &str {
+                match self {
+                    $(ServerError::$variant(e) => e.message(),)*
+                }
+            }
+            #[doc(hidden)]
+            pub unsafe fn create(word: *mut *mut c_char, word_eol: *mut *mut c_char) -> Option<Self> {
+                let id = from_cstring(*word.offset(2));
+                match id.as_str() {
+                    $($t::ID => $t::create(word, word_eol).map(ServerError::$variant),)*
+                    _ => None,
+                }
+            }
+            /// Creates the response from a parsed message, as though it had been received from the
+            /// server.
+            ///
+            /// Returns `None` if the message is not one of the errors, or could not be parsed as it.
+            pub fn from_message(message: &Message) -> Option<Self> {
+                let (mut word, mut word_eol) = message.to_words();
+                unsafe { Self::create(word.ptr(), word_eol.ptr()) }
+            }
+        }
+    };
+}
+
+server_error! {
+    NoSuchNick(ERR_NOSUCHNICK),
+    NoSuchServer(ERR_NOSUCHSERVER),
+    NoSuchChannel(ERR_NOSUCHCHANNEL),
+    CannotSendToChan(ERR_CANNOTSENDTOCHAN),
+    TooManyChannels(ERR_TOOMANYCHANNELS),
+    WasNoSuchNick(ERR_WASNOSUCHNICK),
+    TooManyTargets(ERR_TOOMANYTARGETS),
+    NoSuchService(ERR_NOSUCHSERVICE),
+    NoOrigin(ERR_NOORIGIN),
+    InvalidCapCmd(ERR_INVALIDCAPCMD),
+    NoRecipient(ERR_NORECIPIENT),
+    NoTextToSend(ERR_NOTEXTTOSEND),
+    NoTopLevel(ERR_NOTOPLEVEL),
+    WildTopLevel(ERR_WILDTOPLEVEL),
+    InputTooLong(ERR_INPUTTOOLONG),
+    UnknownCommand(ERR_UNKNOWNCOMMAND),
+    NoMotd(ERR_NOMOTD),
+    NoNicknameGiven(ERR_NONICKNAMEGIVEN),
+    ErroneousNickname(ERR_ERRONEUSNICKNAME),
+    NicknameInUse(ERR_NICKNAMEINUSE),
+    NickCollision(ERR_NICKCOLLISION),
+    UnavailResource(ERR_UNAVAILRESOURCE),
+    UserNotInChannel(ERR_USERNOTINCHANNEL),
+    NotOnChannel(ERR_NOTONCHANNEL),
+    UserOnChannel(ERR_USERONCHANNEL),
+    NotRegistered(ERR_NOTREGISTERED),
+    NeedMoreParams(ERR_NEEDMOREPARAMS),
+    AlreadyRegistered(ERR_ALREADYREGISTRED),
+    PasswdMismatch(ERR_PASSWDMISMATCH),
+    YoureBannedCreep(ERR_YOUREBANNEDCREEP),
+    KeySet(ERR_KEYSET),
+    ChannelIsFull(ERR_CHANNELISFULL),
+    UnknownMode(ERR_UNKNOWNMODE),
+    InviteOnlyChan(ERR_INVITEONLYCHAN),
+    BannedFromChan(ERR_BANNEDFROMCHAN),
+    BadChannelKey(ERR_BADCHANNELKEY),
+    BadChanMask(ERR_BADCHANMASK),
+    NoChanModes(ERR_NOCHANMODES),
+    BanListFull(ERR_BANLISTFULL),
+    NoPrivileges(ERR_NOPRIVILEGES),
+    ChanOPrivsNeeded(ERR_CHANOPRIVSNEEDED),
+    CantKillServer(ERR_CANTKILLSERVER),
+    Restricted(ERR_RESTRICTED),
+    NoOperHost(ERR_NOOPERHOST),
+    UmodeUnknownFlag(ERR_UMODEUNKNOWNFLAG),
+    UsersDontMatch(ERR_USERSDONTMATCH),
+    SaslFail(ERR_SASLFAIL),
+    SaslTooLong(ERR_SASLTOOLONG),
+    SaslAborted(ERR_SASLABORTED),
+    SaslAlready(ERR_SASLALREADY),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply<T: ServerReply>(line: &str) -> T {
+        T::from_message(&line.parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn isupport_token() {
+        let token = ISupportToken::new("CHANTYPES=#&");
+        assert_eq!(token.name(), "CHANTYPES");
+        assert_eq!(token.value(), Some("#&"));
+        assert!(!token.is_negated());
+        let token = ISupportToken::new("EXCEPTS");
+        assert_eq!(token.name(), "EXCEPTS");
+        assert_eq!(token.value(), None);
+        let token = ISupportToken::new("NETWORK=");
+        assert_eq!(token.value(), Some(""));
+        let token = ISupportToken::new("-KNOCK");
+        assert_eq!(token.name(), "KNOCK");
+        assert!(token.is_negated());
+        let token = ISupportToken::new("TARGMAX=PRIVMSG:4,NOTICE:=");
+        assert_eq!(token.value(), Some("PRIVMSG:4,NOTICE:="));
+    }
+
+    #[test]
+    fn isupport_value() {
+        assert_eq!(unescape_isupport_value(""), "");
+        assert_eq!(unescape_isupport_value("Example\\x20Net"), "Example Net");
+        assert_eq!(unescape_isupport_value("a\\x3Db\\x5c"), "a=b\\");
+        assert_eq!(unescape_isupport_value("\\xE2\\x98\\x83"), "\u{2603}");
+        assert_eq!(unescape_isupport_value("\\x2"), "\\x2");
+        assert_eq!(unescape_isupport_value("\\xZZ\\"), "\\xZZ\\");
+    }
+
+    #[test]
+    fn isupport() {
+        let isupport: RPL_ISUPPORT = reply(
+            ":irc.example.com 005 me CHANTYPES=# EXCEPTS -KNOCK NETWORK=Example\\x20Net \
+             :are supported by this server",
+        );
+        let names = isupport
+            .tokens()
+            .iter()
+            .map(ISupportToken::name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["CHANTYPES", "EXCEPTS", "KNOCK", "NETWORK"]);
+        assert_eq!(
+            isupport.token("network").and_then(ISupportToken::value),
+            Some("Example Net")
+        );
+        assert!(isupport.token("PREFIX").is_none());
+    }
+
+    #[test]
+    fn whox_letters() {
+        assert_eq!(WhoxFields::all().letters(), "tcuihsnfdlaor");
+        assert_eq!(
+            (WhoxFields::ACCOUNT | WhoxFields::NICK | WhoxFields::TOKEN).letters(),
+            "tna"
+        );
+        assert_eq!(WhoxFields::empty().letters(), "");
+    }
+
+    #[test]
+    fn whox_entry() {
+        let rpl: RPL_WHOSPCRPL = reply(
+            ":irc.example.com 354 me 42 #channel user 192.0.2.1 host.example.com \
+             irc.example.com nick H@ 0 17 account 0 :Real Name",
+        );
+        let entry = rpl.parse(WhoxFields::all()).unwrap();
+        assert_eq!(entry.token(), Some("42"));
+        assert_eq!(entry.channel().unwrap().0, "#channel");
+        assert_eq!(entry.username(), Some("user"));
+        assert_eq!(entry.ip(), Some("192.0.2.1"));
+        assert_eq!(entry.host(), Some("host.example.com"));
+        assert_eq!(entry.server(), Some("irc.example.com"));
+        assert_eq!(entry.nick().unwrap().0, "nick");
+        assert_eq!(entry.flags(), Some("H@"));
+        assert_eq!(entry.hopcount(), Some(0));
+        assert_eq!(entry.idle(), Some(17));
+        assert_eq!(entry.account().unwrap().0, "account");
+        assert_eq!(entry.oplevel(), Some("0"));
+        assert_eq!(entry.realname(), Some("Real Name"));
+        assert_eq!(
+            entry.user().unwrap().to_string(),
+            "nick!user@host.example.com"
+        );
+        let rpl: RPL_WHOSPCRPL = reply(":irc.example.com 354 me 7 * nick 0");
+        let entry = rpl
+            .parse(WhoxFields::TOKEN | WhoxFields::CHANNEL | WhoxFields::NICK | WhoxFields::ACCOUNT)
+            .unwrap();
+        assert_eq!(entry.token(), Some("7"));
+        assert!(entry.channel().is_none());
+        assert_eq!(entry.nick().unwrap().0, "nick");
+        assert!(entry.account().is_none());
+        assert!(entry.username().is_none());
+        assert!(entry.user().is_none());
+        assert!(rpl.parse(WhoxFields::all()).is_none());
+        let rpl: RPL_WHOSPCRPL = reply(":irc.example.com 354 me 7 nick soon");
+        assert!(rpl
+            .parse(WhoxFields::TOKEN | WhoxFields::NICK | WhoxFields::IDLE)
+            .is_none());
+    }
+
+    #[test]
+    fn errors() {
+        let err: ERR_NOSUCHNICK = reply(":irc.example.com 401 me ghost :No such nick/channel");
+        assert_eq!(err.server().0, "irc.example.com");
+        assert_eq!(err.target().0, "me");
+        assert_eq!(err.nick().0, "ghost");
+        assert_eq!(err.message(), "No such nick/channel");
+        let err: ERR_NICKNAMEINUSE = reply(":irc.example.com 433 * me :Nickname is already in use");
+        assert_eq!(err.target().0, "*");
+        assert_eq!(err.nick().0, "me");
+        assert_eq!(err.message(), "Nickname is already in use");
+        let err: ERR_USERNOTINCHANNEL =
+            reply(":irc.example.com 441 me them #channel :They aren't on that channel");
+        assert_eq!(err.nick().0, "them");
+        assert_eq!(err.channel().0, "#channel");
+        assert_eq!(err.message(), "They aren't on that channel");
+        let err: ERR_BANLISTFULL =
+            reply(":irc.example.com 478 me #channel b :Channel list is full");
+        assert_eq!(err.channel().0, "#channel");
+        assert_eq!(err.mode(), 'b');
+        assert_eq!(err.message(), "Channel list is full");
+        assert!(ERR_BANLISTFULL::from_message(
+            &":irc.example.com 478 me #channel".parse().unwrap()
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn server_error() {
+        let error = |line: &str| ServerError::from_message(&line.parse().unwrap());
+        match error(":irc.example.com 401 me ghost :No such nick/channel") {
+            Some(ServerError::NoSuchNick(e)) => assert_eq!(e.nick().0, "ghost"),
+            _ => panic!("expected ERR_NOSUCHNICK"),
+        }
+        let err = error(":irc.example.com 433 * me :Nickname is already in use").unwrap();
+        assert_eq!(err.id(), "433");
+        assert_eq!(err.server().0, "irc.example.com");
+        assert_eq!(err.target().0, "*");
+        assert_eq!(err.message(), "Nickname is already in use");
+        match error(":irc.example.com 441 me them #channel :They aren't on that channel") {
+            Some(ServerError::UserNotInChannel(e)) => assert_eq!(e.channel().0, "#channel"),
+            _ => panic!("expected ERR_USERNOTINCHANNEL"),
+        }
+        match error(":irc.example.com 478 me #channel b :Channel list is full") {
+            Some(ServerError::BanListFull(e)) => assert_eq!(e.mode(), 'b'),
+            _ => panic!("expected ERR_BANLISTFULL"),
+        }
+        assert!(error(":irc.example.com 001 me :Welcome").is_none());
+        assert!(error(":irc.example.com 478 me #channel").is_none());
+    }
+}
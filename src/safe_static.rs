@@ -1,30 +1,63 @@
 use std::ops::Deref;
 
-use parking_lot::{Once, OnceState, RwLock};
+use parking_lot::{
+    MappedMutexGuard, MappedRwLockReadGuard, MappedRwLockWriteGuard, Mutex, MutexGuard, Once,
+    OnceState, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
 use std::cell::UnsafeCell;
-use std::sync::atomic::AtomicBool;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 #[macro_export(local_inner_macros)]
 #[doc(hidden)]
 macro_rules! __safe_static_internal {
-    ($(#[$attr:meta])* ($($vis:tt)*) static lazy $N:ident : $T:ty = $e:expr; $($t:tt)*) => {
-        __safe_static_internal!(@LAZY TY, $(#[$attr])*, ($($vis)*), $N, $T, $e);
+    ($(#[$attr:meta])* ($($vis:tt)*) static $kind:ident $N:ident : $T:ty = $e:expr; $($t:tt)*) => {
+        __safe_static_internal!(@TY $kind, $(#[$attr])*, ($($vis)*), $N, $T, $e);
         safe_static!($($t)*);
     };
     ($(#[$attr:meta])* ($($vis:tt)*) static uninit $N:ident : $T:ty; $($t:tt)*) => {
         __safe_static_internal!(@UNINIT TY, $(#[$attr])*, ($($vis)*), $N, $T);
+        safe_static!($($t)*);
     };
-    (@LAZY TY, $(#[$attr:meta])*, ($($vis:tt)*), $N:ident, $T:ty, $e:expr) => {
+    (@TY lazy, $(#[$attr:meta])*, ($($vis:tt)*), $N:ident, $T:ty, $e:expr) => {
         $(#[$attr])*
         $($vis)* static $N: $crate::SafeLazy<$T> = $crate::SafeLazy { instance: unsafe { $crate::SafeLazyInstance::new() }, init_fn: || { $e } };
     };
+    (@TY mutex, $(#[$attr:meta])*, ($($vis:tt)*), $N:ident, $T:ty, $e:expr) => {
+        $(#[$attr])*
+        $($vis)* static $N: $crate::SafeMutex<$T> = $crate::SafeMutex { instance: unsafe { $crate::SafeMutexInstance::new() }, init_fn: || { $e } };
+    };
+    (@TY rwlock, $(#[$attr:meta])*, ($($vis:tt)*), $N:ident, $T:ty, $e:expr) => {
+        $(#[$attr])*
+        $($vis)* static $N: $crate::SafeRwLock<$T> = $crate::SafeRwLock { instance: unsafe { $crate::SafeRwLockInstance::new() }, init_fn: || { $e } };
+    };
+    (@TY cell, $(#[$attr:meta])*, ($($vis:tt)*), $N:ident, $T:ty, $e:expr) => {
+        $(#[$attr])*
+        $($vis)* static $N: $crate::SafeCell<$T> = $crate::SafeCell { instance: unsafe { $crate::SafeMutexInstance::new() }, init_fn: || { $e } };
+    };
     (@UNINIT TY, $(#[$attr:meta])*, ($($vis:tt)*), $N:ident, $T:ty) => {
         $(#[$attr])*
         $($vis)* static $N: $crate::SafeUninit<$T> = unsafe { $crate::SafeUninit::new() };
     };
     () => ()
 }
-/// A macro for creating `SafeLazy`s and `SafeUninit`s.
+/// A macro for creating safe statics: `SafeLazy`s, `SafeUninit`s, `SafeMutex`es, `SafeRwLock`s,
+/// and `SafeCell`s.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use hexchat::safe_static;
+/// use std::collections::HashMap;
+///
+/// safe_static! {
+///     static lazy GREETING: String = String::from("Hello");
+///     static uninit CONFIG: HashMap<String, String>;
+///     static mutex SEEN: Vec<String> = Vec::new();
+///     static rwlock NICKS: HashMap<String, String> = HashMap::new();
+///     static cell COUNTER: u32 = 0;
+/// }
+/// ```
 ///
 /// # Important
 ///
@@ -32,15 +65,15 @@ macro_rules! __safe_static_internal {
 /// implementation. To allow otherwise is undefined.
 #[macro_export(local_inner_macros)]
 macro_rules! safe_static {
-    ($(#[$attr:meta])* static lazy $N:ident : $T:ty = $e:expr; $($t:tt)*) => {
+    ($(#[$attr:meta])* static $kind:ident $N:ident : $T:ty = $e:expr; $($t:tt)*) => {
         // use `()` to explicitly forward the information about private items
-        __safe_static_internal!($(#[$attr])* () static lazy $N : $T = $e; $($t)*);
+        __safe_static_internal!($(#[$attr])* () static $kind $N : $T = $e; $($t)*);
     };
-    ($(#[$attr:meta])* pub static lazy $N:ident : $T:ty = $e:expr; $($t:tt)*) => {
-        __safe_static_internal!($(#[$attr])* (pub) static lazy $N : $T = $e; $($t)*);
+    ($(#[$attr:meta])* pub static $kind:ident $N:ident : $T:ty = $e:expr; $($t:tt)*) => {
+        __safe_static_internal!($(#[$attr])* (pub) static $kind $N : $T = $e; $($t)*);
     };
-    ($(#[$attr:meta])* pub ($($vis:tt)+) static lazy $N:ident : $T:ty = $e:expr; $($t:tt)*) => {
-        __safe_static_internal!($(#[$attr])* (pub ($($vis)+)) static lazy $N : $T = $e; $($t)*);
+    ($(#[$attr:meta])* pub ($($vis:tt)+) static $kind:ident $N:ident : $T:ty = $e:expr; $($t:tt)*) => {
+        __safe_static_internal!($(#[$attr])* (pub ($($vis)+)) static $kind $N : $T = $e; $($t)*);
     };
     ($(#[$attr:meta])* static uninit $N:ident : $T:ty; $($t:tt)*) => {
         // use `()` to explicitly forward the information about private items
@@ -151,27 +184,253 @@ impl<T> SafeUninit<T> {
     }
     /// Initializes this `SafeUninit` with a value. Only works the first time it's called.
     pub fn init(&self, instance: T) {
-        self.once
-            .call_once(|| unsafe { *self.instance.get() = Some(instance) });
         let ptr = &*self as *const Self;
-        ALLOCATED
-            .write()
-            .as_mut()
-            .unwrap()
-            .push(Deallocator(Box::new(move || unsafe {
-                (*(*ptr).instance.get()) = None
-            })));
+        self.once.call_once(|| unsafe {
+            *self.instance.get() = Some(instance);
+            ALLOCATED
+                .write()
+                .as_mut()
+                .unwrap()
+                .push(Deallocator(Box::new(move || {
+                    (*(*ptr).instance.get()) = None
+                })));
+        });
+    }
+    /// Gets whether this `SafeUninit` has been initialized with `init`.
+    pub fn is_initialized(&self) -> bool {
+        self.once.state() == OnceState::Done
+    }
+    /// Gets the value, or `None` if this `SafeUninit` has not been initialized yet. Unlike
+    /// dereferencing, this does not panic.
+    pub fn get(&self) -> Option<&T> {
+        if self.is_initialized() {
+            unsafe { &*self.instance.get() }.as_ref()
+        } else {
+            None
+        }
     }
 }
 
 impl<T> Deref for SafeUninit<T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
-        let state = self.once.state();
-        if state != OnceState::Done {
-            panic!("Uninitialized `SafeUninit`");
+        self.get().expect("Uninitialized `SafeUninit`")
+    }
+}
+
+/// A lazily-initialized resource behind a mutex, that is safe to use in a HexChat plugin.
+///
+/// Like a `SafeLazy`, it is initialized the first time it's used, and dropped after your plugin
+/// is dropped. Unlike one, it can be mutated, and reset to its initial value.
+///
+/// A `SafeMutex` must always be in a static variable. To do otherwise is undefined.
+///
+/// # Important
+///
+/// Any thread which accesses a safe static, mutex or no, must be killed inside your plugin's `Drop`
+/// implementation. To allow otherwise is undefined.
+pub struct SafeMutex<T>
+where
+    T: 'static,
+{
+    #[doc(hidden)]
+    pub instance: SafeMutexInstance<T>,
+    #[doc(hidden)]
+    pub init_fn: fn() -> T,
+}
+
+#[doc(hidden)]
+pub struct SafeMutexInstance<T> {
+    instance: Mutex<Option<T>>,
+    registered: AtomicBool,
+}
+
+impl<T> SafeMutexInstance<T> {
+    #[doc(hidden)]
+    pub const unsafe fn new() -> Self {
+        Self {
+            instance: Mutex::new(None),
+            registered: AtomicBool::new(false),
+        }
+    }
+    fn lock(&'static self, init_fn: fn() -> T) -> MutexGuard<'static, Option<T>> {
+        register(&self.registered, move || *self.instance.lock() = None);
+        let mut guard = self.instance.lock();
+        if guard.is_none() {
+            *guard = Some(init_fn());
+        }
+        guard
+    }
+}
+
+impl<T> SafeMutex<T> {
+    /// Locks the mutex, initializing the value first if necessary. Blocks until the lock is
+    /// available.
+    pub fn lock(&'static self) -> MappedMutexGuard<'static, T> {
+        MutexGuard::map(self.instance.lock(self.init_fn), |o| o.as_mut().unwrap())
+    }
+    /// Drops the current value, if any. The init function is run again the next time it's used.
+    pub fn reset(&'static self) {
+        let value = self.instance.instance.lock().take();
+        mem::drop(value);
+    }
+}
+
+unsafe impl<T> Sync for SafeMutex<T> where T: Send {}
+
+/// A lazily-initialized resource behind a reader-writer lock, that is safe to use in a HexChat
+/// plugin.
+///
+/// Like a `SafeLazy`, it is initialized the first time it's used, and dropped after your plugin
+/// is dropped. Unlike one, it can be mutated, and reset to its initial value.
+///
+/// A `SafeRwLock` must always be in a static variable. To do otherwise is undefined.
+///
+/// # Important
+///
+/// Any thread which accesses a safe static, mutex or no, must be killed inside your plugin's `Drop`
+/// implementation. To allow otherwise is undefined.
+pub struct SafeRwLock<T>
+where
+    T: 'static,
+{
+    #[doc(hidden)]
+    pub instance: SafeRwLockInstance<T>,
+    #[doc(hidden)]
+    pub init_fn: fn() -> T,
+}
+
+#[doc(hidden)]
+pub struct SafeRwLockInstance<T> {
+    instance: RwLock<Option<T>>,
+    registered: AtomicBool,
+}
+
+impl<T> SafeRwLockInstance<T> {
+    #[doc(hidden)]
+    pub const unsafe fn new() -> Self {
+        Self {
+            instance: RwLock::new(None),
+            registered: AtomicBool::new(false),
+        }
+    }
+}
+
+impl<T> SafeRwLock<T> {
+    /// Locks the value for reading, initializing it first if necessary. Blocks until the lock is
+    /// available.
+    pub fn read(&'static self) -> MappedRwLockReadGuard<'static, T> {
+        let instance = &self.instance;
+        register(&instance.registered, move || {
+            *instance.instance.write() = None
+        });
+        let guard = instance.instance.read();
+        let guard = if guard.is_some() {
+            guard
+        } else {
+            mem::drop(guard);
+            RwLockWriteGuard::downgrade(self.init())
+        };
+        RwLockReadGuard::map(guard, |o| o.as_ref().unwrap())
+    }
+    /// Locks the value for writing, initializing it first if necessary. Blocks until the lock is
+    /// available.
+    pub fn write(&'static self) -> MappedRwLockWriteGuard<'static, T> {
+        let instance = &self.instance;
+        register(&instance.registered, move || {
+            *instance.instance.write() = None
+        });
+        RwLockWriteGuard::map(self.init(), |o| o.as_mut().unwrap())
+    }
+    /// Drops the current value, if any. The init function is run again the next time it's used.
+    pub fn reset(&'static self) {
+        let value = self.instance.instance.write().take();
+        mem::drop(value);
+    }
+    fn init(&'static self) -> RwLockWriteGuard<'static, Option<T>> {
+        let mut guard = self.instance.instance.write();
+        if guard.is_none() {
+            *guard = Some((self.init_fn)());
         }
-        unsafe { &*self.instance.get() }.as_ref().unwrap()
+        guard
+    }
+}
+
+unsafe impl<T> Sync for SafeRwLock<T> where T: Send + Sync {}
+
+/// A lazily-initialized mutable value, that is safe to use in a HexChat plugin. Like a `Cell`, the
+/// value is only ever accessed by copying it in or out.
+///
+/// Like a `SafeLazy`, it is initialized the first time it's used, and dropped after your plugin
+/// is dropped. Unlike one, it can be mutated, and reset to its initial value.
+///
+/// A `SafeCell` must always be in a static variable. To do otherwise is undefined.
+///
+/// # Important
+///
+/// Any thread which accesses a safe static, mutex or no, must be killed inside your plugin's `Drop`
+/// implementation. To allow otherwise is undefined.
+pub struct SafeCell<T>
+where
+    T: 'static,
+{
+    #[doc(hidden)]
+    pub instance: SafeMutexInstance<T>,
+    #[doc(hidden)]
+    pub init_fn: fn() -> T,
+}
+
+impl<T> SafeCell<T> {
+    /// Gets a copy of the value, initializing it first if necessary.
+    pub fn get(&'static self) -> T
+    where
+        T: Clone,
+    {
+        self.instance.lock(self.init_fn).as_ref().unwrap().clone()
+    }
+    /// Sets the value.
+    pub fn set(&'static self, value: T) {
+        mem::drop(self.replace(value));
+    }
+    /// Sets the value, returning the old one.
+    pub fn replace(&'static self, value: T) -> T {
+        let mut guard = self.instance.lock(self.init_fn);
+        mem::replace(guard.as_mut().unwrap(), value)
+    }
+    /// Sets the value by applying a function to a copy of the old one, returning the new value. If
+    /// the function panics, the old value is kept.
+    ///
+    /// The cell is not locked while the function runs, so it may use the cell itself, but anything
+    /// it sets is overwritten by the function's result.
+    pub fn update(&'static self, function: impl FnOnce(T) -> T) -> T
+    where
+        T: Clone,
+    {
+        let value = function(self.get());
+        self.set(value.clone());
+        value
+    }
+    /// Drops the current value, if any. The init function is run again the next time it's used.
+    pub fn reset(&'static self) {
+        let value = self.instance.instance.lock().take();
+        mem::drop(value);
+    }
+}
+
+unsafe impl<T> Sync for SafeCell<T> where T: Send {}
+
+/// Registers a deallocator the first time a static is used, and again the first time after it has
+/// been deallocated.
+fn register(registered: &'static AtomicBool, dealloc: impl FnOnce() + 'static) {
+    if !registered.swap(true, Ordering::SeqCst) {
+        ALLOCATED
+            .write()
+            .as_mut()
+            .unwrap()
+            .push(Deallocator(Box::new(move || {
+                dealloc();
+                registered.store(false, Ordering::SeqCst);
+            })));
     }
 }
 
@@ -183,3 +442,27 @@ pub(crate) static ALLOCATED: RwLock<Option<Vec<Deallocator>>> = RwLock::new(None
 pub(crate) struct Deallocator(pub(crate) Box<dyn FnOnce()>);
 unsafe impl Send for Deallocator {}
 unsafe impl Sync for Deallocator {}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use crate::testing::MockHexchat;
+    use std::panic::{self, AssertUnwindSafe};
+
+    safe_static! {
+        static cell COUNTER: u32 = 1;
+    }
+
+    #[test]
+    fn cell_update() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        assert_eq!(COUNTER.update(|c| c + 1), 2);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            COUNTER.update(|_| panic!("update failed"))
+        }));
+        assert!(result.is_err());
+        assert_eq!(COUNTER.get(), 2);
+        assert_eq!(COUNTER.update(|c| c + COUNTER.get()), 4);
+        mock.unload();
+    }
+}
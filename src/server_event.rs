@@ -1,3 +1,4 @@
+use crate::irc::{split_token, Message, Prefix};
 use crate::{from_cstring, ChannelRef, IrcIdent, IrcIdentRef, UserString};
 use std::os::raw::c_char;
 
@@ -8,6 +9,23 @@ pub trait ServerEvent {
     const NAME: &'static str;
     #[doc(hidden)]
     unsafe fn create(word: *mut *mut c_char, word_eol: *mut *mut c_char) -> Self;
+    /// Creates the event from a parsed message, as though it had been received from the server.
+    ///
+    /// Returns `None` if the message is a different event.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the message is missing any of the event's parameters.
+    fn from_message(message: &Message) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        if !message.get_command().eq_ignore_ascii_case(Self::NAME) {
+            return None;
+        }
+        let (mut word, mut word_eol) = message.to_words();
+        unsafe { Some(Self::create(word.ptr(), word_eol.ptr())) }
+    }
 }
 
 /// A `ServerEvent` corresponding to `PRIVMSG`.
@@ -45,7 +63,8 @@ impl PRIVMSG {
     pub fn get_target(&self) -> &PrivmsgTarget {
         &self.target
     }
-    /// Gets the message that was sent.
+    /// Gets the message that was sent. CTCP messages are included as-is, `\x01`s and all; see
+    /// `Ctcp::parse` or `add_ctcp_listener`.
     pub fn get_message(&self) -> &str {
         &self.message
     }
@@ -468,3 +487,276 @@ impl ServerEvent for WALLOPS {
         }
     }
 }
+
+/// A `ServerEvent` corresponding to `NICK`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NICK {
+    user: UserString,
+    new_nick: IrcIdent,
+}
+
+impl NICK {
+    /// Gets the user who changed their nick, under their old nick.
+    pub fn get_user(&self) -> &UserString {
+        &self.user
+    }
+    /// Gets the user's new nick.
+    pub fn get_new_nick(&self) -> IrcIdentRef {
+        self.new_nick.as_ref()
+    }
+}
+
+impl ServerEvent for NICK {
+    const NAME: &'static str = "NICK";
+    unsafe fn create(word: *mut *mut c_char, _word_eol: *mut *mut c_char) -> Self {
+        let arg1 = *word.offset(1);
+        let user_string = from_cstring(arg1.offset(1));
+        let user = UserString::new(user_string).unwrap();
+        let arg3 = from_cstring(*word.offset(3));
+        let new_nick = if arg3.starts_with(':') {
+            IrcIdent(arg3[1..].to_string())
+        } else {
+            IrcIdent(arg3)
+        };
+        Self { user, new_nick }
+    }
+}
+
+/// A `ServerEvent` corresponding to `MODE`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MODE {
+    sender: Prefix,
+    target: ModeTarget,
+    changes: Vec<ModeChange>,
+}
+
+/// An enumeration of the possible targets for a `MODE`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ModeTarget {
+    /// A user's modes were changed.
+    User(IrcIdent),
+    /// A channel's modes were changed.
+    Channel {
+        /// The name of the channel whose modes were changed.
+        channel_name: IrcIdent,
+        /// The channel whose modes were changed.
+        channel: ChannelRef,
+    },
+}
+
+/// A single mode being set or unset by a `MODE`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ModeChange {
+    set: bool,
+    mode: char,
+    param: Option<String>,
+}
+
+impl ModeChange {
+    /// Gets whether the mode was set (`+`), as opposed to unset (`-`).
+    pub fn is_set(&self) -> bool {
+        self.set
+    }
+    /// Gets the mode character, e.g. `o` for `+o`.
+    pub fn get_mode(&self) -> char {
+        self.mode
+    }
+    /// Gets the mode's parameter, e.g. the nick for `+o`, or `None` if it doesn't take one.
+    pub fn get_param(&self) -> Option<&str> {
+        self.param.as_ref().map(|s| &**s)
+    }
+}
+
+impl MODE {
+    /// Gets who changed the modes. This may be a server, or a user's bare nick, rather than a full
+    /// userstring.
+    pub fn get_sender(&self) -> &Prefix {
+        &self.sender
+    }
+    /// Gets the target whose modes were changed.
+    pub fn get_target(&self) -> &ModeTarget {
+        &self.target
+    }
+    /// Gets the modes that were changed, in order. Which channel modes take a parameter is decided
+    /// by the server's `ServerFeatures`.
+    ///
+    /// If the server was already connected when the plugin was loaded, its features are unknown,
+    /// and the RFC 2811 modes are assumed instead. Modes the server added will then be read as
+    /// taking no parameter, and the parameters after them will be matched to the wrong modes.
+    pub fn get_changes(&self) -> &[ModeChange] {
+        &self.changes
+    }
+}
+
+/// Splits a line's parameters, only the last of which may start with a `:` and contain spaces.
+fn split_params(mut rest: &str) -> Vec<String> {
+    let mut params = Vec::new();
+    while !rest.is_empty() {
+        if rest.starts_with(':') {
+            params.push(rest[1..].to_string());
+            break;
+        }
+        let (param, after) = split_token(rest);
+        params.push(param.to_string());
+        rest = after;
+    }
+    params
+}
+
+fn parse_mode_changes(
+    modes: &str,
+    params: &mut dyn Iterator<Item = String>,
+    takes_param: impl Fn(char, bool) -> bool,
+) -> Vec<ModeChange> {
+    let mut set = true;
+    let mut changes = Vec::new();
+    for mode in modes.chars() {
+        match mode {
+            '+' => set = true,
+            '-' => set = false,
+            _ => changes.push(ModeChange {
+                set,
+                mode,
+                param: if takes_param(mode, set) {
+                    params.next()
+                } else {
+                    None
+                },
+            }),
+        }
+    }
+    changes
+}
+
+impl ServerEvent for MODE {
+    const NAME: &'static str = "MODE";
+    unsafe fn create(word: *mut *mut c_char, word_eol: *mut *mut c_char) -> Self {
+        let arg1 = *word.offset(1);
+        let sender = Prefix::new(from_cstring(arg1.offset(1)));
+        let target_string = IrcIdent(from_cstring(*word.offset(3)));
+        let arg4_eol = from_cstring(*word_eol.offset(4));
+        let mut args = split_params(&arg4_eol).into_iter();
+        let modes = args.next().unwrap_or_default();
+        let features = crate::get_server_features().unwrap_or_default();
+        let (target, changes) = if features.is_channel(&target_string) {
+            let channel = crate::get_server_name()
+                .and_then(|s| crate::get_channel(&s, &target_string))
+                .unwrap_or_else(|| crate::get_first_channel(&target_string).unwrap());
+            let changes = parse_mode_changes(&modes, &mut args, |mode, set| {
+                features.mode_takes_param(mode, set)
+            });
+            let target = ModeTarget::Channel {
+                channel_name: target_string,
+                channel,
+            };
+            (target, changes)
+        } else {
+            let changes = parse_mode_changes(&modes, &mut args, |_, _| false);
+            (ModeTarget::User(target_string), changes)
+        };
+        Self {
+            sender,
+            target,
+            changes,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::MockHexchat;
+
+    fn event<T: ServerEvent>(line: &str) -> T {
+        T::from_message(&line.parse().unwrap()).unwrap()
+    }
+
+    fn change(set: bool, mode: char, param: Option<&str>) -> ModeChange {
+        ModeChange {
+            set,
+            mode,
+            param: param.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn nick() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let nick: NICK = event(":old!user@host.example.com NICK :new");
+        assert_eq!(nick.get_user().get_nick().0, "old");
+        assert_eq!(nick.get_new_nick().0, "new");
+        let nick: NICK = event(":old!user@host.example.com NICK new");
+        assert_eq!(nick.get_new_nick().0, "new");
+    }
+
+    #[test]
+    fn user_mode() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let mode: MODE = event(":tester MODE tester :+iw-x");
+        assert_eq!(
+            mode.get_sender(),
+            &Prefix::Nick(IrcIdent("tester".to_string()))
+        );
+        assert_eq!(
+            mode.get_target(),
+            &ModeTarget::User(IrcIdent("tester".to_string()))
+        );
+        assert_eq!(
+            mode.get_changes(),
+            &[
+                change(true, 'i', None),
+                change(true, 'w', None),
+                change(false, 'x', None)
+            ]
+        );
+    }
+
+    #[test]
+    fn channel_mode() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let mode: MODE = event(":op!user@host.example.com MODE #test +ol-v nick 10 :other");
+        match mode.get_target() {
+            ModeTarget::Channel { channel_name, .. } => assert_eq!(channel_name.0, "#test"),
+            target => panic!("wrong target {:?}", target),
+        }
+        assert_eq!(
+            mode.get_changes(),
+            &[
+                change(true, 'o', Some("nick")),
+                change(true, 'l', Some("10")),
+                change(false, 'v', Some("other"))
+            ]
+        );
+        let mode: MODE = event(":op!user@host.example.com MODE #test -l+k :::key");
+        assert_eq!(
+            mode.get_changes(),
+            &[change(false, 'l', None), change(true, 'k', Some("::key"))]
+        );
+        let mode: MODE = event(":op!user@host.example.com MODE #test +lh 10 nick");
+        assert_eq!(
+            mode.get_changes(),
+            &[change(true, 'l', Some("10")), change(true, 'h', None)]
+        );
+    }
+
+    #[test]
+    fn prefix_modes() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        mock.inject_server_line(
+            ":irc.example.com 005 tester PREFIX=(qaohv)~&@%+ CHANMODES=b,k,l,imnt :are supported",
+        );
+        let mode: MODE = event(":op!user@host.example.com MODE #test +qh-a one two three");
+        assert_eq!(
+            mode.get_changes(),
+            &[
+                change(true, 'q', Some("one")),
+                change(true, 'h', Some("two")),
+                change(false, 'a', Some("three"))
+            ]
+        );
+    }
+}
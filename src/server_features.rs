@@ -0,0 +1,374 @@
+use crate::reply::RPL_ISUPPORT;
+use crate::{
+    add_raw_server_event_listener, add_reply_listener, remove_raw_server_event_listener,
+    remove_reply_listener, EatMode, Priority, RawServerEventListener, ReplyListener,
+};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+
+static FEATURES: Mutex<Option<HashMap<i32, ServerFeatures>>> = Mutex::new(None);
+static LISTENERS: Mutex<Option<(RawServerEventListener, ReplyListener)>> = Mutex::new(None);
+
+/// The features a server has advertised with `RPL_ISUPPORT` (`005`).
+///
+/// Each server's features are collected automatically as it sends them, and can be retrieved with
+/// `get_server_features`. Features the server hasn't advertised fall back to the defaults given in
+/// each getter, which are also what a `ServerFeatures::default()` returns.
+///
+/// Servers only send `RPL_ISUPPORT` while connecting, so if your plugin is loaded into a session
+/// that is already connected, nothing is known about those servers until they reconnect. Anything
+/// that relies on a server's features, such as `MODE::get_changes` and `whois`, uses the defaults
+/// for them in the meantime.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ServerFeatures {
+    tokens: HashMap<String, String>,
+}
+
+/// How a server compares nicks and channel names, as advertised by `CASEMAPPING`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum CaseMapping {
+    /// Only `A-Z` are folded to `a-z`.
+    Ascii,
+    /// `A-Z` are folded to `a-z`, and `[]\^` to `{}|~`.
+    Rfc1459,
+    /// `A-Z` are folded to `a-z`, and `[]\` to `{}|`.
+    StrictRfc1459,
+    /// Any other case mapping. Folded like `Ascii`.
+    Other(String),
+}
+
+impl CaseMapping {
+    /// Folds a string to lowercase according to this case mapping.
+    pub fn to_lower(&self, string: &str) -> String {
+        string
+            .chars()
+            .map(|ch| match (self, ch) {
+                (CaseMapping::Rfc1459, '^')
+                | (CaseMapping::Rfc1459, '[')
+                | (CaseMapping::Rfc1459, ']')
+                | (CaseMapping::Rfc1459, '\\')
+                | (CaseMapping::StrictRfc1459, '[')
+                | (CaseMapping::StrictRfc1459, ']')
+                | (CaseMapping::StrictRfc1459, '\\') => (ch as u8 + 32) as char,
+                _ => ch.to_ascii_lowercase(),
+            })
+            .collect()
+    }
+    /// Gets whether two strings are equal under this case mapping.
+    pub fn equal_ignore_case(&self, a: &str, b: &str) -> bool {
+        self.to_lower(a) == self.to_lower(b)
+    }
+}
+
+/// The channel modes a server supports, as advertised by `CHANMODES`, sorted by how they take
+/// parameters. Nick prefix modes, such as `o`, are not included; see `get_prefix`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ChannelModes {
+    list: String,
+    always: String,
+    when_set: String,
+    never: String,
+}
+
+impl ChannelModes {
+    /// Gets the modes that add or remove an entry from a list, such as bans. These always take a
+    /// parameter.
+    pub fn get_list_modes(&self) -> &str {
+        &self.list
+    }
+    /// Gets the modes that always take a parameter, such as the channel key.
+    pub fn get_param_modes(&self) -> &str {
+        &self.always
+    }
+    /// Gets the modes that only take a parameter when set, such as the user limit.
+    pub fn get_set_param_modes(&self) -> &str {
+        &self.when_set
+    }
+    /// Gets the modes that never take a parameter.
+    pub fn get_flag_modes(&self) -> &str {
+        &self.never
+    }
+}
+
+impl ServerFeatures {
+    fn update(&mut self, reply: &RPL_ISUPPORT) {
+        for token in reply.tokens() {
+            let name = token.name().to_uppercase();
+            if token.is_negated() {
+                self.tokens.remove(&name);
+            } else {
+                self.tokens
+                    .insert(name, token.value().unwrap_or_default().to_string());
+            }
+        }
+    }
+    /// Gets the raw value of a feature, or `None` if the server hasn't advertised it. Names are
+    /// case-insensitive, and features without a value have an empty one.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.tokens.get(&name.to_uppercase()).map(String::as_str)
+    }
+    /// Gets whether the server has advertised a feature.
+    pub fn supports(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+    /// Gets the nick prefix modes and their prefixes, from highest to lowest, as advertised by
+    /// `PREFIX`. Defaults to `o` (`@`) and `v` (`+`).
+    pub fn get_prefix(&self) -> Vec<(char, char)> {
+        let prefix = self.get("PREFIX").unwrap_or("(ov)@+");
+        if !prefix.starts_with('(') {
+            return Vec::new();
+        }
+        let mut split = prefix[1..].splitn(2, ')');
+        let modes = split.next().unwrap();
+        let prefixes = split.next().unwrap_or_default();
+        modes.chars().zip(prefixes.chars()).collect()
+    }
+    /// Gets the channel modes, as advertised by `CHANMODES`. Defaults to those of RFC 2811,
+    /// `beI,k,l,imnpst`.
+    pub fn get_chanmodes(&self) -> ChannelModes {
+        let mut split = self
+            .get("CHANMODES")
+            .unwrap_or("beI,k,l,imnpst")
+            .split(',')
+            .map(str::to_string);
+        ChannelModes {
+            list: split.next().unwrap_or_default(),
+            always: split.next().unwrap_or_default(),
+            when_set: split.next().unwrap_or_default(),
+            never: split.next().unwrap_or_default(),
+        }
+    }
+    /// Gets whether a channel mode takes a parameter when set, or when unset.
+    pub fn mode_takes_param(&self, mode: char, set: bool) -> bool {
+        let chanmodes = self.get_chanmodes();
+        self.get_prefix().iter().any(|(m, _)| *m == mode)
+            || chanmodes.list.contains(mode)
+            || chanmodes.always.contains(mode)
+            || (set && chanmodes.when_set.contains(mode))
+    }
+    /// Gets the case mapping, as advertised by `CASEMAPPING`. Defaults to `Rfc1459`.
+    pub fn get_casemapping(&self) -> CaseMapping {
+        match self.get("CASEMAPPING") {
+            None | Some("rfc1459") => CaseMapping::Rfc1459,
+            Some("ascii") => CaseMapping::Ascii,
+            Some("strict-rfc1459") => CaseMapping::StrictRfc1459,
+            Some(other) => CaseMapping::Other(other.to_string()),
+        }
+    }
+    /// Gets the channel name prefixes, as advertised by `CHANTYPES`. Defaults to those of RFC
+    /// 2811, `#&+!`.
+    pub fn get_chantypes(&self) -> &str {
+        self.get("CHANTYPES").unwrap_or("#&+!")
+    }
+    /// Gets whether a name is a channel name, going by `get_chantypes`.
+    pub fn is_channel(&self, name: &str) -> bool {
+        name.chars()
+            .next()
+            .map_or(false, |c| self.get_chantypes().contains(c))
+    }
+    /// Gets the maximum length of a nick, as advertised by `NICKLEN`, or `None` if unknown.
+    pub fn get_nicklen(&self) -> Option<usize> {
+        self.get("NICKLEN").and_then(|n| n.parse().ok())
+    }
+    /// Gets the maximum number of targets a command accepts, as advertised by `TARGMAX`, or
+    /// `None` if there is no limit or it is unknown.
+    pub fn get_targmax(&self, command: &str) -> Option<usize> {
+        self.get("TARGMAX")?
+            .split(',')
+            .filter_map(|t| {
+                let mut split = t.splitn(2, ':');
+                Some((split.next()?, split.next()?))
+            })
+            .find(|(c, _)| c.eq_ignore_ascii_case(command))
+            .and_then(|(_, n)| n.parse().ok())
+    }
+    /// Gets the maximum number of modes with parameters that can be changed in a single `MODE`,
+    /// as advertised by `MODES`, or `None` if there is no limit. Defaults to 3.
+    pub fn get_modes(&self) -> Option<usize> {
+        match self.get("MODES") {
+            None => Some(3),
+            Some(modes) => modes.parse().ok(),
+        }
+    }
+    /// Gets the name of the network, as advertised by `NETWORK`, or `None` if unknown.
+    pub fn get_network(&self) -> Option<&str> {
+        self.get("NETWORK").filter(|n| !n.is_empty())
+    }
+    /// Gets the ban exception mode, as advertised by `EXCEPTS`, or `None` if unsupported.
+    pub fn get_excepts(&self) -> Option<char> {
+        self.get("EXCEPTS").map(|e| e.chars().next().unwrap_or('e'))
+    }
+    /// Gets the invite exception mode, as advertised by `INVEX`, or `None` if unsupported.
+    pub fn get_invex(&self) -> Option<char> {
+        self.get("INVEX").map(|i| i.chars().next().unwrap_or('I'))
+    }
+}
+
+/// Gets the features advertised by the current server, or `None` if it hasn't advertised any
+/// since the plugin was loaded.
+///
+/// In particular, this is `None` for any server that was already connected when the plugin was
+/// loaded, until it reconnects. See `ServerFeatures` for more details.
+pub fn get_server_features() -> Option<ServerFeatures> {
+    get_server_features_by_id(crate::get_server_id()?)
+}
+
+/// Gets the features advertised by the server with the specified ID, as returned by
+/// `get_server_id`, or `None` if it hasn't advertised any since the plugin was loaded.
+///
+/// Unlike most of this crate's functions, this may be called from any thread.
+pub fn get_server_features_by_id(server_id: i32) -> Option<ServerFeatures> {
+    FEATURES
+        .lock()
+        .as_ref()
+        .and_then(|map| map.get(&server_id))
+        .cloned()
+}
+
+/// Registers the listeners that fill in each server's features.
+pub(crate) fn init() {
+    // A new connection starts over with whatever the server advertises next.
+    let welcome = add_raw_server_event_listener("001", Priority::HIGHEST, |_, _| {
+        if let (Some(id), Some(map)) = (crate::get_server_id(), FEATURES.lock().as_mut()) {
+            map.remove(&id);
+        }
+        EatMode::None
+    });
+    let isupport = add_reply_listener(Priority::HIGHEST, |reply: RPL_ISUPPORT, _| {
+        if let Some(id) = crate::get_server_id() {
+            FEATURES
+                .lock()
+                .get_or_insert_with(HashMap::new)
+                .entry(id)
+                .or_insert_with(ServerFeatures::default)
+                .update(&reply);
+        }
+        EatMode::None
+    });
+    *LISTENERS.lock() = Some((welcome, isupport));
+}
+
+/// Gets whether a hook is one of the listeners registered by `init`.
+#[cfg(any(feature = "diagnostics", feature = "testing"))]
+pub(crate) fn is_internal(hook: *mut crate::c::hexchat_hook) -> bool {
+    LISTENERS
+        .lock()
+        .as_ref()
+        .map_or(false, |(welcome, isupport)| {
+            hook == welcome.0 || hook == isupport.0
+        })
+}
+
+pub(crate) fn shutdown() {
+    let listeners = LISTENERS.lock().take();
+    if let Some((welcome, isupport)) = listeners {
+        remove_raw_server_event_listener(welcome);
+        remove_reply_listener(isupport);
+    }
+    let features = FEATURES.lock().take();
+    drop(features);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reply::ServerReply;
+
+    fn features(tokens: &str) -> ServerFeatures {
+        let line = format!(":irc.example.com 005 me {} :are supported", tokens);
+        let reply = RPL_ISUPPORT::from_message(&line.parse().unwrap()).unwrap();
+        let mut features = ServerFeatures::default();
+        features.update(&reply);
+        features
+    }
+
+    #[test]
+    fn casemapping() {
+        let string = "Nick[]\\^~{}|";
+        assert_eq!(CaseMapping::Ascii.to_lower(string), "nick[]\\^~{}|");
+        assert_eq!(CaseMapping::Rfc1459.to_lower(string), "nick{}|~~{}|");
+        assert_eq!(CaseMapping::StrictRfc1459.to_lower(string), "nick{}|^~{}|");
+        assert_eq!(
+            CaseMapping::Other("rfc7613".to_string()).to_lower(string),
+            "nick[]\\^~{}|"
+        );
+        assert!(CaseMapping::Rfc1459.equal_ignore_case("[Nick]", "{nick}"));
+        assert!(!CaseMapping::Ascii.equal_ignore_case("[Nick]", "{nick}"));
+        assert_eq!(features("").get_casemapping(), CaseMapping::Rfc1459);
+        assert_eq!(
+            features("CASEMAPPING=ascii").get_casemapping(),
+            CaseMapping::Ascii
+        );
+    }
+
+    #[test]
+    fn prefix() {
+        assert_eq!(features("").get_prefix(), [('o', '@'), ('v', '+')]);
+        assert_eq!(
+            features("PREFIX=(qaohv)~&@%+").get_prefix(),
+            [('q', '~'), ('a', '&'), ('o', '@'), ('h', '%'), ('v', '+')]
+        );
+        assert!(features("PREFIX=").get_prefix().is_empty());
+        assert!(features("PREFIX").get_prefix().is_empty());
+    }
+
+    #[test]
+    fn chanmodes() {
+        let chanmodes = features("").get_chanmodes();
+        assert_eq!(chanmodes.get_list_modes(), "beI");
+        assert_eq!(chanmodes.get_param_modes(), "k");
+        assert_eq!(chanmodes.get_set_param_modes(), "l");
+        assert_eq!(chanmodes.get_flag_modes(), "imnpst");
+        let chanmodes = features("CHANMODES=beIq,k,fjl,CMnt,XYZ").get_chanmodes();
+        assert_eq!(chanmodes.get_list_modes(), "beIq");
+        assert_eq!(chanmodes.get_set_param_modes(), "fjl");
+        assert_eq!(chanmodes.get_flag_modes(), "CMnt");
+        let chanmodes = features("CHANMODES=b,k").get_chanmodes();
+        assert_eq!(chanmodes.get_set_param_modes(), "");
+        assert_eq!(chanmodes.get_flag_modes(), "");
+    }
+
+    #[test]
+    fn mode_takes_param() {
+        let features = features("PREFIX=(ohv)@%+ CHANMODES=b,k,l,imnt");
+        for &(mode, set, takes_param) in &[
+            ('h', true, true),
+            ('h', false, true),
+            ('b', false, true),
+            ('k', false, true),
+            ('l', true, true),
+            ('l', false, false),
+            ('m', true, false),
+            ('x', true, false),
+        ] {
+            assert_eq!(
+                features.mode_takes_param(mode, set),
+                takes_param,
+                "{}{}",
+                if set { '+' } else { '-' },
+                mode
+            );
+        }
+    }
+
+    #[test]
+    fn targmax() {
+        let features = features("TARGMAX=PRIVMSG:4,NOTICE:3,JOIN:,WHOIS:1");
+        assert_eq!(features.get_targmax("PRIVMSG"), Some(4));
+        assert_eq!(features.get_targmax("notice"), Some(3));
+        assert_eq!(features.get_targmax("JOIN"), None);
+        assert_eq!(features.get_targmax("KICK"), None);
+        assert_eq!(ServerFeatures::default().get_targmax("PRIVMSG"), None);
+    }
+
+    #[test]
+    fn negated() {
+        let mut features = features("EXCEPTS INVEX=J");
+        assert_eq!(features.get_excepts(), Some('e'));
+        assert_eq!(features.get_invex(), Some('J'));
+        let line = ":irc.example.com 005 me -EXCEPTS :are supported";
+        features.update(&RPL_ISUPPORT::from_message(&line.parse().unwrap()).unwrap());
+        assert_eq!(features.get_excepts(), None);
+        assert!(features.supports("invex"));
+    }
+}
@@ -28,6 +28,8 @@ pub fn add_fake_plugin(
             ptr::null(),
         )
     };
+    #[cfg(feature = "diagnostics")]
+    crate::diagnostics::FAKE_PLUGINS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
     FakePlugin { handle }
 }
 /// Removes a fake plugin entry from the plugin list added by `add_fake_plugin`.
@@ -36,6 +38,8 @@ pub fn add_fake_plugin(
 /// this list.
 #[allow(clippy::needless_pass_by_value)]
 pub fn remove_fake_plugin(plugin: FakePlugin) {
+    #[cfg(feature = "diagnostics")]
+    crate::diagnostics::FAKE_PLUGINS.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
     unsafe { c!(hexchat_plugingui_remove, plugin.handle) }
 }
 
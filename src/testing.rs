@@ -0,0 +1,1568 @@
+//! A mock HexChat host for unit-testing plugins without running HexChat.
+//!
+//! Create a `MockHexchat`, load your plugin into it with `MockHexchat::load`, and then drive it by
+//! injecting server lines, print events, commands, and the passage of time. Everything the plugin
+//! prints or sends is recorded for inspection. Only one `MockHexchat` can exist at a time; creating
+//! another blocks until the first is dropped, so tests using it are effectively run one at a time.
+//!
+//! # Example
+//!
+//! ```rust
+//! use hexchat::testing::MockHexchat;
+//! use hexchat::{add_raw_server_event_listener, send_command, EatMode, Plugin, Priority};
+//!
+//! struct MyPlugin;
+//!
+//! impl Plugin for MyPlugin {
+//!     const NAME: &'static str = "myplugin";
+//!     fn new() -> Self {
+//!         add_raw_server_event_listener("PRIVMSG", Priority::NORMAL, |word, _| {
+//!             if word[3] == ":!ping" {
+//!                 send_command(&format!("MSG {} pong", word[2]));
+//!             }
+//!             EatMode::None
+//!         });
+//!         MyPlugin
+//!     }
+//! }
+//!
+//! let mut mock = MockHexchat::new();
+//! assert!(mock.load::<MyPlugin>());
+//! mock.inject_server_line(":nick!user@host.example.com PRIVMSG #test :!ping");
+//! assert_eq!(mock.commands(), ["MSG #test pong"]);
+//! ```
+
+use crate::irc::{split_words, WordArray};
+use crate::{c, ChannelRef, EatMode, FdFlags, GlobalPreferenceValue, Plugin};
+use libc::time_t;
+use parking_lot::{Mutex, MutexGuard};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{c_void, CStr, CString};
+use std::fs;
+use std::os::raw::{c_char, c_int};
+use std::process;
+use std::ptr;
+use std::time::Duration;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+type CommandCallback =
+    unsafe extern "C" fn(*mut *mut c_char, *mut *mut c_char, *mut c_void) -> c_int;
+type ServerAttrsCallback = unsafe extern "C" fn(
+    *mut *mut c_char,
+    *mut *mut c_char,
+    *mut c::hexchat_event_attrs,
+    *mut c_void,
+) -> c_int;
+type PrintCallback = unsafe extern "C" fn(*mut *mut c_char, *mut c_void) -> c_int;
+type PrintAttrsCallback =
+    unsafe extern "C" fn(*mut *mut c_char, *mut c::hexchat_event_attrs, *mut c_void) -> c_int;
+type TimerCallback = unsafe extern "C" fn(*mut c_void) -> c_int;
+type FdCallback = unsafe extern "C" fn(c_int, c_int, *mut c_void) -> c_int;
+
+#[derive(Copy, Clone)]
+enum HookKind {
+    Command(CommandCallback),
+    Server(CommandCallback),
+    ServerAttrs(ServerAttrsCallback),
+    Print(PrintCallback),
+    PrintAttrs(PrintAttrsCallback),
+    Timer(TimerCallback),
+    Fd(FdCallback),
+}
+
+#[derive(Clone)]
+struct MockHook {
+    name: String,
+    priority: c_int,
+    kind: HookKind,
+    user_data: *mut c_void,
+    interval: u64,
+    due: u64,
+    fd: c_int,
+    flags: c_int,
+}
+
+/// A fake channel context for use with `MockHexchat`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MockContext {
+    /// The name of the server.
+    pub server: String,
+    /// The name of the channel. For server tabs, this is the server name.
+    pub channel: String,
+    /// The name of the network, if any.
+    pub network: Option<String>,
+    /// The client's nickname on this server.
+    pub nick: String,
+    /// The channel topic.
+    pub topic: String,
+    /// The channel mode string, if any.
+    pub modes: Option<String>,
+    /// The client's away reason, if away.
+    pub away: Option<String>,
+    /// The unique ID of the server.
+    pub server_id: i32,
+    /// The type of the context, as HexChat numbers them: 1 for a server, 2 for a channel, 3 for a
+    /// dialog, 4 for channel notices, and 5 for server notices.
+    pub context_type: i32,
+    /// The users in the channel.
+    pub users: Vec<MockUser>,
+}
+
+impl Default for MockContext {
+    fn default() -> Self {
+        Self {
+            server: "irc.example.com".to_string(),
+            channel: "#test".to_string(),
+            network: Some("ExampleNet".to_string()),
+            nick: "tester".to_string(),
+            topic: String::new(),
+            modes: None,
+            away: None,
+            server_id: 1,
+            context_type: 2,
+            users: Vec::new(),
+        }
+    }
+}
+
+/// A fake user in a `MockContext`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MockUser {
+    /// The user's nickname.
+    pub nick: String,
+    /// The user's `user@host` string.
+    pub host: String,
+    /// The user's account name, if logged in.
+    pub account: Option<String>,
+    /// The user's real name, if known.
+    pub real_name: Option<String>,
+    /// The user's prefix character, e.g. `@`, or `'\0'` if none.
+    pub prefix: char,
+    /// Whether the user is away.
+    pub away: bool,
+}
+
+/// A row in a fake list, for use with `MockHexchat::set_list`. Fields are named the same way
+/// HexChat names them, e.g. `"nick"` or `"flags"`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MockListEntry {
+    fields: HashMap<String, MockField>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum MockField {
+    String(String),
+    Int(i32),
+    Time(i64),
+    Context(usize),
+}
+
+impl MockListEntry {
+    /// Creates an empty list entry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sets a string field.
+    pub fn string(mut self, name: &str, value: &str) -> Self {
+        self.fields
+            .insert(name.to_string(), MockField::String(value.to_string()));
+        self
+    }
+    /// Sets an integer field.
+    pub fn int(mut self, name: &str, value: i32) -> Self {
+        self.fields.insert(name.to_string(), MockField::Int(value));
+        self
+    }
+    /// Sets a time field, in seconds since the Unix epoch.
+    pub fn time(mut self, name: &str, value: i64) -> Self {
+        self.fields.insert(name.to_string(), MockField::Time(value));
+        self
+    }
+}
+
+struct MockList {
+    rows: Vec<MockListEntry>,
+    position: Option<usize>,
+}
+
+#[derive(Default)]
+struct MockState {
+    hooks: HashMap<usize, MockHook>,
+    next_hook: usize,
+    contexts: Vec<MockContext>,
+    current: usize,
+    printed: Vec<(usize, String)>,
+    emitted: Vec<(usize, String, Vec<String>)>,
+    commands: Vec<String>,
+    lists: HashMap<String, Vec<MockListEntry>>,
+    info: HashMap<String, String>,
+    global_prefs: HashMap<String, GlobalPreferenceValue>,
+    plugin_prefs: HashMap<String, String>,
+    fake_plugins: Vec<Option<String>>,
+    time: u64,
+    strings: Vec<CString>,
+    field_lists: Vec<Vec<*const c_char>>,
+}
+
+impl MockState {
+    fn keep(&mut self, string: &str) -> *const c_char {
+        let string = crate::to_cstring(string);
+        let ptr = string.as_ptr();
+        self.strings.push(string);
+        ptr
+    }
+    fn keep_opt(&mut self, string: Option<String>) -> *const c_char {
+        string.map_or(ptr::null(), |s| self.keep(&s))
+    }
+    fn context(&self) -> &MockContext {
+        &self.contexts[self.current]
+    }
+    fn hooks_by(&self, filter: impl Fn(&MockHook) -> bool) -> Vec<(usize, MockHook)> {
+        let mut hooks = self
+            .hooks
+            .iter()
+            .filter(|(_, h)| filter(h))
+            .map(|(id, h)| (*id, h.clone()))
+            .collect::<Vec<_>>();
+        hooks.sort_by_key(|(id, h)| (-h.priority, *id));
+        hooks
+    }
+}
+
+#[repr(C)]
+struct MockHandle {
+    vtable: c::hexchat_plugin,
+    state: RefCell<MockState>,
+}
+
+/// A mock HexChat host. See the module documentation for more details.
+pub struct MockHexchat {
+    handle: Box<MockHandle>,
+    unload: Option<unsafe fn(*mut c::hexchat_plugin) -> c_int>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Default for MockHexchat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockHexchat {
+    /// Creates a new mock host, with a single channel context as defined by
+    /// `MockContext::default()`.
+    ///
+    /// HexChat's config directory is set to a directory under the system's temporary directory,
+    /// which is shared by all mock hosts in the same process.
+    pub fn new() -> Self {
+        let lock = LOCK.lock();
+        let mut state = MockState::default();
+        state.contexts.push(MockContext::default());
+        state.next_hook = 1;
+        let config_dir = env::temp_dir().join(format!("hexchat-mock-{}", process::id()));
+        fs::create_dir_all(&config_dir).ok();
+        let config_dir = config_dir.to_string_lossy();
+        for (key, value) in &[
+            ("configdir", &*config_dir),
+            ("libdirfs", "/usr/lib/hexchat/plugins"),
+            ("version", "2.14.2"),
+            ("charset", "UTF-8"),
+            ("host", "example.com"),
+            ("inputbox", ""),
+        ] {
+            state.info.insert(key.to_string(), value.to_string());
+        }
+        Self {
+            handle: Box::new(MockHandle {
+                vtable: VTABLE,
+                state: RefCell::new(state),
+            }),
+            unload: None,
+            _lock: lock,
+        }
+    }
+    fn ph(&self) -> *mut c::hexchat_plugin {
+        &*self.handle as *const MockHandle as *mut c::hexchat_plugin
+    }
+    fn state(&self) -> &RefCell<MockState> {
+        &self.handle.state
+    }
+    /// Loads a plugin into the mock host, as `hexchat_plugin_init` would, with no load argument.
+    ///
+    /// Returns whether the plugin loaded successfully.
+    pub fn load<T>(&mut self) -> bool
+    where
+        T: Plugin + 'static,
+    {
+        self.load_with_arg::<T>(None)
+    }
+    /// Loads a plugin that registers nothing of its own, named `test`, as defined in `load`. This
+    /// is useful for testing code that only needs some plugin to be loaded.
+    pub fn load_default(&mut self) -> bool {
+        self.load::<DefaultPlugin>()
+    }
+    /// Loads a plugin into the mock host, as `hexchat_plugin_init` would, passing `arg` as the load
+    /// argument.
+    ///
+    /// Returns whether the plugin loaded successfully.
+    pub fn load_with_arg<T>(&mut self, arg: Option<&str>) -> bool
+    where
+        T: Plugin + 'static,
+    {
+        self.unload();
+        let mut name = ptr::null();
+        let mut desc = ptr::null();
+        let mut version = ptr::null();
+        let arg = arg.map(crate::to_cstring);
+        let arg_ptr = arg
+            .as_ref()
+            .map_or(ptr::null_mut(), |a| a.as_ptr() as *mut _);
+        let res = unsafe {
+            crate::call::hexchat_plugin_init::<T>(
+                self.ph(),
+                &mut name,
+                &mut desc,
+                &mut version,
+                arg_ptr,
+            )
+        };
+        unsafe {
+            for ptr in &[name, desc, version] {
+                if !ptr.is_null() {
+                    CString::from_raw(*ptr as *mut _);
+                }
+            }
+        }
+        // HexChat only treats 0 as a failure, and never deinitializes a plugin that failed to
+        // initialize; it is expected to have cleaned up after itself.
+        if res != 0 {
+            self.unload = Some(crate::call::hexchat_plugin_deinit::<T>);
+        }
+        res != 0
+    }
+    /// Unloads the currently loaded plugin, as `hexchat_plugin_deinit` would.
+    ///
+    /// Returns whether a plugin was loaded.
+    pub fn unload(&mut self) -> bool {
+        if let Some(unload) = self.unload.take() {
+            unsafe {
+                unload(self.ph());
+            }
+            true
+        } else {
+            false
+        }
+    }
+    /// Adds a fake channel context, returning a reference to it.
+    pub fn add_context(&mut self, context: MockContext) -> ChannelRef {
+        let mut state = self.state().borrow_mut();
+        state.contexts.push(context);
+        ChannelRef {
+            handle: state.contexts.len() as *mut _,
+        }
+    }
+    /// Gets a reference to the current context.
+    pub fn get_current_context(&self) -> ChannelRef {
+        ChannelRef {
+            handle: (self.state().borrow().current + 1) as *mut _,
+        }
+    }
+    /// Sets the current context, i.e. the one events are injected into and text is printed to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `context` was not created by this mock host.
+    pub fn set_current_context(&mut self, context: &ChannelRef) {
+        let mut state = self.state().borrow_mut();
+        let index = context.handle as usize - 1;
+        assert!(index < state.contexts.len(), "Invalid context");
+        state.current = index;
+    }
+    /// Modifies the data of a context.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `context` was not created by this mock host.
+    pub fn edit_context(&mut self, context: &ChannelRef, function: impl FnOnce(&mut MockContext)) {
+        let mut state = self.state().borrow_mut();
+        function(&mut state.contexts[context.handle as usize - 1]);
+    }
+    /// Sets the rows of a fake list, such as `"dcc"`, `"ignore"`, or `"notify"`. The `"channels"`
+    /// and `"users"` lists are generated from the contexts instead.
+    pub fn set_list(&mut self, name: &str, rows: Vec<MockListEntry>) {
+        self.state()
+            .borrow_mut()
+            .lists
+            .insert(name.to_string(), rows);
+    }
+    /// Sets the value returned by `get_info` for a particular ID, such as `"configdir"`.
+    pub fn set_info(&mut self, id: &str, value: &str) {
+        self.state()
+            .borrow_mut()
+            .info
+            .insert(id.to_string(), value.to_string());
+    }
+    /// Sets a global HexChat preference.
+    pub fn set_global_pref(&mut self, name: &str, value: GlobalPreferenceValue) {
+        self.state()
+            .borrow_mut()
+            .global_prefs
+            .insert(name.to_string(), value);
+    }
+    /// Gets all the plugin preferences that have been saved.
+    pub fn plugin_prefs(&self) -> HashMap<String, String> {
+        self.state().borrow().plugin_prefs.clone()
+    }
+    /// Gets every line of plain text that has been printed, in any context.
+    pub fn printed(&self) -> Vec<String> {
+        let state = self.state().borrow();
+        state.printed.iter().map(|(_, s)| s.clone()).collect()
+    }
+    /// Gets every line of plain text that has been printed to a particular context.
+    pub fn printed_in(&self, context: &ChannelRef) -> Vec<String> {
+        let state = self.state().borrow();
+        let index = context.handle as usize - 1;
+        state
+            .printed
+            .iter()
+            .filter(|(c, _)| *c == index)
+            .map(|(_, s)| s.clone())
+            .collect()
+    }
+    /// Gets every print event that has been emitted and not eaten from HexChat, along with its
+    /// arguments.
+    pub fn emitted(&self) -> Vec<(String, Vec<String>)> {
+        let state = self.state().borrow();
+        state
+            .emitted
+            .iter()
+            .map(|(_, e, a)| (e.clone(), a.clone()))
+            .collect()
+    }
+    /// Gets every command that has been executed, without the leading `/`.
+    pub fn commands(&self) -> Vec<String> {
+        self.state().borrow().commands.clone()
+    }
+    /// Clears all recorded printed lines, print events, and commands.
+    pub fn clear(&mut self) {
+        let mut state = self.state().borrow_mut();
+        state.printed.clear();
+        state.emitted.clear();
+        state.commands.clear();
+    }
+    /// Gets the number of hooks currently registered by the plugin.
+    pub fn hook_count(&self) -> usize {
+        self.state()
+            .borrow()
+            .hooks
+            .keys()
+            .filter(|id| !crate::call::is_internal_hook(**id as *mut _))
+            .count()
+    }
+    /// Runs a command as though the user had typed it, without the leading `/`.
+    ///
+    /// Returns who the command was hidden from.
+    pub fn run_command(&mut self, command: &str) -> EatMode {
+        unsafe { run_command(self.ph(), command) }
+    }
+    /// Injects a raw line from the server, such as `:nick!user@host PRIVMSG #test :hello`, into
+    /// the current context's server.
+    ///
+    /// Returns who the line was hidden from.
+    pub fn inject_server_line(&mut self, line: &str) -> EatMode {
+        self.inject_server_line_at(line, 0)
+    }
+    /// Injects a raw line from the server with a particular `server-time`, in seconds since the
+    /// Unix epoch.
+    ///
+    /// Returns who the line was hidden from.
+    pub fn inject_server_line_at(&mut self, line: &str, time: i64) -> EatMode {
+        let (words, word_eol) = split_words(line);
+        let name = words.get(1).cloned().unwrap_or_default();
+        let hooks = self.state().borrow().hooks_by(|h| match h.kind {
+            HookKind::Server(_) | HookKind::ServerAttrs(_) => {
+                h.name.eq_ignore_ascii_case(&name) || h.name.eq_ignore_ascii_case("RAW LINE")
+            }
+            _ => false,
+        });
+        let mut word = WordArray::new(&words);
+        let mut word_eol = WordArray::new(&word_eol);
+        let mut attrs = c::hexchat_event_attrs {
+            server_time_utc: time as time_t,
+        };
+        self.dispatch(hooks, |kind, user_data| unsafe {
+            match kind {
+                HookKind::Server(f) => f(word.ptr(), word_eol.ptr(), user_data),
+                HookKind::ServerAttrs(f) => f(word.ptr(), word_eol.ptr(), &mut attrs, user_data),
+                _ => 0,
+            }
+        })
+    }
+    /// Emits a print event in the current context, as though HexChat had.
+    ///
+    /// Returns who the event was hidden from. If it was not hidden from HexChat, it is recorded as
+    /// emitted.
+    pub fn inject_print_event(&mut self, event: &str, args: &[&str]) -> EatMode {
+        self.inject_print_event_at(event, args, 0)
+    }
+    /// Emits a print event in the current context with a particular timestamp, in seconds since
+    /// the Unix epoch.
+    ///
+    /// Returns who the event was hidden from.
+    pub fn inject_print_event_at(&mut self, event: &str, args: &[&str], time: i64) -> EatMode {
+        let args = args.iter().map(ToString::to_string).collect::<Vec<_>>();
+        unsafe { emit_print(self.ph(), event, args, time) }
+    }
+    /// Advances the mock clock, firing every timer that comes due in order.
+    pub fn advance_time(&mut self, duration: Duration) {
+        let target = self.state().borrow().time + duration.as_millis() as u64;
+        loop {
+            let next = {
+                let state = self.state().borrow();
+                state
+                    .hooks
+                    .iter()
+                    .filter(|(_, h)| match h.kind {
+                        HookKind::Timer(_) => h.due <= target,
+                        _ => false,
+                    })
+                    .min_by_key(|(id, h)| (h.due, **id))
+                    .map(|(id, h)| (*id, h.clone()))
+            };
+            let (id, hook) = match next {
+                Some(next) => next,
+                None => break,
+            };
+            self.state().borrow_mut().time = hook.due;
+            if let Some(h) = self.state().borrow_mut().hooks.get_mut(&id) {
+                h.due += h.interval.max(1);
+            }
+            let res = match hook.kind {
+                HookKind::Timer(f) => unsafe { f(hook.user_data) },
+                _ => unreachable!(),
+            };
+            if res == 0 {
+                self.state().borrow_mut().hooks.remove(&id);
+            }
+        }
+        self.state().borrow_mut().time = target;
+    }
+    /// Fires every file descriptor watcher on `fd` that is watching for any of `flags`.
+    pub fn trigger_fd(&mut self, fd: i32, flags: FdFlags) {
+        self.trigger_fds_by(|h| h.fd == fd, flags);
+    }
+    /// Fires every file descriptor watcher that is watching for any of `flags`, regardless of its
+    /// file descriptor.
+    pub fn trigger_all_fds(&mut self, flags: FdFlags) {
+        self.trigger_fds_by(|_| true, flags);
+    }
+    fn trigger_fds_by(&mut self, filter: impl Fn(&MockHook) -> bool, flags: FdFlags) {
+        let hooks = self.state().borrow().hooks_by(|h| match h.kind {
+            HookKind::Fd(_) => filter(h) && h.flags & flags.bits() != 0,
+            _ => false,
+        });
+        for (id, hook) in hooks {
+            if !self.state().borrow().hooks.contains_key(&id) {
+                continue;
+            }
+            let res = match hook.kind {
+                HookKind::Fd(f) => unsafe { f(hook.fd, hook.flags & flags.bits(), hook.user_data) },
+                _ => unreachable!(),
+            };
+            if res == 0 {
+                self.state().borrow_mut().hooks.remove(&id);
+            }
+        }
+    }
+    fn dispatch(
+        &self,
+        hooks: Vec<(usize, MockHook)>,
+        mut call: impl FnMut(HookKind, *mut c_void) -> c_int,
+    ) -> EatMode {
+        dispatch(self.state(), hooks, |kind, user_data| call(kind, user_data))
+    }
+}
+
+struct DefaultPlugin;
+
+impl Plugin for DefaultPlugin {
+    const NAME: &'static str = "test";
+    fn new() -> Self {
+        DefaultPlugin
+    }
+}
+
+impl Drop for MockHexchat {
+    fn drop(&mut self) {
+        self.unload();
+    }
+}
+
+fn dispatch(
+    state: &RefCell<MockState>,
+    hooks: Vec<(usize, MockHook)>,
+    mut call: impl FnMut(HookKind, *mut c_void) -> c_int,
+) -> EatMode {
+    let mut eaten = 0;
+    for (id, hook) in hooks {
+        if !state.borrow().hooks.contains_key(&id) {
+            continue;
+        }
+        eaten |= call(hook.kind, hook.user_data);
+        if eaten & EatMode::Plugin as c_int != 0 {
+            break;
+        }
+    }
+    match eaten & EatMode::All as c_int {
+        0 => EatMode::None,
+        1 => EatMode::Hexchat,
+        2 => EatMode::Plugin,
+        _ => EatMode::All,
+    }
+}
+
+unsafe fn state<'a>(ph: *mut c::hexchat_plugin) -> &'a RefCell<MockState> {
+    &(*(ph as *mut MockHandle)).state
+}
+
+unsafe fn run_command(ph: *mut c::hexchat_plugin, command: &str) -> EatMode {
+    let state = state(ph);
+    state.borrow_mut().commands.push(command.to_string());
+    let (words, word_eol) = split_words(command);
+    let name = words.get(0).cloned().unwrap_or_default();
+    let hooks = state.borrow().hooks_by(|h| match h.kind {
+        HookKind::Command(_) => h.name.eq_ignore_ascii_case(&name),
+        _ => false,
+    });
+    let mut word = WordArray::new(&words);
+    let mut word_eol = WordArray::new(&word_eol);
+    dispatch(state, hooks, |kind, user_data| match kind {
+        HookKind::Command(f) => f(word.ptr(), word_eol.ptr(), user_data),
+        _ => 0,
+    })
+}
+
+unsafe fn emit_print(
+    ph: *mut c::hexchat_plugin,
+    event: &str,
+    args: Vec<String>,
+    time: i64,
+) -> EatMode {
+    let state = state(ph);
+    let hooks = state.borrow().hooks_by(|h| match h.kind {
+        HookKind::Print(_) | HookKind::PrintAttrs(_) => h.name == event,
+        _ => false,
+    });
+    let mut word = WordArray::new(&args);
+    let mut attrs = c::hexchat_event_attrs {
+        server_time_utc: time as time_t,
+    };
+    let eat = dispatch(state, hooks, |kind, user_data| match kind {
+        HookKind::Print(f) => f(word.ptr(), user_data),
+        HookKind::PrintAttrs(f) => f(word.ptr(), &mut attrs, user_data),
+        _ => 0,
+    });
+    if eat == EatMode::None || eat == EatMode::Plugin {
+        let mut state = state.borrow_mut();
+        let current = state.current;
+        state.emitted.push((current, event.to_string(), args));
+    }
+    eat
+}
+
+unsafe fn add_hook(
+    ph: *mut c::hexchat_plugin,
+    name: *const c_char,
+    priority: c_int,
+    kind: HookKind,
+    user_data: *mut c_void,
+) -> *mut c::hexchat_hook {
+    let mut state = state(ph).borrow_mut();
+    let id = state.next_hook;
+    state.next_hook += 1;
+    let name = crate::from_cstring_opt(name).unwrap_or_default();
+    state.hooks.insert(
+        id,
+        MockHook {
+            name,
+            priority,
+            kind,
+            user_data,
+            interval: 0,
+            due: 0,
+            fd: 0,
+            flags: 0,
+        },
+    );
+    id as *mut _
+}
+
+unsafe fn read_varargs(args: &mut std::ffi::VaList) -> Vec<String> {
+    let mut vec = Vec::new();
+    loop {
+        let arg = args.arg::<*const c_char>();
+        if arg.is_null() || vec.len() >= 31 {
+            break;
+        }
+        vec.push(crate::from_cstring(arg));
+    }
+    vec
+}
+
+unsafe extern "C" fn hook_command(
+    ph: *mut c::hexchat_plugin,
+    name: *const c_char,
+    pri: c_int,
+    callback: CommandCallback,
+    _help_text: *const c_char,
+    userdata: *mut c_void,
+) -> *mut c::hexchat_hook {
+    add_hook(ph, name, pri, HookKind::Command(callback), userdata)
+}
+
+unsafe extern "C" fn hook_server(
+    ph: *mut c::hexchat_plugin,
+    name: *const c_char,
+    pri: c_int,
+    callback: CommandCallback,
+    userdata: *mut c_void,
+) -> *mut c::hexchat_hook {
+    add_hook(ph, name, pri, HookKind::Server(callback), userdata)
+}
+
+unsafe extern "C" fn hook_print(
+    ph: *mut c::hexchat_plugin,
+    name: *const c_char,
+    pri: c_int,
+    callback: PrintCallback,
+    userdata: *mut c_void,
+) -> *mut c::hexchat_hook {
+    add_hook(ph, name, pri, HookKind::Print(callback), userdata)
+}
+
+unsafe extern "C" fn hook_timer(
+    ph: *mut c::hexchat_plugin,
+    timeout: c_int,
+    callback: TimerCallback,
+    userdata: *mut c_void,
+) -> *mut c::hexchat_hook {
+    let hook = add_hook(ph, ptr::null(), 0, HookKind::Timer(callback), userdata);
+    let mut state = state(ph).borrow_mut();
+    let time = state.time;
+    let hook_ref = state.hooks.get_mut(&(hook as usize)).unwrap();
+    hook_ref.interval = timeout.max(0) as u64;
+    hook_ref.due = time + hook_ref.interval;
+    hook
+}
+
+unsafe extern "C" fn hook_fd(
+    ph: *mut c::hexchat_plugin,
+    fd: c_int,
+    flags: c_int,
+    callback: FdCallback,
+    userdata: *mut c_void,
+) -> *mut c::hexchat_hook {
+    let hook = add_hook(ph, ptr::null(), 0, HookKind::Fd(callback), userdata);
+    let mut state = state(ph).borrow_mut();
+    let hook_ref = state.hooks.get_mut(&(hook as usize)).unwrap();
+    hook_ref.fd = fd;
+    hook_ref.flags = flags;
+    hook
+}
+
+unsafe extern "C" fn unhook(ph: *mut c::hexchat_plugin, hook: *mut c::hexchat_hook) -> *mut c_void {
+    state(ph)
+        .borrow_mut()
+        .hooks
+        .remove(&(hook as usize))
+        .map_or(ptr::null_mut(), |h| h.user_data)
+}
+
+unsafe extern "C" fn print(ph: *mut c::hexchat_plugin, text: *const c_char) {
+    let mut state = state(ph).borrow_mut();
+    let current = state.current;
+    state.printed.push((current, crate::from_cstring(text)));
+}
+
+unsafe extern "C" fn printf(ph: *mut c::hexchat_plugin, format: *const c_char, _args: ...) {
+    print(ph, format);
+}
+
+unsafe extern "C" fn command(ph: *mut c::hexchat_plugin, command: *const c_char) {
+    run_command(ph, &crate::from_cstring(command));
+}
+
+unsafe extern "C" fn commandf(ph: *mut c::hexchat_plugin, format: *const c_char, _args: ...) {
+    command(ph, format);
+}
+
+unsafe extern "C" fn nickcmp(
+    _ph: *mut c::hexchat_plugin,
+    s1: *const c_char,
+    s2: *const c_char,
+) -> c_int {
+    fn fold(c: u8) -> u8 {
+        match c {
+            b'[' => b'{',
+            b']' => b'}',
+            b'\\' => b'|',
+            b'~' => b'^',
+            c => c.to_ascii_lowercase(),
+        }
+    }
+    let s1 = CStr::from_ptr(s1).to_bytes().iter().map(|&c| fold(c));
+    let s2 = CStr::from_ptr(s2).to_bytes().iter().map(|&c| fold(c));
+    s1.cmp(s2) as c_int
+}
+
+unsafe extern "C" fn set_context(
+    ph: *mut c::hexchat_plugin,
+    ctx: *mut c::hexchat_context,
+) -> c_int {
+    let mut state = state(ph).borrow_mut();
+    let index = (ctx as usize).wrapping_sub(1);
+    if index < state.contexts.len() {
+        state.current = index;
+        1
+    } else {
+        0
+    }
+}
+
+unsafe extern "C" fn find_context(
+    ph: *mut c::hexchat_plugin,
+    servname: *const c_char,
+    channel: *const c_char,
+) -> *mut c::hexchat_context {
+    let state = state(ph).borrow();
+    let servname = crate::from_cstring_opt(servname);
+    let channel = crate::from_cstring_opt(channel);
+    if servname.is_none() && channel.is_none() {
+        return (state.current + 1) as *mut _;
+    }
+    state
+        .contexts
+        .iter()
+        .position(|c| {
+            servname
+                .as_ref()
+                .map_or(true, |s| c.server.eq_ignore_ascii_case(s))
+                && channel
+                    .as_ref()
+                    .map_or(true, |ch| c.channel.eq_ignore_ascii_case(ch))
+        })
+        .map_or(ptr::null_mut(), |i| (i + 1) as *mut _)
+}
+
+unsafe extern "C" fn get_context(ph: *mut c::hexchat_plugin) -> *mut c::hexchat_context {
+    (state(ph).borrow().current + 1) as *mut _
+}
+
+unsafe extern "C" fn get_info(ph: *mut c::hexchat_plugin, id: *const c_char) -> *const c_char {
+    let mut state = state(ph).borrow_mut();
+    let id = crate::from_cstring(id);
+    let context = state.context().clone();
+    let value = match &*id {
+        "away" => context.away,
+        "channel" => Some(context.channel),
+        "modes" => context.modes,
+        "network" => context.network,
+        "nick" => Some(context.nick),
+        "nickserv" => None,
+        "server" => Some(context.server),
+        "topic" => Some(context.topic),
+        _ if id.starts_with("event_text ") => Some(
+            state
+                .info
+                .get(&id)
+                .cloned()
+                .unwrap_or_else(|| "$1".to_string()),
+        ),
+        _ => state.info.get(&id).cloned(),
+    };
+    state.keep_opt(value)
+}
+
+unsafe extern "C" fn get_prefs(
+    ph: *mut c::hexchat_plugin,
+    name: *const c_char,
+    string: *mut *const c_char,
+    integer: *mut c_int,
+) -> c_int {
+    let mut state = state(ph).borrow_mut();
+    let name = crate::from_cstring(name);
+    match &*name {
+        "id" => {
+            *integer = state.context().server_id;
+            2
+        }
+        "state_cursor" => {
+            *integer = 0;
+            2
+        }
+        _ => match state.global_prefs.get(&name) {
+            Some(GlobalPreferenceValue::String(s)) => {
+                let s = s.clone();
+                *string = state.keep(&s);
+                1
+            }
+            Some(GlobalPreferenceValue::Int(i)) => {
+                *integer = *i;
+                2
+            }
+            Some(GlobalPreferenceValue::Bool(b)) => {
+                *integer = *b as c_int;
+                3
+            }
+            None => 0,
+        },
+    }
+}
+
+unsafe extern "C" fn list_get(
+    ph: *mut c::hexchat_plugin,
+    name: *const c_char,
+) -> *mut c::hexchat_list {
+    let state = state(ph).borrow();
+    let name = crate::from_cstring(name);
+    let rows = match &*name {
+        "channels" => state
+            .contexts
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let mut row = MockListEntry::new()
+                    .string("channel", &c.channel)
+                    .string("chanmodes", c.modes.as_ref().map_or("", |s| &**s))
+                    .string("chantypes", "#&")
+                    .int("flags", 1)
+                    .int("id", c.server_id)
+                    .int("maxmodes", 4)
+                    .string("network", c.network.as_ref().map_or("", |s| &**s))
+                    .string("nickprefixes", "@+")
+                    .string("nickmodes", "ov")
+                    .string("server", &c.server)
+                    .int("type", c.context_type)
+                    .int("users", c.users.len() as i32);
+                row.fields
+                    .insert("context".to_string(), MockField::Context(i));
+                row
+            })
+            .collect(),
+        "users" => state
+            .context()
+            .users
+            .iter()
+            .map(|u| {
+                let mut row = MockListEntry::new()
+                    .string("nick", &u.nick)
+                    .string("host", &u.host)
+                    .int("away", u.away as i32)
+                    .time("lasttalk", 0)
+                    .int("selected", 0);
+                if let Some(account) = &u.account {
+                    row = row.string("account", account);
+                }
+                if let Some(real_name) = &u.real_name {
+                    row = row.string("realname", real_name);
+                }
+                if u.prefix != '\0' {
+                    row = row.string("prefix", &u.prefix.to_string());
+                }
+                row
+            })
+            .collect(),
+        _ => match state.lists.get(&name) {
+            Some(rows) => rows.clone(),
+            None => return ptr::null_mut(),
+        },
+    };
+    Box::into_raw(Box::new(MockList {
+        rows,
+        position: None,
+    })) as *mut _
+}
+
+unsafe extern "C" fn list_free(_ph: *mut c::hexchat_plugin, xlist: *mut c::hexchat_list) {
+    if !xlist.is_null() {
+        Box::from_raw(xlist as *mut MockList);
+    }
+}
+
+unsafe extern "C" fn list_fields(
+    ph: *mut c::hexchat_plugin,
+    name: *const c_char,
+) -> *const *const c_char {
+    let mut state = state(ph).borrow_mut();
+    let name = crate::from_cstring(name);
+    let mut fields = state
+        .lists
+        .get(&name)
+        .and_then(|rows| rows.get(0))
+        .map(|row| row.fields.keys().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    fields.sort();
+    let mut ptrs = fields.iter().map(|f| state.keep(f)).collect::<Vec<_>>();
+    ptrs.push(ptr::null());
+    let ptr = ptrs.as_ptr();
+    state.field_lists.push(ptrs);
+    ptr
+}
+
+unsafe extern "C" fn list_next(_ph: *mut c::hexchat_plugin, xlist: *mut c::hexchat_list) -> c_int {
+    if xlist.is_null() {
+        return 0;
+    }
+    let list = &mut *(xlist as *mut MockList);
+    let next = list.position.map_or(0, |p| p + 1);
+    if next < list.rows.len() {
+        list.position = Some(next);
+        1
+    } else {
+        0
+    }
+}
+
+unsafe fn list_field(xlist: *mut c::hexchat_list, name: *const c_char) -> Option<MockField> {
+    if xlist.is_null() {
+        return None;
+    }
+    let list = &*(xlist as *mut MockList);
+    let row = list.rows.get(list.position?)?;
+    row.fields.get(&crate::from_cstring(name)).cloned()
+}
+
+unsafe extern "C" fn list_str(
+    ph: *mut c::hexchat_plugin,
+    xlist: *mut c::hexchat_list,
+    name: *const c_char,
+) -> *const c_char {
+    match list_field(xlist, name) {
+        Some(MockField::String(s)) => state(ph).borrow_mut().keep(&s),
+        Some(MockField::Context(i)) => (i + 1) as *const _,
+        _ => ptr::null(),
+    }
+}
+
+unsafe extern "C" fn list_int(
+    _ph: *mut c::hexchat_plugin,
+    xlist: *mut c::hexchat_list,
+    name: *const c_char,
+) -> c_int {
+    match list_field(xlist, name) {
+        Some(MockField::Int(i)) => i,
+        _ => -1,
+    }
+}
+
+unsafe extern "C" fn list_time(
+    _ph: *mut c::hexchat_plugin,
+    xlist: *mut c::hexchat_list,
+    name: *const c_char,
+) -> time_t {
+    match list_field(xlist, name) {
+        Some(MockField::Time(t)) => t as time_t,
+        _ => -1,
+    }
+}
+
+unsafe extern "C" fn plugingui_add(
+    ph: *mut c::hexchat_plugin,
+    _filename: *const c_char,
+    name: *const c_char,
+    _desc: *const c_char,
+    _version: *const c_char,
+    _reserved: *const c_char,
+) -> *mut c_void {
+    let mut state = state(ph).borrow_mut();
+    state.fake_plugins.push(Some(crate::from_cstring(name)));
+    state.fake_plugins.len() as *mut _
+}
+
+unsafe extern "C" fn plugingui_remove(ph: *mut c::hexchat_plugin, handle: *mut c_void) {
+    let mut state = state(ph).borrow_mut();
+    if let Some(entry) = state
+        .fake_plugins
+        .get_mut((handle as usize).wrapping_sub(1))
+    {
+        *entry = None;
+    }
+}
+
+unsafe extern "C" fn emit_print_variadic(
+    ph: *mut c::hexchat_plugin,
+    name: *const c_char,
+    mut args: ...
+) -> c_int {
+    let args = read_varargs(&mut args);
+    emit_print(ph, &crate::from_cstring(name), args, 0);
+    1
+}
+
+unsafe extern "C" fn read_fd(
+    _ph: *mut c::hexchat_plugin,
+    _src: *mut c_void,
+    _buf: *mut c_char,
+    _len: *mut c_int,
+) -> c_int {
+    0
+}
+
+unsafe extern "C" fn gettext(_ph: *mut c::hexchat_plugin, msgid: *const c_char) -> *mut c_char {
+    msgid as *mut _
+}
+
+unsafe extern "C" fn send_modes(
+    ph: *mut c::hexchat_plugin,
+    targets: *mut *const c_char,
+    ntargets: c_int,
+    _modes_per_line: c_int,
+    sign: c_char,
+    mode: c_char,
+) {
+    let targets = (0..ntargets as isize)
+        .map(|i| crate::from_cstring(*targets.offset(i)))
+        .collect::<Vec<_>>();
+    let modes = (mode as u8 as char).to_string().repeat(targets.len());
+    let channel = state(ph).borrow().context().channel.clone();
+    let command = format!(
+        "MODE {} {}{} {}",
+        channel,
+        sign as u8 as char,
+        modes,
+        targets.join(" ")
+    );
+    state(ph).borrow_mut().commands.push(command);
+}
+
+unsafe extern "C" fn strip(
+    _ph: *mut c::hexchat_plugin,
+    str: *const c_char,
+    len: c_int,
+    flags: c_int,
+) -> *mut c_char {
+    let bytes = if len < 0 {
+        CStr::from_ptr(str).to_bytes()
+    } else {
+        std::slice::from_raw_parts(str as *const u8, len as usize)
+    };
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            3 if flags & 1 != 0 => {
+                i += 1;
+                for _ in 0..2 {
+                    if i < bytes.len() && bytes[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+                if i + 1 < bytes.len() && bytes[i] == b',' && bytes[i + 1].is_ascii_digit() {
+                    i += 2;
+                    if i < bytes.len() && bytes[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+                continue;
+            }
+            2 | 0xf | 0x16 | 0x1d | 0x1f if flags & 2 != 0 => {}
+            0 => {}
+            c => out.push(c),
+        }
+        i += 1;
+    }
+    CString::new(out).unwrap().into_raw()
+}
+
+unsafe extern "C" fn free(_ph: *mut c::hexchat_plugin, ptr: *mut c_void) {
+    if !ptr.is_null() {
+        CString::from_raw(ptr as *mut c_char);
+    }
+}
+
+unsafe extern "C" fn pluginpref_set_str(
+    ph: *mut c::hexchat_plugin,
+    var: *const c_char,
+    value: *const c_char,
+) -> c_int {
+    state(ph)
+        .borrow_mut()
+        .plugin_prefs
+        .insert(crate::from_cstring(var), crate::from_cstring(value));
+    1
+}
+
+unsafe extern "C" fn pluginpref_get_str(
+    ph: *mut c::hexchat_plugin,
+    var: *const c_char,
+    dest: *mut c_char,
+) -> c_int {
+    match state(ph)
+        .borrow()
+        .plugin_prefs
+        .get(&crate::from_cstring(var))
+    {
+        Some(value) => {
+            let len = value.len().min(511);
+            ptr::copy_nonoverlapping(value.as_ptr() as *const c_char, dest, len);
+            *dest.add(len) = 0;
+            1
+        }
+        None => 0,
+    }
+}
+
+unsafe extern "C" fn pluginpref_set_int(
+    ph: *mut c::hexchat_plugin,
+    var: *const c_char,
+    value: c_int,
+) -> c_int {
+    state(ph)
+        .borrow_mut()
+        .plugin_prefs
+        .insert(crate::from_cstring(var), value.to_string());
+    1
+}
+
+unsafe extern "C" fn pluginpref_get_int(ph: *mut c::hexchat_plugin, var: *const c_char) -> c_int {
+    state(ph)
+        .borrow()
+        .plugin_prefs
+        .get(&crate::from_cstring(var))
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(-1)
+}
+
+unsafe extern "C" fn pluginpref_delete(ph: *mut c::hexchat_plugin, var: *const c_char) -> c_int {
+    state(ph)
+        .borrow_mut()
+        .plugin_prefs
+        .remove(&crate::from_cstring(var))
+        .is_some() as c_int
+}
+
+unsafe extern "C" fn pluginpref_list(ph: *mut c::hexchat_plugin, dest: *const c_char) -> c_int {
+    let state = state(ph).borrow();
+    let mut names = state.plugin_prefs.keys().cloned().collect::<Vec<_>>();
+    names.sort();
+    let list = names.join(",");
+    let len = list.len().min(4095);
+    ptr::copy_nonoverlapping(list.as_ptr() as *const c_char, dest as *mut c_char, len);
+    *(dest as *mut c_char).add(len) = 0;
+    1
+}
+
+unsafe extern "C" fn hook_server_attrs(
+    ph: *mut c::hexchat_plugin,
+    name: *const c_char,
+    pri: c_int,
+    callback: ServerAttrsCallback,
+    userdata: *mut c_void,
+) -> *mut c::hexchat_hook {
+    add_hook(ph, name, pri, HookKind::ServerAttrs(callback), userdata)
+}
+
+unsafe extern "C" fn hook_print_attrs(
+    ph: *mut c::hexchat_plugin,
+    name: *const c_char,
+    pri: c_int,
+    callback: PrintAttrsCallback,
+    userdata: *mut c_void,
+) -> *mut c::hexchat_hook {
+    add_hook(ph, name, pri, HookKind::PrintAttrs(callback), userdata)
+}
+
+unsafe extern "C" fn emit_print_attrs(
+    ph: *mut c::hexchat_plugin,
+    attrs: *mut c::hexchat_event_attrs,
+    event_name: *const c_char,
+    mut args: ...
+) -> c_int {
+    let args = read_varargs(&mut args);
+    let time = if attrs.is_null() {
+        0
+    } else {
+        (*attrs).server_time_utc as i64
+    };
+    emit_print(ph, &crate::from_cstring(event_name), args, time);
+    1
+}
+
+unsafe extern "C" fn event_attrs_create(
+    _ph: *mut c::hexchat_plugin,
+) -> *mut c::hexchat_event_attrs {
+    Box::into_raw(Box::new(c::hexchat_event_attrs { server_time_utc: 0 }))
+}
+
+unsafe extern "C" fn event_attrs_free(
+    _ph: *mut c::hexchat_plugin,
+    attrs: *mut c::hexchat_event_attrs,
+) {
+    if !attrs.is_null() {
+        Box::from_raw(attrs);
+    }
+}
+
+const VTABLE: c::hexchat_plugin = c::hexchat_plugin {
+    hexchat_hook_command: hook_command,
+    hexchat_hook_server: hook_server,
+    hexchat_hook_print: hook_print,
+    hexchat_hook_timer: hook_timer,
+    hexchat_hook_fd: hook_fd,
+    hexchat_unhook: unhook,
+    hexchat_print: print,
+    hexchat_printf: printf,
+    hexchat_command: command,
+    hexchat_commandf: commandf,
+    hexchat_nickcmp: nickcmp,
+    hexchat_set_context: set_context,
+    hexchat_find_context: find_context,
+    hexchat_get_context: get_context,
+    hexchat_get_info: get_info,
+    hexchat_get_prefs: get_prefs,
+    hexchat_list_get: list_get,
+    hexchat_list_free: list_free,
+    hexchat_list_fields: list_fields,
+    hexchat_list_next: list_next,
+    hexchat_list_str: list_str,
+    hexchat_list_int: list_int,
+    hexchat_plugingui_add: plugingui_add,
+    hexchat_plugingui_remove: plugingui_remove,
+    hexchat_emit_print: emit_print_variadic,
+    hexchat_read_fd: read_fd,
+    hexchat_list_time: list_time,
+    hexchat_gettext: gettext,
+    hexchat_send_modes: send_modes,
+    hexchat_strip: strip,
+    hexchat_free: free,
+    hexchat_pluginpref_set_str: pluginpref_set_str,
+    hexchat_pluginpref_get_str: pluginpref_get_str,
+    hexchat_pluginpref_set_int: pluginpref_set_int,
+    hexchat_pluginpref_get_int: pluginpref_get_int,
+    hexchat_pluginpref_delete: pluginpref_delete,
+    hexchat_pluginpref_list: pluginpref_list,
+    hexchat_hook_server_attrs: hook_server_attrs,
+    hexchat_hook_print_attrs: hook_print_attrs,
+    hexchat_emit_print_attrs: emit_print_attrs,
+    hexchat_event_attrs_create: event_attrs_create,
+    hexchat_event_attrs_free: event_attrs_free,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        add_print_event_listener, add_raw_server_event_listener, add_timer_task, get_all_channels,
+        get_ignore_entries, print_plain, register_command, send_command, ChannelType, IgnoreType,
+        PluginContext, PrintEvent, Priority,
+    };
+    use std::error::Error;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    struct GreeterPlugin;
+
+    impl Plugin for GreeterPlugin {
+        const NAME: &'static str = "test";
+        fn new() -> Self {
+            register_command("greet", "", Priority::NORMAL, |word| {
+                send_command(&format!("MSG {} hello", word[1]));
+                EatMode::All
+            });
+            add_raw_server_event_listener("PRIVMSG", Priority::NORMAL, |word, _| {
+                print_plain(&format!("{} said {}", word[0], word[3]));
+                EatMode::None
+            });
+            add_timer_task(Duration::from_secs(1), || print_plain("tick"));
+            add_print_event_listener(PrintEvent::JOIN, Priority::NORMAL, |_, _| EatMode::Hexchat);
+            GreeterPlugin
+        }
+    }
+
+    #[test]
+    fn load_and_unload() {
+        let mut mock = MockHexchat::new();
+        assert!(!mock.unload());
+        assert!(mock.load::<GreeterPlugin>());
+        // Let the timer that calls `on_loaded` fire.
+        mock.advance_time(Duration::from_millis(0));
+        assert_eq!(mock.hook_count(), 4);
+        assert!(mock.unload());
+        assert_eq!(mock.hook_count(), 0);
+        assert!(!mock.unload());
+    }
+
+    struct FailingPlugin;
+
+    impl Plugin for FailingPlugin {
+        const NAME: &'static str = "failing";
+        fn new() -> Self {
+            FailingPlugin
+        }
+        fn init(_: &PluginContext) -> Result<Self, Box<dyn Error>> {
+            register_command("greet", "", Priority::NORMAL, |_| EatMode::All);
+            add_timer_task(Duration::from_secs(1), || print_plain("tick"));
+            Err("failed".into())
+        }
+    }
+
+    #[test]
+    fn failed_load() {
+        let mut mock = MockHexchat::new();
+        assert!(!mock.load::<FailingPlugin>());
+        assert_eq!(mock.hook_count(), 0);
+        assert!(!mock.unload());
+        assert!(mock.load::<GreeterPlugin>());
+    }
+
+    struct LifecyclePlugin;
+
+    impl Plugin for LifecyclePlugin {
+        const NAME: &'static str = "lifecycle";
+        fn new() -> Self {
+            LifecyclePlugin
+        }
+        fn on_loaded(&mut self) {
+            print_plain("loaded");
+        }
+        fn on_unloading(&mut self) {
+            print_plain("unloading");
+        }
+    }
+
+    #[test]
+    fn lifecycle() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load::<LifecyclePlugin>());
+        assert!(mock.printed().is_empty());
+        mock.advance_time(Duration::from_millis(0));
+        assert_eq!(mock.printed(), ["loaded"]);
+        assert!(mock.unload());
+        assert_eq!(mock.printed()[..2], ["loaded", "unloading"]);
+    }
+
+    static SELF_UNLOADING_DROPPED: AtomicBool = AtomicBool::new(false);
+
+    struct SelfUnloadingPlugin;
+
+    impl Plugin for SelfUnloadingPlugin {
+        const NAME: &'static str = "self-unloading";
+        fn new() -> Self {
+            register_command("greet", "", Priority::NORMAL, |_| EatMode::All);
+            add_timer_task(Duration::from_secs(1), || print_plain("tick"));
+            SelfUnloadingPlugin
+        }
+        fn on_loaded(&mut self) {
+            // As HexChat does if `on_loaded` runs `/UNLOAD` on the plugin.
+            unsafe {
+                crate::call::hexchat_plugin_deinit::<Self>(ptr::null_mut());
+            }
+        }
+    }
+
+    impl Drop for SelfUnloadingPlugin {
+        fn drop(&mut self) {
+            SELF_UNLOADING_DROPPED.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn unload_in_on_loaded() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load::<SelfUnloadingPlugin>());
+        mock.advance_time(Duration::from_secs(2));
+        assert!(SELF_UNLOADING_DROPPED.load(Ordering::SeqCst));
+        assert!(!crate::call::is_loaded());
+        assert_eq!(mock.hook_count(), 0);
+        assert!(!mock.printed().contains(&"tick".to_string()));
+        assert!(mock.load::<GreeterPlugin>());
+    }
+
+    #[test]
+    fn server_lines() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load::<GreeterPlugin>());
+        let eat = mock.inject_server_line(":nick!user@host.example.com PRIVMSG #test :hi");
+        assert_eq!(eat, EatMode::None);
+        assert_eq!(mock.printed(), [":nick!user@host.example.com said :hi"]);
+        mock.inject_server_line(":nick!user@host.example.com NOTICE #test :hi");
+        assert_eq!(mock.printed().len(), 1);
+    }
+
+    #[test]
+    fn commands() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load::<GreeterPlugin>());
+        assert_eq!(mock.run_command("greet bob"), EatMode::All);
+        assert_eq!(mock.commands(), ["greet bob", "MSG bob hello"]);
+        mock.clear();
+        assert!(mock.commands().is_empty());
+    }
+
+    #[test]
+    fn timers() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load::<GreeterPlugin>());
+        mock.advance_time(Duration::from_millis(999));
+        assert!(mock.printed().is_empty());
+        mock.advance_time(Duration::from_millis(1501));
+        assert_eq!(mock.printed(), ["tick", "tick"]);
+        mock.unload();
+        mock.clear();
+        mock.advance_time(Duration::from_secs(5));
+        assert!(mock.printed().is_empty());
+    }
+
+    #[test]
+    fn print_events() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load::<GreeterPlugin>());
+        let eat = mock.inject_print_event("Join", &["bob", "#test", "bob@example.com"]);
+        assert_eq!(eat, EatMode::Hexchat);
+        assert!(mock.emitted().is_empty());
+        mock.inject_print_event("Part", &["bob", "bob@example.com", "#test"]);
+        assert_eq!(
+            mock.emitted(),
+            [(
+                "Part".to_string(),
+                vec![
+                    "bob".to_string(),
+                    "bob@example.com".to_string(),
+                    "#test".to_string()
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn lists() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        mock.set_list(
+            "ignore",
+            vec![
+                MockListEntry::new()
+                    .string("mask", "*!*@spam.example.com")
+                    .int("flags", (IgnoreType::CHANNEL | IgnoreType::CTCP).bits()),
+                MockListEntry::new()
+                    .string("mask", "troll!*@*")
+                    .int("flags", IgnoreType::PRIVATE.bits()),
+            ],
+        );
+        let entries = get_ignore_entries().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].get_mask(), "*!*@spam.example.com");
+        assert_eq!(
+            entries[0].get_ignore_type(),
+            IgnoreType::CHANNEL | IgnoreType::CTCP
+        );
+        assert_eq!(entries[1].get_mask(), "troll!*@*");
+        assert_eq!(entries[1].get_ignore_type(), IgnoreType::PRIVATE);
+        let channels = get_all_channels().collect::<Vec<_>>();
+        assert_eq!(channels.len(), 1);
+        assert_eq!(channels[0].get_name(), "#test");
+        assert_eq!(channels[0].get_type(), ChannelType::Channel);
+    }
+}
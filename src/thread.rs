@@ -0,0 +1,182 @@
+use crate::call;
+use parking_lot::{Condvar, Mutex};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread as std_thread;
+use std::time::{Duration, Instant};
+
+/// How long unloading waits for the plugin's threads to stop before giving up on them.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct State {
+    shutdown: bool,
+    finished: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    condvar: Condvar,
+}
+
+impl Shared {
+    fn signal(&self) {
+        self.state.lock().shutdown = true;
+        self.condvar.notify_all();
+    }
+    fn finish(&self) {
+        self.state.lock().finished = true;
+        self.condvar.notify_all();
+    }
+    fn wait_finished(&self, deadline: Option<Instant>) -> bool {
+        let mut state = self.state.lock();
+        while !state.finished {
+            match deadline {
+                Some(deadline) => {
+                    if self.condvar.wait_until(&mut state, deadline).timed_out() {
+                        return state.finished;
+                    }
+                }
+                None => self.condvar.wait(&mut state),
+            }
+        }
+        true
+    }
+}
+
+// Marks the thread as finished even if it panics.
+struct FinishGuard(Arc<Shared>);
+
+impl Drop for FinishGuard {
+    fn drop(&mut self) {
+        self.0.finish();
+    }
+}
+
+/// A token passed to a thread spawned with `spawn`, which tells it when to stop.
+#[derive(Clone)]
+pub struct ShutdownToken(Arc<Shared>);
+
+impl ShutdownToken {
+    /// Gets whether the thread has been asked to stop, either because the plugin is being unloaded
+    /// or because `JoinHandle::shutdown` was called.
+    pub fn is_shutdown(&self) -> bool {
+        self.0.state.lock().shutdown
+    }
+    /// Sleeps for the specified duration, waking up early if the thread is asked to stop.
+    ///
+    /// Returns whether the thread has been asked to stop.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        let mut state = self.0.state.lock();
+        while !state.shutdown {
+            if self.0.condvar.wait_until(&mut state, deadline).timed_out() {
+                break;
+            }
+        }
+        state.shutdown
+    }
+}
+
+/// A handle to a thread spawned with `spawn`.
+///
+/// Dropping the handle does not stop the thread; it keeps running until it returns or the plugin
+/// is unloaded.
+pub struct JoinHandle<T> {
+    shared: Arc<Shared>,
+    result: Arc<Mutex<Option<std_thread::Result<T>>>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Asks the thread to stop. The thread must itself check its `ShutdownToken` to notice.
+    pub fn shutdown(&self) {
+        self.shared.signal();
+    }
+    /// Gets whether the thread has finished running.
+    pub fn is_finished(&self) -> bool {
+        self.shared.state.lock().finished
+    }
+    /// Gets a `ShutdownToken` for the thread.
+    pub fn get_token(&self) -> ShutdownToken {
+        ShutdownToken(self.shared.clone())
+    }
+    /// Waits for the thread to finish, returning its result, or an error if it panicked.
+    pub fn join(self) -> std_thread::Result<T> {
+        self.shared.wait_finished(None);
+        self.result.lock().take().unwrap()
+    }
+}
+
+pub(crate) struct ThreadEntry {
+    shared: Arc<Shared>,
+    handle: std_thread::JoinHandle<()>,
+}
+
+impl ThreadEntry {
+    pub(crate) fn is_finished(&self) -> bool {
+        self.shared.state.lock().finished
+    }
+}
+
+/// Spawns a new thread, which is passed a `ShutdownToken` and returns a `JoinHandle` for it.
+///
+/// Unlike threads spawned with `std::thread::spawn`, these are tracked by the plugin. When it is
+/// unloaded, each one is asked to stop, and the plugin waits up to `SHUTDOWN_TIMEOUT` for them all
+/// to finish before deallocating any safe statics, so that they can be safely used from the thread
+/// as long as it checks its token regularly. Any that are still running after that are left
+/// behind, and a warning is printed. Since they may still be using them, the plugin's safe statics
+/// and its other state are then leaked instead of being deallocated.
+///
+/// Like the rest of this crate's functions, this must be called from the main thread.
+///
+/// # Panics
+///
+/// Panics if the OS fails to create a thread.
+pub fn spawn<T>(function: impl FnOnce(ShutdownToken) -> T + Send + 'static) -> JoinHandle<T>
+where
+    T: Send + 'static,
+{
+    let shared = Arc::new(Shared::default());
+    let result = Arc::new(Mutex::new(None));
+    let token = ShutdownToken(shared.clone());
+    let guard = FinishGuard(shared.clone());
+    let thread_result = result.clone();
+    let handle = std_thread::spawn(move || {
+        let _guard = guard;
+        let res = panic::catch_unwind(AssertUnwindSafe(move || function(token)));
+        *thread_result.lock() = Some(res);
+    });
+    let mut plugin = call::get_plugin();
+    plugin.threads.retain(|t| !t.is_finished());
+    plugin.threads.push(ThreadEntry {
+        shared: shared.clone(),
+        handle,
+    });
+    JoinHandle { shared, result }
+}
+
+/// Returns the number of threads that were left running.
+pub(crate) fn shutdown(threads: Vec<ThreadEntry>) -> usize {
+    for thread in &threads {
+        thread.shared.signal();
+    }
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    let mut left = 0;
+    for thread in threads {
+        if thread.shared.wait_finished(Some(deadline)) {
+            thread.handle.join().ok();
+        } else {
+            left += 1;
+        }
+    }
+    if left > 0 {
+        crate::print_plain(&format!(
+            "Warning: {} thread(s) did not stop within {} seconds of unloading, and were left \
+             running. The plugin's state was not freed, since they may still be using it.",
+            left,
+            SHUTDOWN_TIMEOUT.as_secs()
+        ));
+    }
+    left
+}
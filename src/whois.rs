@@ -0,0 +1,608 @@
+use crate::reply::{
+    ChannelEntry, ServerReply, ERR_NOSUCHNICK, RPL_AWAY, RPL_ENDOFWHOIS, RPL_WHOISACCOUNT,
+    RPL_WHOISCHANNELS, RPL_WHOISIDLE, RPL_WHOISOPERATOR, RPL_WHOISSECURE, RPL_WHOISSERVER,
+    RPL_WHOISUSER,
+};
+use crate::{
+    add_raw_server_event_listener, add_reply_listener, add_timer_once,
+    remove_raw_server_event_listener, remove_reply_listener, remove_timer_task, send_command,
+    CaseMapping, EatMode, IrcIdent, IrcIdentRef, Priority, RawServerEventListener, ReplyListener,
+    TimerTask, UserString,
+};
+use parking_lot::Mutex;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::mem;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// How long `whois` waits for the server to finish replying.
+pub const WHOIS_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The number of queries still waiting for `RPL_ENDOFWHOIS`, by server ID and folded nick. The
+// server answers queries in order, so a query's replies start after the `RPL_ENDOFWHOIS` of each
+// one sent before it. Queries that time out keep their place until then, so that the ones behind
+// them don't take their replies, and a server's queries all leave the line when it reconnects.
+static IN_LINE: Mutex<Option<HashMap<(Option<i32>, String), usize>>> = Mutex::new(None);
+
+/// Everything the server said about a user in reply to a `whois` query.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WhoisResult {
+    nick: IrcIdent,
+    user: Option<UserString>,
+    realname: Option<String>,
+    server: Option<String>,
+    server_info: Option<String>,
+    operator: bool,
+    idle: Option<chrono::Duration>,
+    channels: Vec<ChannelEntry>,
+    account: Option<IrcIdent>,
+    secure: bool,
+    away: Option<String>,
+}
+
+impl WhoisResult {
+    fn new(nick: &str) -> Self {
+        Self {
+            nick: IrcIdent(nick.to_string()),
+            user: None,
+            realname: None,
+            server: None,
+            server_info: None,
+            operator: false,
+            idle: None,
+            channels: Vec::new(),
+            account: None,
+            secure: false,
+            away: None,
+        }
+    }
+    /// The user's nick, as the server capitalizes it.
+    pub fn nick(&self) -> IrcIdentRef {
+        self.nick.as_ref()
+    }
+    /// The user's full userstring, from `RPL_WHOISUSER`.
+    pub fn user(&self) -> Option<&UserString> {
+        self.user.as_ref()
+    }
+    /// The user's real name, from `RPL_WHOISUSER`.
+    pub fn realname(&self) -> Option<&str> {
+        self.realname.as_ref().map(String::as_str)
+    }
+    /// The server the user is connected to, from `RPL_WHOISSERVER`.
+    pub fn server(&self) -> Option<&str> {
+        self.server.as_ref().map(String::as_str)
+    }
+    /// The description of the server the user is connected to, from `RPL_WHOISSERVER`.
+    pub fn server_info(&self) -> Option<&str> {
+        self.server_info.as_ref().map(String::as_str)
+    }
+    /// Whether the user is an IRC operator, from `RPL_WHOISOPERATOR`.
+    pub fn operator(&self) -> bool {
+        self.operator
+    }
+    /// How long the user has been idle for, from `RPL_WHOISIDLE`.
+    pub fn idle(&self) -> Option<chrono::Duration> {
+        self.idle
+    }
+    /// The channels the user is in, from `RPL_WHOISCHANNELS`.
+    pub fn channels(&self) -> &[ChannelEntry] {
+        &self.channels
+    }
+    /// The account the user is logged in to, from `RPL_WHOISACCOUNT`.
+    pub fn account(&self) -> Option<IrcIdentRef> {
+        self.account.as_ref().map(IrcIdent::as_ref)
+    }
+    /// Whether the user is using a secure connection, from `RPL_WHOISSECURE`.
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+    /// The user's away message, or `None` if they aren't away.
+    pub fn away_message(&self) -> Option<&str> {
+        self.away.as_ref().map(String::as_str)
+    }
+}
+
+/// The reason a `whois` query failed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum WhoisError {
+    /// The server replied with `ERR_NOSUCHNICK`.
+    NoSuchNick,
+    /// The server didn't finish replying in time.
+    TimedOut,
+}
+
+impl Display for WhoisError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            WhoisError::NoSuchNick => write!(f, "no such nick"),
+            WhoisError::TimedOut => write!(f, "WHOIS timed out"),
+        }
+    }
+}
+
+impl Error for WhoisError {}
+
+type WhoisCallback = Box<dyn FnOnce(Result<WhoisResult, WhoisError>)>;
+
+struct WhoisState {
+    nick: String,
+    casemapping: CaseMapping,
+    server_id: Option<i32>,
+    ahead: usize,
+    result: WhoisResult,
+    not_found: bool,
+    in_line: bool,
+    callback: Option<WhoisCallback>,
+    listeners: Vec<ReplyListener>,
+    welcome: Option<RawServerEventListener>,
+    timer: Option<TimerTask>,
+}
+
+impl WhoisState {
+    fn key(&self) -> (Option<i32>, String) {
+        (self.server_id, self.casemapping.to_lower(&self.nick))
+    }
+    fn is_about(&self, nick: &str) -> bool {
+        self.in_line
+            && crate::get_server_id() == self.server_id
+            && self.casemapping.equal_ignore_case(&self.nick, nick)
+    }
+    fn matches(&self, nick: &str) -> bool {
+        self.ahead == 0 && self.is_about(nick)
+    }
+    fn leave(state: &RefCell<Self>) {
+        let (listeners, welcome) = {
+            let mut state = state.borrow_mut();
+            if !state.in_line {
+                return;
+            }
+            state.in_line = false;
+            leave_line(&state.key());
+            (
+                mem::replace(&mut state.listeners, Vec::new()),
+                state.welcome.take(),
+            )
+        };
+        // This may be running inside one of the listeners, which can't be removed until it returns.
+        add_timer_once(Duration::from_millis(0), move || {
+            for listener in listeners {
+                remove_reply_listener(listener);
+            }
+            if let Some(welcome) = welcome {
+                remove_raw_server_event_listener(welcome);
+            }
+        });
+    }
+    fn finish(state: &RefCell<Self>, result: Result<WhoisResult, WhoisError>) {
+        let (callback, timer) = {
+            let mut state = state.borrow_mut();
+            (state.callback.take(), state.timer.take())
+        };
+        if let Some(timer) = timer {
+            remove_timer_task(timer);
+        }
+        if let Some(callback) = callback {
+            callback(result);
+        }
+    }
+}
+
+/// Sends a `WHOIS` query for a user to the current server, and calls the callback with everything
+/// the server replied with once `RPL_ENDOFWHOIS` is received, or with an error if the user doesn't
+/// exist or the server takes longer than `WHOIS_TIMEOUT`.
+///
+/// The replies are shown as usual; see `whois_with` to hide them. Only replies from the server the
+/// query was sent to are collected. Several queries for the same nick may be outstanding at once,
+/// and each is answered in the order they were sent.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use hexchat::{print_plain, whois};
+///
+/// whois("pie_flavor", |result| match result {
+///     Ok(whois) => match whois.account() {
+///         Some(account) => print_plain(&format!("Logged in as {}", account)),
+///         None => print_plain("Not logged in"),
+///     },
+///     Err(e) => print_plain(&format!("WHOIS failed: {}", e)),
+/// });
+/// ```
+pub fn whois(nick: &str, callback: impl FnOnce(Result<WhoisResult, WhoisError>) + 'static) {
+    whois_with(nick, EatMode::None, WHOIS_TIMEOUT, callback);
+}
+
+/// Sends a `WHOIS` query as defined in `whois`, hiding the replies according to `eat_mode`, and
+/// giving up after `timeout`.
+///
+/// Nicks are compared using the server's `CASEMAPPING`, and channel prefixes are read using its
+/// `PREFIX`. If the server was already connected when the plugin was loaded, neither is known, and
+/// the defaults of `rfc1459` and `(ov)@+` are used instead; see `ServerFeatures`.
+///
+/// A query that times out still waits for its `RPL_ENDOFWHOIS`, hiding the late replies according
+/// to `eat_mode`, so that they aren't mistaken for those of a later query for the same nick.
+///
+/// Pass `EatMode::Hexchat` to keep the replies out of the user's tab.
+pub fn whois_with(
+    nick: &str,
+    eat_mode: EatMode,
+    timeout: Duration,
+    callback: impl FnOnce(Result<WhoisResult, WhoisError>) + 'static,
+) {
+    let casemapping = crate::get_server_features()
+        .unwrap_or_default()
+        .get_casemapping();
+    let server_id = crate::get_server_id();
+    let key = (server_id, casemapping.to_lower(nick));
+    let ahead = {
+        let mut in_line = IN_LINE.lock();
+        let count = in_line
+            .get_or_insert_with(HashMap::new)
+            .entry(key)
+            .or_insert(0);
+        *count += 1;
+        *count - 1
+    };
+    let state = Rc::new(RefCell::new(WhoisState {
+        nick: nick.to_string(),
+        casemapping,
+        server_id,
+        ahead,
+        result: WhoisResult::new(nick),
+        not_found: false,
+        in_line: true,
+        callback: Some(Box::new(callback)),
+        listeners: Vec::new(),
+        welcome: None,
+        timer: None,
+    }));
+    let listeners = vec![
+        listen(&state, eat_mode, |s, r: RPL_WHOISUSER| {
+            s.result.nick = IrcIdent(r.user().get_nick().0.to_string());
+            s.result.realname = Some(r.real_name().to_string());
+            s.result.user = Some(r.user().clone());
+        }),
+        listen(&state, eat_mode, |s, r: RPL_WHOISSERVER| {
+            s.result.server = Some(r.rpl_server().0.to_string());
+            s.result.server_info = Some(r.info().to_string());
+        }),
+        listen(&state, eat_mode, |s, _: RPL_WHOISOPERATOR| {
+            s.result.operator = true;
+        }),
+        listen(&state, eat_mode, |s, r: RPL_WHOISIDLE| {
+            s.result.idle = Some(r.idle());
+        }),
+        listen(&state, eat_mode, |s, r: RPL_WHOISCHANNELS| {
+            s.result.channels.extend(r.channels().iter().cloned());
+        }),
+        listen(&state, eat_mode, |s, r: RPL_WHOISACCOUNT| {
+            s.result.account = Some(IrcIdent(r.account().0.to_string()));
+        }),
+        listen(&state, eat_mode, |s, _: RPL_WHOISSECURE| {
+            s.result.secure = true;
+        }),
+        listen(&state, eat_mode, |s, r: RPL_AWAY| {
+            s.result.away = Some(r.message().to_string());
+        }),
+        listen(&state, eat_mode, |s, _: ERR_NOSUCHNICK| {
+            s.not_found = true;
+        }),
+        {
+            let state = state.clone();
+            add_reply_listener(Priority::HIGH, move |reply: RPL_ENDOFWHOIS, _| {
+                let (state, eat_mode) = (state.clone(), eat_mode);
+                {
+                    let mut state = state.borrow_mut();
+                    if !state.is_about(reply.nick().0) {
+                        return EatMode::None;
+                    }
+                    // The end of an earlier query for the same nick.
+                    if state.ahead > 0 {
+                        state.ahead -= 1;
+                        return EatMode::None;
+                    }
+                }
+                let result = {
+                    let state = state.borrow();
+                    if state.not_found {
+                        Err(WhoisError::NoSuchNick)
+                    } else {
+                        Ok(state.result.clone())
+                    }
+                };
+                WhoisState::leave(&state);
+                WhoisState::finish(&state, result);
+                eat_mode
+            })
+        },
+    ];
+    let welcome_state = state.clone();
+    let welcome = add_raw_server_event_listener("001", Priority::HIGH, move |_, _| {
+        // A new connection won't answer anything sent over the old one.
+        if crate::get_server_id() == welcome_state.borrow().server_id {
+            WhoisState::leave(&welcome_state);
+        }
+        EatMode::None
+    });
+    let timer_state = state.clone();
+    let timer = add_timer_once(timeout, move || {
+        timer_state.borrow_mut().timer = None;
+        let result = if timer_state.borrow().not_found {
+            Err(WhoisError::NoSuchNick)
+        } else {
+            Err(WhoisError::TimedOut)
+        };
+        WhoisState::finish(&timer_state, result);
+    });
+    {
+        let mut state = state.borrow_mut();
+        state.listeners = listeners;
+        state.welcome = Some(welcome);
+        state.timer = Some(timer);
+    }
+    send_command(&format!("QUOTE WHOIS {}", nick));
+}
+
+fn leave_line(key: &(Option<i32>, String)) {
+    let mut in_line = IN_LINE.lock();
+    if let Some(in_line) = in_line.as_mut() {
+        if let Some(count) = in_line.get_mut(key) {
+            *count -= 1;
+            if *count == 0 {
+                in_line.remove(key);
+            }
+        }
+    }
+}
+
+pub(crate) fn shutdown() {
+    let in_line = IN_LINE.lock().take();
+    drop(in_line);
+}
+
+/// A reply that is part of a `WHOIS` response, about a particular nick.
+trait WhoisReply: ServerReply + 'static {
+    fn nick_of(&self) -> IrcIdentRef;
+}
+
+macro_rules! whois_reply {
+    ($($t:ident),*) => {
+        $(
+        impl WhoisReply for $t {
+            fn nick_of(&self) -> IrcIdentRef {
+                self.nick()
+            }
+        }
+        )*
+    };
+}
+
+whois_reply!(
+    RPL_WHOISSERVER,
+    RPL_WHOISOPERATOR,
+    RPL_WHOISIDLE,
+    RPL_WHOISCHANNELS,
+    RPL_WHOISACCOUNT,
+    RPL_WHOISSECURE,
+    RPL_AWAY,
+    ERR_NOSUCHNICK
+);
+
+impl WhoisReply for RPL_WHOISUSER {
+    fn nick_of(&self) -> IrcIdentRef {
+        self.user().get_nick()
+    }
+}
+
+fn listen<T>(
+    state: &Rc<RefCell<WhoisState>>,
+    eat_mode: EatMode,
+    apply: impl Fn(&mut WhoisState, T) + 'static,
+) -> ReplyListener
+where
+    T: WhoisReply,
+{
+    let state = state.clone();
+    add_reply_listener(Priority::HIGH, move |reply: T, _| {
+        let mut state = state.borrow_mut();
+        if !state.matches(reply.nick_of().0) {
+            return EatMode::None;
+        }
+        apply(&mut state, reply);
+        eat_mode
+    })
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::{MockContext, MockHexchat};
+
+    type Slot = Rc<RefCell<Option<Result<WhoisResult, WhoisError>>>>;
+
+    fn query(nick: &str, eat_mode: EatMode) -> Slot {
+        let slot = Slot::default();
+        let result = slot.clone();
+        whois_with(nick, eat_mode, WHOIS_TIMEOUT, move |r| {
+            *result.borrow_mut() = Some(r)
+        });
+        slot
+    }
+
+    #[test]
+    fn aggregation() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        mock.advance_time(Duration::from_millis(0));
+        mock.inject_server_line(
+            ":irc.example.com 005 tester PREFIX=(qaohv)~&@%+ :are supported by this server",
+        );
+        let hooks = mock.hook_count();
+        let slot = query("bob", EatMode::Hexchat);
+        assert_eq!(mock.commands(), ["QUOTE WHOIS bob"]);
+        let lines = [
+            ":irc.example.com 311 tester Bob bob host.example.com * :Bob Smith",
+            ":irc.example.com 312 tester Bob irc.example.com :Example server",
+            ":irc.example.com 319 tester Bob :~@#a &#b +#c #d",
+            ":irc.example.com 319 tester Bob :%#e &local ++chan",
+            ":irc.example.com 330 tester Bob bob_account :is logged in as",
+            ":irc.example.com 301 tester Bob :Gone",
+        ];
+        for line in &lines {
+            assert_eq!(mock.inject_server_line(line), EatMode::Hexchat);
+        }
+        let other = ":irc.example.com 312 tester alice irc.example.com :Example server";
+        assert_eq!(mock.inject_server_line(other), EatMode::None);
+        assert!(slot.borrow().is_none());
+        let end = ":irc.example.com 318 tester Bob :End of /WHOIS list.";
+        assert_eq!(mock.inject_server_line(end), EatMode::Hexchat);
+        let whois = slot.borrow_mut().take().unwrap().unwrap();
+        assert_eq!(whois.nick().0, "Bob");
+        assert_eq!(
+            whois.user().unwrap().to_string(),
+            "Bob!bob@host.example.com"
+        );
+        assert_eq!(whois.realname(), Some("Bob Smith"));
+        assert_eq!(whois.server(), Some("irc.example.com"));
+        assert_eq!(whois.account().unwrap().0, "bob_account");
+        assert_eq!(whois.away_message(), Some("Gone"));
+        assert!(!whois.operator());
+        let channels = whois
+            .channels()
+            .iter()
+            .map(|c| (c.channel().0, c.modes()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            channels,
+            [
+                ("#a", "qo"),
+                ("#b", "a"),
+                ("#c", "v"),
+                ("#d", ""),
+                ("#e", "h"),
+                ("&local", ""),
+                ("+chan", "v")
+            ]
+        );
+        assert!(whois.channels()[0].operator());
+        assert!(whois.channels()[2].voice());
+        assert_eq!(mock.inject_server_line(end), EatMode::None);
+        mock.advance_time(Duration::from_millis(0));
+        assert_eq!(mock.hook_count(), hooks);
+    }
+
+    #[test]
+    fn no_such_nick() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let slot = query("ghost", EatMode::None);
+        mock.inject_server_line(":irc.example.com 401 tester ghost :No such nick/channel");
+        assert!(slot.borrow().is_none());
+        mock.inject_server_line(":irc.example.com 318 tester ghost :End of /WHOIS list.");
+        assert_eq!(*slot.borrow(), Some(Err(WhoisError::NoSuchNick)));
+    }
+
+    #[test]
+    fn timeout() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        mock.advance_time(Duration::from_millis(0));
+        let hooks = mock.hook_count();
+        let slot = query("bob", EatMode::None);
+        mock.inject_server_line(":irc.example.com 311 tester bob bob host.example.com * :Bob");
+        mock.advance_time(WHOIS_TIMEOUT - Duration::from_millis(1));
+        assert!(slot.borrow().is_none());
+        mock.advance_time(Duration::from_millis(1));
+        assert_eq!(*slot.borrow(), Some(Err(WhoisError::TimedOut)));
+        mock.inject_server_line(":irc.example.com 318 tester bob :End of /WHOIS list.");
+        assert_eq!(*slot.borrow(), Some(Err(WhoisError::TimedOut)));
+        mock.advance_time(Duration::from_millis(0));
+        assert_eq!(mock.hook_count(), hooks);
+    }
+
+    #[test]
+    fn timeout_requery() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let first = query("bob", EatMode::None);
+        mock.advance_time(WHOIS_TIMEOUT);
+        assert_eq!(*first.borrow(), Some(Err(WhoisError::TimedOut)));
+        let second = query("bob", EatMode::None);
+        mock.inject_server_line(":irc.example.com 311 tester bob bob host.example.com * :First");
+        mock.inject_server_line(":irc.example.com 318 tester bob :End of /WHOIS list.");
+        assert!(second.borrow().is_none());
+        mock.inject_server_line(":irc.example.com 311 tester bob bob host.example.com * :Second");
+        mock.inject_server_line(":irc.example.com 318 tester bob :End of /WHOIS list.");
+        assert_eq!(
+            realname(&second).as_ref().map(String::as_str),
+            Some("Second")
+        );
+    }
+
+    #[test]
+    fn reconnect() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let first = query("bob", EatMode::None);
+        mock.inject_server_line(":irc.example.com 001 tester :Welcome");
+        let second = query("bob", EatMode::None);
+        mock.inject_server_line(":irc.example.com 311 tester bob bob host.example.com * :Second");
+        mock.inject_server_line(":irc.example.com 318 tester bob :End of /WHOIS list.");
+        assert_eq!(
+            realname(&second).as_ref().map(String::as_str),
+            Some("Second")
+        );
+        assert!(first.borrow().is_none());
+        mock.advance_time(WHOIS_TIMEOUT);
+        assert_eq!(*first.borrow(), Some(Err(WhoisError::TimedOut)));
+    }
+
+    fn realname(slot: &Slot) -> Option<String> {
+        let result = slot.borrow_mut().take()?;
+        result.unwrap().realname().map(str::to_string)
+    }
+
+    #[test]
+    fn other_server() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let first_server = mock.get_current_context();
+        let second_server = mock.add_context(MockContext {
+            server: "irc.example.org".to_string(),
+            server_id: 2,
+            ..MockContext::default()
+        });
+        let slot = query("bob", EatMode::Hexchat);
+        mock.set_current_context(&second_server);
+        let line = ":irc.example.org 311 tester bob bob other.example.org * :Other Bob";
+        assert_eq!(mock.inject_server_line(line), EatMode::None);
+        let end = ":irc.example.org 318 tester bob :End of /WHOIS list.";
+        assert_eq!(mock.inject_server_line(end), EatMode::None);
+        assert!(slot.borrow().is_none());
+        mock.set_current_context(&first_server);
+        mock.inject_server_line(":irc.example.com 311 tester bob bob host.example.com * :Bob");
+        mock.inject_server_line(":irc.example.com 318 tester bob :End of /WHOIS list.");
+        assert_eq!(realname(&slot).as_ref().map(String::as_str), Some("Bob"));
+    }
+
+    #[test]
+    fn same_nick() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let first = query("bob", EatMode::None);
+        let second = query("Bob", EatMode::None);
+        mock.inject_server_line(":irc.example.com 311 tester bob bob host.example.com * :First");
+        mock.inject_server_line(":irc.example.com 318 tester bob :End of /WHOIS list.");
+        assert_eq!(realname(&first).as_ref().map(String::as_str), Some("First"));
+        assert!(second.borrow().is_none());
+        mock.inject_server_line(":irc.example.com 311 tester bob bob host.example.com * :Second");
+        mock.inject_server_line(":irc.example.com 318 tester bob :End of /WHOIS list.");
+        assert_eq!(
+            realname(&second).as_ref().map(String::as_str),
+            Some("Second")
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use crate::reply::{WhoxEntry, WhoxFields, RPL_ENDOFWHO, RPL_WHOSPCRPL};
+use crate::{
+    add_raw_server_event_listener, add_reply_listener, send_command, EatMode, Hook, HookGuard,
+    Priority, RawServerEventListener, ReplyListener, ServerEventListener,
+};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+static PENDING: Mutex<PendingSlot> = Mutex::new(PendingSlot(None));
+
+struct PendingSlot(Option<Pending>);
+// The queries are only ever touched from the main thread.
+unsafe impl Send for PendingSlot {}
+
+// The queries still waiting for `RPL_ENDOFWHO`, by server ID and lowercased mask. The server
+// answers queries in order, so each `RPL_ENDOFWHO` ends the first query in line for its mask.
+// Dropped queries keep their place until then, so that the ones behind them don't end early.
+struct Pending {
+    queries: HashMap<(Option<i32>, String), VecDeque<Rc<WhoxState>>>,
+    welcome: RawServerEventListener,
+    end: ReplyListener,
+}
+
+#[derive(Default)]
+struct WhoxState {
+    entries: RefCell<Vec<WhoxEntry>>,
+    done: Cell<bool>,
+    waker: RefCell<Option<Waker>>,
+}
+
+/// A future returned by `send_whox`, which completes with every user the query matched.
+pub struct Whox {
+    state: Rc<WhoxState>,
+    _entries: HookGuard<ServerEventListener>,
+}
+
+impl Drop for Whox {
+    fn drop(&mut self) {
+        self.state.waker.borrow_mut().take();
+    }
+}
+
+impl Future for Whox {
+    type Output = Vec<WhoxEntry>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if self.state.done.get() {
+            Poll::Ready(mem::replace(
+                &mut *self.state.entries.borrow_mut(),
+                Vec::new(),
+            ))
+        } else {
+            *self.state.waker.borrow_mut() = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Sends a WHOX query to the current server, i.e. `WHO <mask> %<fields>,<query_token>`, and returns
+/// a future that completes with the matched users once `RPL_ENDOFWHO` is received.
+///
+/// Only the `RPL_WHOSPCRPL` replies carrying `query_token` are collected, so pick one that no
+/// other query is using. The `TOKEN` field is always requested. The collected replies and the
+/// `RPL_ENDOFWHO` are hidden from HexChat. The listener for the replies is added immediately, and
+/// is removed when the future is dropped; use `timeout` in case the server doesn't support WHOX.
+///
+/// Several queries for the same mask may be outstanding at once, on the same server or different
+/// ones, and each completes at its own `RPL_ENDOFWHO`, even if one sent before it was dropped. If
+/// the server reconnects before answering, the query completes early with the users collected so
+/// far.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use hexchat::reply::WhoxFields;
+/// use hexchat::{print_plain, send_whox, spawn_local};
+///
+/// spawn_local(async {
+///     let entries = send_whox("#rust", WhoxFields::NICK | WhoxFields::ACCOUNT, 42).await;
+///     for entry in entries {
+///         if let (Some(nick), Some(account)) = (entry.nick(), entry.account()) {
+///             print_plain(&format!("{} is logged in as {}", nick, account));
+///         }
+///     }
+/// });
+/// ```
+///
+/// # Panics
+///
+/// Panics if `query_token` is more than 999, the longest token WHOX allows.
+pub fn send_whox(mask: &str, fields: WhoxFields, query_token: u16) -> Whox {
+    assert!(query_token <= 999, "WHOX query tokens can be at most 999");
+    let fields = fields | WhoxFields::TOKEN;
+    let token = query_token.to_string();
+    let state = Rc::new(WhoxState::default());
+    let server_id = crate::get_server_id();
+    PENDING
+        .lock()
+        .0
+        .get_or_insert_with(|| Pending {
+            queries: HashMap::new(),
+            welcome: add_raw_server_event_listener("001", Priority::HIGHEST, on_welcome),
+            end: add_reply_listener(Priority::NORMAL, on_end),
+        })
+        .queries
+        .entry((server_id, mask.to_ascii_lowercase()))
+        .or_insert_with(VecDeque::new)
+        .push_back(state.clone());
+    let entries_state = state.clone();
+    let entries = add_reply_listener(Priority::NORMAL, move |reply: RPL_WHOSPCRPL, _| {
+        if entries_state.done.get() || crate::get_server_id() != server_id {
+            return EatMode::None;
+        }
+        match reply.parse(fields) {
+            Some(entry) if entry.token() == Some(&*token) => {
+                entries_state.entries.borrow_mut().push(entry);
+                EatMode::Hexchat
+            }
+            _ => EatMode::None,
+        }
+    });
+    send_command(&format!(
+        "QUOTE WHO {} %{},{}",
+        mask,
+        fields.letters(),
+        query_token
+    ));
+    Whox {
+        state,
+        _entries: ServerEventListener(entries.0, entries.1).scoped(),
+    }
+}
+
+fn on_end(reply: RPL_ENDOFWHO, _: DateTime<Utc>) -> EatMode {
+    let key = (crate::get_server_id(), reply.query().to_ascii_lowercase());
+    let state = {
+        let mut pending = PENDING.lock();
+        let queries = match pending.0.as_mut() {
+            Some(pending) => &mut pending.queries,
+            None => return EatMode::None,
+        };
+        let state = queries.get_mut(&key).and_then(VecDeque::pop_front);
+        if queries.get(&key).map_or(false, VecDeque::is_empty) {
+            queries.remove(&key);
+        }
+        state
+    };
+    match state {
+        Some(state) => {
+            finish(&state);
+            EatMode::Hexchat
+        }
+        None => EatMode::None,
+    }
+}
+
+// A new connection won't answer anything sent over the old one, so its queries end with whatever
+// they have collected.
+fn on_welcome(_: &[String], _: DateTime<Utc>) -> EatMode {
+    let server_id = crate::get_server_id();
+    let mut ended = Vec::new();
+    if let Some(pending) = PENDING.lock().0.as_mut() {
+        pending.queries.retain(|(id, _), states| {
+            if *id == server_id {
+                ended.extend(states.drain(..));
+                false
+            } else {
+                true
+            }
+        });
+    }
+    for state in ended {
+        finish(&state);
+    }
+    EatMode::None
+}
+
+// Called without `PENDING` locked, since waking may poll the future right away.
+fn finish(state: &WhoxState) {
+    state.done.set(true);
+    let waker = state.waker.borrow_mut().take();
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+/// Gets whether a hook is one of the listeners shared by all queries.
+#[cfg(any(feature = "diagnostics", feature = "testing"))]
+pub(crate) fn is_internal(hook: *mut crate::c::hexchat_hook) -> bool {
+    PENDING.lock().0.as_ref().map_or(false, |pending| {
+        hook == pending.welcome.0 || hook == pending.end.0
+    })
+}
+
+// Called after the plugin's hooks, including the shared listeners, have been removed.
+pub(crate) fn shutdown() {
+    let pending = PENDING.lock().0.take();
+    drop(pending);
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::{MockContext, MockHexchat};
+    use std::ptr;
+    use std::task::{RawWaker, RawWakerVTable};
+
+    fn poll(whox: &mut Whox) -> Poll<Vec<WhoxEntry>> {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        let waker = unsafe { Waker::from_raw(clone(ptr::null())) };
+        Pin::new(whox).poll(&mut Context::from_waker(&waker))
+    }
+
+    fn nicks(poll: Poll<Vec<WhoxEntry>>) -> Vec<String> {
+        match poll {
+            Poll::Ready(entries) => entries
+                .iter()
+                .map(|e| e.nick().unwrap().0.to_string())
+                .collect(),
+            Poll::Pending => panic!("query still pending"),
+        }
+    }
+
+    #[test]
+    fn tokens() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let mut whox = send_whox("#test", WhoxFields::NICK, 42);
+        assert_eq!(mock.commands(), ["QUOTE WHO #test %tn,42"]);
+        let eat = mock.inject_server_line(":irc.example.com 354 tester 42 alice");
+        assert_eq!(eat, EatMode::Hexchat);
+        let eat = mock.inject_server_line(":irc.example.com 354 tester 7 bob");
+        assert_eq!(eat, EatMode::None);
+        assert!(poll(&mut whox).is_pending());
+        let eat = mock.inject_server_line(":irc.example.com 315 tester #other :End of /WHO list.");
+        assert_eq!(eat, EatMode::None);
+        let eat = mock.inject_server_line(":irc.example.com 315 tester #TEST :End of /WHO list.");
+        assert_eq!(eat, EatMode::Hexchat);
+        assert_eq!(nicks(poll(&mut whox)), ["alice"]);
+    }
+
+    #[test]
+    fn same_mask() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let mut first = send_whox("#test", WhoxFields::NICK, 1);
+        let mut second = send_whox("#test", WhoxFields::NICK, 2);
+        mock.inject_server_line(":irc.example.com 354 tester 1 alice");
+        mock.inject_server_line(":irc.example.com 315 tester #test :End of /WHO list.");
+        assert_eq!(nicks(poll(&mut first)), ["alice"]);
+        assert!(poll(&mut second).is_pending());
+        mock.inject_server_line(":irc.example.com 354 tester 2 bob");
+        mock.inject_server_line(":irc.example.com 315 tester #test :End of /WHO list.");
+        assert_eq!(nicks(poll(&mut second)), ["bob"]);
+        let mut third = send_whox("#test", WhoxFields::NICK, 3);
+        mock.inject_server_line(":irc.example.com 315 tester #test :End of /WHO list.");
+        assert!(nicks(poll(&mut third)).is_empty());
+    }
+
+    #[test]
+    fn dropped() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let first = send_whox("#test", WhoxFields::NICK, 1);
+        drop(first);
+        let mut second = send_whox("#test", WhoxFields::NICK, 2);
+        let eat = mock.inject_server_line(":irc.example.com 315 tester #test :End of /WHO list.");
+        assert_eq!(eat, EatMode::Hexchat);
+        assert!(poll(&mut second).is_pending());
+        mock.inject_server_line(":irc.example.com 354 tester 2 bob");
+        mock.inject_server_line(":irc.example.com 315 tester #test :End of /WHO list.");
+        assert_eq!(nicks(poll(&mut second)), ["bob"]);
+    }
+
+    #[test]
+    fn servers() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        let first_server = mock.get_current_context();
+        let second_server = mock.add_context(MockContext {
+            server: "irc.example.org".to_string(),
+            server_id: 2,
+            ..MockContext::default()
+        });
+        let mut first = send_whox("#test", WhoxFields::NICK, 1);
+        mock.set_current_context(&second_server);
+        let mut second = send_whox("#test", WhoxFields::NICK, 1);
+        mock.inject_server_line(":irc.example.org 354 tester 1 bob");
+        mock.inject_server_line(":irc.example.org 315 tester #test :End of /WHO list.");
+        assert_eq!(nicks(poll(&mut second)), ["bob"]);
+        assert!(poll(&mut first).is_pending());
+        mock.set_current_context(&first_server);
+        mock.inject_server_line(":irc.example.com 354 tester 1 alice");
+        mock.inject_server_line(":irc.example.com 315 tester #test :End of /WHO list.");
+        assert_eq!(nicks(poll(&mut first)), ["alice"]);
+    }
+
+    #[test]
+    fn reconnect() {
+        let mut mock = MockHexchat::new();
+        assert!(mock.load_default());
+        drop(send_whox("#test", WhoxFields::NICK, 1));
+        let mut held = send_whox("#other", WhoxFields::NICK, 3);
+        mock.inject_server_line(":irc.example.com 354 tester 3 alice");
+        assert!(poll(&mut held).is_pending());
+        mock.inject_server_line(":irc.example.com 001 tester :Welcome");
+        assert_eq!(nicks(poll(&mut held)), ["alice"]);
+        let mut second = send_whox("#test", WhoxFields::NICK, 2);
+        mock.inject_server_line(":irc.example.com 315 tester #test :End of /WHO list.");
+        assert!(nicks(poll(&mut second)).is_empty());
+    }
+}